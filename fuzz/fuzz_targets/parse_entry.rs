@@ -0,0 +1,29 @@
+//! Feed arbitrary bytes to the entry parser and everything that reads a
+//! parsed entry. Any panic is a bug: malformed entries should only ever
+//! surface as `frontmatter_err`.
+//!
+//!     cargo +nightly fuzz run parse_entry
+//!
+#![no_main]
+use std::path::Path;
+
+use libfuzzer_sys::fuzz_target;
+
+use jrni::{footnotes, Entry};
+
+fuzz_target!(|data: &[u8]| {
+    let raw = String::from_utf8_lossy(data);
+    let e = Entry::parse(Path::new("2024-05-10-fuzz.md"), &raw);
+
+    for key in e.frontmatter.keys() {
+        let _ = e.frontmatter.get(&key);
+    }
+    let _ = e.get_tags();
+    let _ = e.date();
+    let _ = e.expires();
+    let _ = e.habits();
+    let _ = e.headings();
+    let _ = e.task_progress();
+    let _ = footnotes::check(&e.body);
+    let _ = footnotes::renumber(&e.body);
+});
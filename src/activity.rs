@@ -0,0 +1,243 @@
+//! An append-only audit trail of changes to entries, kept in
+//! `.jrni/activity.log`, so there's some history even for journals that
+//! aren't in version control.
+//!
+//! Changes are detected by comparing the journal against a snapshot of
+//! content hashes in `.jrni/state` whenever `record_changes` runs. Creations
+//! and edits are dated by file mtime; deletions by when they were noticed.
+//!
+//! Optionally, edited entries are stamped with a `modified:` frontmatter
+//! field, which survives sync tools that don't preserve mtimes, and new and
+//! edited entries with `words:` and `reading_time:` fields, so tools reading
+//! the files directly can see them.
+//!
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use chrono::{DateTime, Local};
+
+use crate::datetime;
+use crate::error::Result;
+use crate::filedb::{self, content_hash, walk_journal, Entry};
+
+
+pub const STATE_DIR: &str = ".jrni";
+const LOG_FILE: &str = "activity.log";
+const SNAPSHOT_FILE: &str = "state";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventKind {
+    Created,
+    Modified,
+    Deleted,
+}
+
+impl EventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::Created => "created",
+            EventKind::Modified => "modified",
+            EventKind::Deleted => "deleted",
+        }
+    }
+
+    fn parse(s: &str) -> Option<EventKind> {
+        match s {
+            "created" => Some(EventKind::Created),
+            "modified" => Some(EventKind::Modified),
+            "deleted" => Some(EventKind::Deleted),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Event {
+    pub when: DateTime<Local>,
+    pub kind: EventKind,
+    /// Path of the entry, relative to the journal.
+    pub path: String,
+}
+
+/// Which fields `record_changes` keeps up to date on the entries it finds
+/// have changed.
+///
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Stamps {
+    /// `modified:`, set to when an entry was edited.
+    pub modified: bool,
+    /// `words:` and `reading_time:` (in minutes), from the body.
+    pub stats: bool,
+}
+
+pub fn state_dir(jrnl_path: &Path) -> PathBuf {
+    jrnl_path.join(STATE_DIR)
+}
+
+/// Compare the journal against the last snapshot, append any changes to the
+/// activity log, and return them. Entries that were created or edited are
+/// also stamped with the fields in `stamps`.
+///
+pub fn record_changes(jrnl_path: &Path, stamps: Stamps) -> Result<Vec<Event>> {
+    let previous = read_snapshot(jrnl_path)?;
+    let root = jrnl_path.to_owned();
+    let mut current: HashMap<String, (SystemTime, u64)> = walk_journal(
+        jrnl_path.to_str().unwrap(),
+        move |p| {
+            let rel = p.strip_prefix(&root).unwrap_or(&p)
+                .to_string_lossy().into_owned();
+            Ok((rel, (fs::metadata(&p)?.modified()?, content_hash(&fs::read(&p)?))))
+        })
+        .into_iter()
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let mut events = Vec::new();
+    for (path, (mtime, hash)) in current.iter() {
+        let kind = match previous.get(path) {
+            None => EventKind::Created,
+            Some(old) if old != hash => EventKind::Modified,
+            Some(_) => continue,
+        };
+        events.push(Event { when: DateTime::from(*mtime), kind, path: path.clone() });
+    }
+    let now = Local::now();
+    // A file that couldn't be read this time isn't gone; it's kept as it
+    // was, so it isn't taken for created later either.
+    for (path, hash) in previous.iter() {
+        if !current.contains_key(path) && jrnl_path.join(path).exists() {
+            current.insert(path.clone(), (SystemTime::UNIX_EPOCH, *hash));
+        }
+    }
+    for path in previous.keys().filter(|p| !current.contains_key(*p)) {
+        events.push(Event { when: now, kind: EventKind::Deleted, path: path.clone() });
+    }
+    events.sort_by(|a, b| a.when.cmp(&b.when).then(a.path.cmp(&b.path)));
+
+    for e in events.iter().filter(|e| e.kind != EventKind::Deleted) {
+        let path = jrnl_path.join(&e.path);
+        let mut stamped = false;
+        if stamps.modified && e.kind == EventKind::Modified {
+            filedb::set_field(&path, "modified", &datetime::to_str(e.when))?;
+            stamped = true;
+        }
+        if stamps.stats {
+            stamped |= stamp_stats(&path)?;
+        }
+        if stamped {
+            // Snapshot the stamped file so the stamp itself isn't an edit.
+            let stamped = (fs::metadata(&path)?.modified()?, content_hash(&fs::read(&path)?));
+            current.insert(e.path.clone(), stamped);
+        }
+    }
+
+    fs::create_dir_all(state_dir(jrnl_path))?;
+    let mut log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(state_dir(jrnl_path).join(LOG_FILE))?;
+    for e in events.iter() {
+        writeln!(log, "{}\t{}\t{}", e.when.to_rfc3339(), e.kind.as_str(), e.path)?;
+    }
+
+    let snapshot: String = current.iter()
+        .map(|(path, (_, hash))| format!("{}\t{:016x}\n", path, hash))
+        .collect();
+    fs::write(state_dir(jrnl_path).join(SNAPSHOT_FILE), snapshot)?;
+
+    Ok(events)
+}
+
+/// Set the `words:` and `reading_time:` fields of the entry at `path` from
+/// its body, unless they're right already. Returns whether it was changed.
+/// Encrypted entries are left alone, as their words can't be counted.
+///
+fn stamp_stats(path: &Path) -> Result<bool> {
+    let e = Entry::from_path(path)?;
+    if e.is_encrypted || e.frontmatter_err.is_some() {
+        return Ok(false);
+    }
+    let mut changed = false;
+    for (key, value) in [("words", e.word_count()), ("reading_time", e.reading_time())].iter() {
+        let current = e.frontmatter.get(key).map(|v| filedb::inline_value(&v));
+        if current.as_deref() != Some(value.to_string().as_str()) {
+            filedb::set_number(path, key, *value)?;
+            changed = true;
+        }
+    }
+    Ok(changed)
+}
+
+/// Every event in the activity log, oldest first.
+///
+pub fn read_log(jrnl_path: &Path) -> Result<Vec<Event>> {
+    let path = state_dir(jrnl_path).join(LOG_FILE);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut events = Vec::new();
+    for line in fs::read_to_string(path)?.lines() {
+        let fields: Vec<&str> = line.splitn(3, '\t').collect();
+        if fields.len() != 3 {
+            continue;
+        }
+        let when = match DateTime::parse_from_rfc3339(fields[0]) {
+            Ok(w) => w.with_timezone(&Local),
+            Err(_) => continue,
+        };
+        if let Some(kind) = EventKind::parse(fields[1]) {
+            events.push(Event { when, kind, path: fields[2].to_owned() });
+        }
+    }
+    Ok(events)
+}
+
+fn read_snapshot(jrnl_path: &Path) -> Result<HashMap<String, u64>> {
+    let path = state_dir(jrnl_path).join(SNAPSHOT_FILE);
+    let mut snapshot = HashMap::new();
+    if !path.exists() {
+        return Ok(snapshot);
+    }
+
+    for line in fs::read_to_string(path)?.lines() {
+        let mut fields = line.rsplitn(2, '\t');
+        let hash = fields.next().and_then(|h| u64::from_str_radix(h, 16).ok());
+        if let (Some(hash), Some(path)) = (hash, fields.next()) {
+            snapshot.insert(path.to_owned(), hash);
+        }
+    }
+    Ok(snapshot)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stamp_stats_keeps_frontmatter_as_the_body_grows() {
+        let dir = std::env::temp_dir().join(format!("jrni-stamp-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("entry.md");
+        fs::write(&path, "tags: a\n---\n\nshort\n").unwrap();
+        assert!(stamp_stats(&path).unwrap());
+
+        // reading_time is now the last key, and changes with the body.
+        let grown = fs::read_to_string(&path).unwrap() + &"word ".repeat(600);
+        fs::write(&path, grown).unwrap();
+        assert!(stamp_stats(&path).unwrap());
+        assert!(!stamp_stats(&path).unwrap());
+
+        let e = Entry::from_path(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(e.frontmatter_err.is_none());
+        assert_eq!(e.get_tags(), Some(vec!["a"]));
+        assert_eq!(e.frontmatter.get("words").map(|v| filedb::inline_value(&v)),
+                   Some("601".to_owned()));
+        assert!(!e.body.contains("reading_time"));
+    }
+}
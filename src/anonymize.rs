@@ -0,0 +1,224 @@
+//! Scrubbing personal details out of entries before they're exported, per a
+//! substitution map in the config:
+//!
+//! ```toml
+//! [anonymize]
+//! "Alice Smith" = "A."
+//! "alice@example.com" = "someone@example.com"
+//! "Project Falcon" = "the project"
+//! ```
+//!
+//! Matches ignore case and only count as whole words, so "Al" doesn't
+//! touch "Alice". Where names overlap, the longest wins. Substitutions
+//! apply to the body and to every string in the frontmatter, ids and tags
+//! included.
+//!
+//! Exports that copy images can have links to images in the journal left as
+//! they are, so they can still find them; they're exported under names made
+//! from their contents (see `export::thumbnails`), so their own names don't
+//! get out. Nor do entries' filenames, where they'd stand in for an id or a
+//! title.
+//!
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use regex::{Captures, Regex, RegexBuilder};
+use serde_yaml::Value as YValue;
+
+use crate::assets;
+use crate::error::Result;
+use crate::filedb::{self, Entry};
+
+
+pub struct Anonymizer {
+    /// None when there's nothing to substitute. Each name is a group.
+    pattern: Option<Regex>,
+    /// The stand-in for each group of `pattern`, in order.
+    replacements: Vec<String>,
+    /// The journal whose images links are kept to, if they are.
+    jrnl_path: Option<PathBuf>,
+    images: Regex,
+}
+
+impl Anonymizer {
+    /// An anonymizer, which with `jrnl_path` leaves links to images in that
+    /// journal alone.
+    ///
+    pub fn new(substitutions: &HashMap<String, String>, jrnl_path: Option<&Path>) -> Result<Anonymizer> {
+        let mut names: Vec<(&String, &String)> = substitutions.iter()
+            .filter(|(k, _)| !k.trim().is_empty())
+            .collect();
+        names.sort_by(|a, b| b.0.len().cmp(&a.0.len()).then(a.0.cmp(b.0)));
+
+        let alternatives: Vec<String> = names.iter().map(|(n, _)| {
+            let n = n.trim();
+            let start = if n.starts_with(is_word_char) { r"\b" } else { "" };
+            let end = if n.ends_with(is_word_char) { r"\b" } else { "" };
+            format!("({}{}{})", start, regex::escape(n), end)
+        }).collect();
+        let pattern = match alternatives.is_empty() {
+            true => None,
+            false => Some(RegexBuilder::new(&alternatives.join("|")).case_insensitive(true).build()?),
+        };
+        Ok(Anonymizer {
+            pattern,
+            replacements: names.into_iter().map(|(_, v)| v.clone()).collect(),
+            jrnl_path: jrnl_path.map(Path::to_owned),
+            images: Regex::new(r"!\[([^\]]*)\]\(([^)\s]+)\)").unwrap(),
+        })
+    }
+
+    pub fn text(&self, text: &str) -> String {
+        match self.pattern {
+            Some(ref p) => p.replace_all(text, |c: &Captures| {
+                // Whichever name matched; there's always one.
+                (1..c.len()).find(|i| c.get(*i).is_some())
+                    .map_or_else(|| c[0].to_owned(), |i| self.replacements[i - 1].clone())
+            }).into_owned(),
+            None => text.to_owned(),
+        }
+    }
+
+    /// `entries` anonymized (see `entry`), with ids that became the same
+    /// as another's numbered, as `filedb::unique_id` does.
+    ///
+    pub fn entries(&self, entries: Vec<Entry>) -> Vec<Entry> {
+        let mut taken = HashSet::new();
+        entries.into_iter().map(|e| {
+            let mut e = self.entry(e);
+            if let Some(id) = e.frontmatter.id.take() {
+                let id = filedb::unique_id(&id, &taken);
+                taken.insert(id.clone());
+                e.frontmatter.id = Some(id);
+            }
+            e
+        }).collect()
+    }
+
+    /// `e` with its body and frontmatter anonymized. An entry without an
+    /// id, or a title other than its filename, is given one from its
+    /// filename, anonymized, so that nothing naming it (a page's URL, say)
+    /// falls back on the original filename.
+    ///
+    pub fn entry(&self, mut e: Entry) -> Entry {
+        let stem = e.path.file_stem().map(|s| s.to_string_lossy().into_owned());
+        if e.frontmatter.title.is_none() && Some(e.title()) == stem.as_deref() {
+            e.frontmatter.title = stem.clone();
+        }
+        e.body = self.body(&e);
+        let fm = &mut e.frontmatter;
+        if fm.id.is_none() {
+            fm.id = stem;
+        }
+        fm.id = fm.id.as_ref().map(|id| self.text(id));
+        fm.title = fm.title.as_ref().map(|t| self.text(t));
+        fm.tags = fm.tags.iter().map(|t| self.text(t)).collect();
+        for v in fm.extra.values_mut() {
+            self.value(v);
+        }
+        e
+    }
+
+    /// The body of `e` anonymized, less the targets of links to images in
+    /// the journal if they're kept.
+    ///
+    fn body(&self, e: &Entry) -> String {
+        let jrnl_path = match self.jrnl_path {
+            Some(ref p) => p,
+            None => return self.text(&e.body),
+        };
+        let mut out = String::new();
+        let mut last = 0;
+        for caps in self.images.captures_iter(&e.body) {
+            let whole = caps.get(0).unwrap();
+            let target = &caps[2];
+            let local = !target.contains("://") && assets::is_image(target)
+                && e.resolve_link(jrnl_path, target).is_some();
+            if !local {
+                continue;
+            }
+            out.push_str(&self.text(&e.body[last..whole.start()]));
+            out.push_str(&format!("![{}]({})", self.text(&caps[1]), target));
+            last = whole.end();
+        }
+        out.push_str(&self.text(&e.body[last..]));
+        out
+    }
+
+    fn value(&self, v: &mut YValue) {
+        match v {
+            YValue::String(s) => *s = self.text(s),
+            YValue::Sequence(items) => items.iter_mut().for_each(|i| self.value(i)),
+            YValue::Mapping(m) => m.iter_mut().for_each(|(_, i)| self.value(i)),
+            _ => (),
+        }
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn anonymizer(jrnl_path: Option<&Path>) -> Anonymizer {
+        let subs: HashMap<String, String> = [
+            ("Alice", "A."),
+            ("Alice Smith", "A. S."),
+            ("alice@example.com", "someone@example.com"),
+            ("Falcon", "the project"),
+        ].iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        Anonymizer::new(&subs, jrnl_path).unwrap()
+    }
+
+    #[test]
+    fn whole_names_are_replaced_longest_first() {
+        let a = anonymizer(None);
+        assert_eq!(a.text("Met ALICE SMITH and alice, not Alicent."),
+                   "Met A. S. and A., not Alicent.");
+        assert_eq!(a.text("mail alice@example.com re falcon"),
+                   "mail someone@example.com re the project");
+        assert_eq!(Anonymizer::new(&HashMap::new(), None).unwrap().text("Alice"), "Alice");
+    }
+
+    #[test]
+    fn frontmatter_is_anonymized_and_ids_kept_unique() {
+        let a = anonymizer(None);
+        let raw = |id: &str| format!(
+            "tags: falcon,people/alice\nid: {}\nwith:\n- Alice Smith\n---\n\nAlice said hi.\n", id);
+        let entries = a.entries(vec![
+            Entry::parse(Path::new("/j/2024-05-10-alice.md"), &raw("alice")),
+            Entry::parse(Path::new("/j/2024-05-11-a.md"), &raw("A.")),
+            Entry::parse(Path::new("/j/2024-05-12-falcon.md"), "Launched Falcon.\n"),
+        ]);
+        assert_eq!(entries[0].get_id(), Some("A."));
+        assert_eq!(entries[1].get_id(), Some("A.-2"));
+        assert_eq!(entries[0].frontmatter.tags, vec!["the project", "people/A."]);
+        assert_eq!(entries[0].frontmatter.extra["with"][0].as_str(), Some("A. S."));
+        assert_eq!(entries[0].body.trim(), "A. said hi.");
+        // Named from their filenames, anonymized, rather than falling back
+        // on them.
+        assert_eq!(entries[2].get_id(), Some("2024-05-12-the project"));
+        assert_eq!(entries[2].title(), "2024-05-12-the project");
+    }
+
+    #[test]
+    fn links_to_journal_images_are_kept() {
+        let dir = std::env::temp_dir().join(format!("jrni-anonymize-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("alice.png"), b"").unwrap();
+        let body = "![Alice](alice.png) ![Alice](elsewhere/alice.png) [Alice](alice.png)\n";
+        let e = Entry::parse(&dir.join("e.md"), body);
+
+        let kept = anonymizer(Some(&dir)).entry(Entry::parse(&e.path, body));
+        assert_eq!(kept.body.trim(), "![A.](alice.png) ![A.](elsewhere/A..png) [A.](A..png)");
+        let scrubbed = anonymizer(None).entry(e);
+        assert_eq!(scrubbed.body.trim(), "![A.](A..png) ![A.](elsewhere/A..png) [A.](A..png)");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,64 @@
+//! Archived entries: kept in the journal, but left out of listings, search
+//! and tag counts unless asked for with `--all`.
+//!
+//! An entry is archived by moving it under `archive/`, keeping its place in
+//! the journal's layout, so `2024/05/standup.md` becomes
+//! `archive/2024/05/standup.md`. Entries marked `archived: true` in their
+//! frontmatter count as archived wherever they are.
+//!
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+use crate::filedb::Entry;
+
+
+/// Directory, under the journal, that archived entries are moved into.
+pub const ARCHIVE_DIR: &str = "archive";
+
+/// Move the entry at `path` under `archive/`, returning its new path.
+///
+pub fn archive(jrnl_path: &Path, path: &Path) -> Result<PathBuf> {
+    let rel = match path.strip_prefix(jrnl_path) {
+        Ok(rel) => rel,
+        Err(_) => bail!("{} isn't in the journal", path.display()),
+    };
+    if rel.starts_with(ARCHIVE_DIR) {
+        bail!("{} is already archived", path.display());
+    }
+    move_to(path, &jrnl_path.join(ARCHIVE_DIR).join(rel))
+}
+
+/// Move the archived entry at `path` back to where it was before it was
+/// archived, returning its new path.
+///
+pub fn unarchive(jrnl_path: &Path, path: &Path) -> Result<PathBuf> {
+    let rel = match path.strip_prefix(jrnl_path.join(ARCHIVE_DIR)) {
+        Ok(rel) => rel,
+        Err(_) => bail!("{} isn't under {}/", path.display(), ARCHIVE_DIR),
+    };
+    move_to(path, &jrnl_path.join(rel))
+}
+
+fn move_to(path: &Path, dest: &Path) -> Result<PathBuf> {
+    if dest.exists() {
+        bail!("{} is in the way", dest.display());
+    }
+    fs::create_dir_all(dest.parent().unwrap())?;
+    fs::rename(path, dest)?;
+    Ok(dest.to_owned())
+}
+
+/// Whether `e` is archived, by being under `archive/` or by its
+/// frontmatter.
+///
+pub fn is_archived(jrnl_path: &Path, e: &Entry) -> bool {
+    is_under_archive(jrnl_path, &e.path)
+        || e.frontmatter.extra.get("archived").and_then(|v| v.as_bool()) == Some(true)
+}
+
+/// Whether `path` is under `archive/`.
+///
+pub fn is_under_archive(jrnl_path: &Path, path: &Path) -> bool {
+    path.strip_prefix(jrnl_path).is_ok_and(|rel| rel.starts_with(ARCHIVE_DIR))
+}
@@ -0,0 +1,198 @@
+//! A content-addressed store for attachments, so that a file attached to
+//! several entries (or attached twice) is only kept once.
+//!
+//! Files live at `assets/<prefix>/<hash>.<ext>` under the journal, where
+//! `hash` is the SHA-256 of the file and `prefix` its first two digits.
+//! (Stores from before used a shorter hash; files named by it still count
+//! as stored.)
+//! `assets/index` maps each stored file back to the names it was attached
+//! under, since the store itself forgets them.
+//!
+//! Attachments from before the store, at `assets/<entry>/<name>`, can be
+//! moved into it with `migrate`, which also rewrites links to them.
+//!
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use walkdir::WalkDir;
+
+use crate::error::Result;
+use crate::filedb::{self, Entry};
+
+
+pub const ASSETS_DIR: &str = "assets";
+const INDEX_FILE: &str = "index";
+
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "gif", "webp"];
+
+/// Copy `file` into the store, unless an identical file is already there,
+/// and return its path relative to the journal root, suitable for linking
+/// from an entry body.
+///
+pub fn store(jrnl_path: &Path, file: &Path) -> Result<String> {
+    let name = match file.file_name() {
+        Some(f) => f.to_string_lossy().into_owned(),
+        None => bail!("can't attach {}: not a file", file.display()),
+    };
+    let rel = stored_path(&filedb::digest(&fs::read(file)?), file);
+    let dest = jrnl_path.join(&rel);
+
+    if !dest.exists() {
+        fs::create_dir_all(dest.parent().unwrap())?;
+        fs::copy(file, &dest)?;
+    }
+    let rel = rel.to_string_lossy().into_owned();
+    record_name(jrnl_path, &rel, &name)?;
+    Ok(rel)
+}
+
+/// A move of an attachment into the store.
+///
+#[derive(Debug)]
+pub struct Move {
+    /// Where it is now, relative to the journal root.
+    pub from: String,
+    /// Where it belongs in the store.
+    pub to: String,
+}
+
+/// Attachments under `assets/` that aren't in the store yet, and where they
+/// would go. Several may go to the same place if they're identical.
+///
+pub fn unstored(jrnl_path: &Path) -> Result<Vec<Move>> {
+    let mut moves = Vec::new();
+    if !jrnl_path.join(ASSETS_DIR).is_dir() {
+        return Ok(moves);
+    }
+    let walker = WalkDir::new(jrnl_path.join(ASSETS_DIR))
+        .sort_by(|a, b| a.path().cmp(b.path()));
+    for dent in walker {
+        let dent = dent?;
+        if !dent.file_type().is_file() {
+            continue;
+        }
+        let rel = dent.path().strip_prefix(jrnl_path).unwrap().to_owned();
+        if rel == Path::new(ASSETS_DIR).join(INDEX_FILE) || is_stored(&rel) {
+            continue;
+        }
+        let to = stored_path(&filedb::digest(&fs::read(dent.path())?), &rel);
+        moves.push(Move {
+            from: rel.to_string_lossy().into_owned(),
+            to: to.to_string_lossy().into_owned(),
+        });
+    }
+    Ok(moves)
+}
+
+/// Move attachments into the store, rewrite links to them in `entries`, and
+/// return the paths of the entries that were rewritten.
+///
+pub fn migrate(jrnl_path: &Path, moves: &[Move], entries: &[Entry]) -> Result<Vec<PathBuf>> {
+    for m in moves.iter() {
+        let from = jrnl_path.join(&m.from);
+        let to = jrnl_path.join(&m.to);
+        if !to.exists() {
+            fs::create_dir_all(to.parent().unwrap())?;
+            fs::copy(&from, &to)?;
+        }
+        let name = from.file_name().unwrap().to_string_lossy().into_owned();
+        record_name(jrnl_path, &m.to, &name)?;
+    }
+
+    let mut rewritten = Vec::new();
+    for e in entries.iter() {
+        let raw = fs::read_to_string(&e.path)?;
+        let mut updated = raw.clone();
+        for m in moves.iter() {
+            updated = updated.replace(&format!("({})", m.from), &format!("({})", m.to));
+        }
+        if updated != raw {
+            filedb::write_atomic(&e.path, &updated)?;
+            rewritten.push(e.path.clone());
+        }
+    }
+
+    // Only now that nothing links to the originals, remove them.
+    let mut dirs = HashSet::new();
+    for m in moves.iter() {
+        let from = jrnl_path.join(&m.from);
+        fs::remove_file(&from)?;
+        dirs.insert(from.parent().unwrap().to_owned());
+    }
+    for dir in dirs {
+        // Leave directories with anything else in them.
+        let _ = fs::remove_dir(dir);
+    }
+    Ok(rewritten)
+}
+
+/// Whether `path` looks like an image, going by its extension.
+///
+pub fn is_image(path: &str) -> bool {
+    let ext = Path::new(path).extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+    ext.is_some_and(|e| IMAGE_EXTENSIONS.contains(&e.as_str()))
+}
+
+/// Whether `rel`, relative to the journal root, is in the store.
+///
+pub fn is_asset(rel: &Path) -> bool {
+    rel.starts_with(ASSETS_DIR) && is_stored(rel)
+}
+
+/// The names each stored file has been attached under, by its path.
+///
+pub fn names(jrnl_path: &Path) -> Result<BTreeMap<String, Vec<String>>> {
+    let path = jrnl_path.join(ASSETS_DIR).join(INDEX_FILE);
+    let mut names: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    if !path.exists() {
+        return Ok(names);
+    }
+    for line in fs::read_to_string(path)?.lines() {
+        let mut fields = line.splitn(2, '\t');
+        if let (Some(rel), Some(name)) = (fields.next(), fields.next()) {
+            names.entry(rel.to_owned()).or_default().push(name.to_owned());
+        }
+    }
+    Ok(names)
+}
+
+fn record_name(jrnl_path: &Path, rel: &str, name: &str) -> Result<()> {
+    if names(jrnl_path)?.get(rel).is_some_and(|n| n.iter().any(|n| n == name)) {
+        return Ok(());
+    }
+    let mut index = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(jrnl_path.join(ASSETS_DIR).join(INDEX_FILE))?;
+    writeln!(index, "{}\t{}", rel, name)?;
+    Ok(())
+}
+
+/// Where a file with contents hashing to `hex` (see `filedb::digest`) is
+/// kept, relative to the journal. The extension is kept so that browsers
+/// and viewers know what it is.
+///
+pub fn stored_path(hex: &str, original: &Path) -> PathBuf {
+    let mut file = hex.to_owned();
+    if let Some(ext) = original.extension() {
+        file.push('.');
+        file.push_str(&ext.to_string_lossy().to_lowercase());
+    }
+    Path::new(ASSETS_DIR).join(&hex[..2]).join(file)
+}
+
+fn is_stored(rel: &Path) -> bool {
+    let parts: Vec<String> = rel.iter().map(|p| p.to_string_lossy().into_owned()).collect();
+    match parts.as_slice() {
+        [_, prefix, file] => {
+            let stem = file.split('.').next().unwrap_or("");
+            (stem.len() == 64 || stem.len() == 16) && stem.starts_with(prefix.as_str())
+                && stem.chars().all(|c| c.is_ascii_hexdigit())
+        },
+        _ => false,
+    }
+}
@@ -0,0 +1,73 @@
+//! Tags applied automatically based on where an entry lives or what it
+//! says, per `[[autotag]]` rules in the config:
+//!
+//! ```toml
+//! [[autotag]]
+//! tags = ["work"]
+//! body = "(?i)standup"
+//! ```
+//!
+//! A rule applies when all of its conditions match.
+//!
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+
+use crate::config::AutoTagRule;
+use crate::error::Result;
+
+
+struct Rule {
+    tags: Vec<String>,
+    filename: Option<Regex>,
+    dir: Option<PathBuf>,
+    body: Option<Regex>,
+}
+
+pub struct AutoTagger {
+    rules: Vec<Rule>,
+}
+
+impl AutoTagger {
+    pub fn new(rules: &[AutoTagRule]) -> Result<AutoTagger> {
+        let mut compiled = Vec::new();
+
+        for r in rules {
+            if r.filename.is_none() && r.dir.is_none() && r.body.is_none() {
+                bail!("autotag rule for {:?} has no conditions", r.tags);
+            }
+            compiled.push(Rule {
+                tags: r.tags.clone(),
+                filename: match r.filename { Some(ref f) => Some(Regex::new(f)?), None => None },
+                dir: r.dir.as_ref().map(PathBuf::from),
+                body: match r.body { Some(ref b) => Some(Regex::new(b)?), None => None },
+            });
+        }
+        Ok(AutoTagger { rules: compiled })
+    }
+
+    /// Tags that apply to the entry at `path` (within the journal at
+    /// `jrnl_path`) with the given body, without duplicates.
+    ///
+    pub fn tags_for(&self, jrnl_path: &Path, path: &Path, body: &str) -> Vec<String> {
+        let filename = path.file_name().and_then(|f| f.to_str()).unwrap_or("");
+        let relative = path.strip_prefix(jrnl_path).unwrap_or(path);
+        let mut tags: Vec<String> = Vec::new();
+
+        for rule in self.rules.iter() {
+            let matches =
+                rule.filename.as_ref().is_none_or(|re| re.is_match(filename))
+                && rule.dir.as_ref().is_none_or(|d| relative.starts_with(d))
+                && rule.body.as_ref().is_none_or(|re| re.is_match(body));
+
+            if matches {
+                for t in rule.tags.iter() {
+                    if !tags.contains(t) {
+                        tags.push(t.clone());
+                    }
+                }
+            }
+        }
+        tags
+    }
+}
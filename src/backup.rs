@@ -0,0 +1,217 @@
+//! Encrypted, deduplicated backups to S3-compatible storage.
+//!
+//! Each file in the journal is encrypted with `age` and stored once under
+//! `<target>/objects/<hash>.age`; every backup also stores an encrypted
+//! manifest of paths and hashes under `<target>/manifests/`. Only objects the
+//! remote doesn't have yet are uploaded.
+//!
+//! The hashes are HMAC-SHA256, keyed with a secret kept in
+//! `.jrni/backup-key`, so that object names give nothing away about what's
+//! in them to whoever can list the bucket. The key isn't backed up, and
+//! isn't needed to restore; without it, the next backup uploads everything
+//! again.
+//!
+//! Transfers go through the `aws` CLI (pass `endpoint` for non-AWS stores),
+//! and encryption through the `age` CLI, so both need to be installed.
+//!
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::Command;
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use walkdir::WalkDir;
+
+use crate::activity::{self, STATE_DIR};
+use crate::crypt::run;
+use crate::datetime;
+use crate::error::Result;
+
+
+/// Where, in jrni's state, the key for object names is kept.
+const KEY_FILE: &str = "backup-key";
+
+
+pub struct Target {
+    /// e.g. `s3://bucket/prefix`, without a trailing slash.
+    url: String,
+    endpoint: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub files: usize,
+    pub uploaded: usize,
+    pub manifest: String,
+}
+
+impl Target {
+    pub fn new(url: &str, endpoint: Option<&str>) -> Result<Target> {
+        if !url.starts_with("s3://") {
+            bail!("backup target must be an s3:// URL, not '{}'", url);
+        }
+        Ok(Target {
+            url: url.trim_end_matches('/').to_owned(),
+            endpoint: endpoint.map(String::from),
+        })
+    }
+
+    fn aws(&self) -> Command {
+        let mut cmd = Command::new("aws");
+        if let Some(ref e) = self.endpoint {
+            cmd.arg("--endpoint-url").arg(e);
+        }
+        cmd.arg("s3");
+        cmd
+    }
+
+    /// Names of the objects directly under `dir`.
+    ///
+    fn list(&self, dir: &str) -> Result<HashSet<String>> {
+        let out = run(self.aws().arg("ls").arg(format!("{}/{}/", self.url, dir)), None)
+            // `aws s3 ls` fails on an empty prefix.
+            .unwrap_or_default();
+        Ok(String::from_utf8_lossy(&out).lines()
+            .filter_map(|l| l.split_whitespace().last())
+            .map(String::from)
+            .collect())
+    }
+
+    fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+        run(self.aws().arg("cp").arg("-").arg(format!("{}/{}", self.url, key)), Some(data))?;
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Result<Vec<u8>> {
+        run(self.aws().arg("cp").arg(format!("{}/{}", self.url, key)).arg("-"), None)
+    }
+}
+
+/// Back up everything in the journal (except jrni's own `.jrni/` state)
+/// to `target`, encrypted to the age `recipient`.
+///
+pub fn backup(jrnl_path: &Path, target: &Target, recipient: &str) -> Result<Report> {
+    let files = snapshot(jrnl_path, &key(jrnl_path)?)?;
+    let remote = target.list("objects")?;
+    let mut report = Report { files: files.len(), ..Report::default() };
+
+    for (rel, hash) in files.iter() {
+        let name = object_name(hash);
+        if remote.contains(&name) {
+            continue;
+        }
+        let data = fs::read(jrnl_path.join(rel))?;
+        target.put(&format!("objects/{}", name), &encrypt(recipient, &data)?)?;
+        report.uploaded += 1;
+    }
+
+    let manifest: String = files.iter()
+        .map(|(rel, hash)| format!("{}\t{}\n", hash, rel))
+        .collect();
+    report.manifest = format!("{}.age", datetime::now().format("%Y%m%dT%H%M%S"));
+    target.put(&format!("manifests/{}", report.manifest),
+        &encrypt(recipient, manifest.as_bytes())?)?;
+
+    Ok(report)
+}
+
+/// Check that the remote copy is complete, returning a description of each
+/// problem found.
+///
+/// With an age `identity`, the latest manifest is decrypted and every object
+/// it references must exist. Without one, every file currently in the
+/// journal must have been backed up.
+///
+pub fn verify(jrnl_path: &Path, target: &Target, identity: Option<&str>) -> Result<Vec<String>> {
+    let remote = target.list("objects")?;
+    let expected: Vec<(String, String)> = match identity {
+        Some(identity) => {
+            let latest = match target.list("manifests")?.into_iter().max() {
+                Some(m) => m,
+                None => bail!("no backups found at {}", target.url),
+            };
+            let raw = decrypt(identity, &target.get(&format!("manifests/{}", latest))?)?;
+            String::from_utf8_lossy(&raw).lines()
+                .filter_map(|l| {
+                    let mut fields = l.splitn(2, '\t');
+                    let hash = fields.next()?.to_owned();
+                    Some((fields.next()?.to_owned(), hash))
+                })
+                .collect()
+        },
+        None => snapshot(jrnl_path, &key(jrnl_path)?)?.into_iter().collect(),
+    };
+
+    Ok(expected.iter()
+        .filter(|(_, hash)| !remote.contains(&object_name(hash)))
+        .map(|(rel, _)| format!("missing from backup: {}", rel))
+        .collect())
+}
+
+fn object_name(hash: &str) -> String {
+    format!("{}.age", hash)
+}
+
+/// Keyed hashes of every file to back up, keyed by relative path.
+///
+fn snapshot(jrnl_path: &Path, key: &[u8]) -> Result<BTreeMap<String, String>> {
+    let mut files = BTreeMap::new();
+    let walker = WalkDir::new(jrnl_path).into_iter()
+        .filter_entry(|e| e.depth() == 0 || (e.file_name() != STATE_DIR && e.file_name() != ".git"));
+
+    for entry in walker {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let rel = entry.path().strip_prefix(jrnl_path).unwrap_or(entry.path());
+        files.insert(rel.to_string_lossy().into_owned(), keyed_hash(key, &fs::read(entry.path())?)?);
+    }
+    Ok(files)
+}
+
+fn keyed_hash(key: &[u8], data: &[u8]) -> Result<String> {
+    let mut mac = match Hmac::<Sha256>::new_from_slice(key) {
+        Ok(mac) => mac,
+        Err(_) => bail!("bad backup key"),
+    };
+    mac.update(data);
+    Ok(mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// The journal's key for object names, made the first time it's needed.
+///
+fn key(jrnl_path: &Path) -> Result<Vec<u8>> {
+    let path = activity::state_dir(jrnl_path).join(KEY_FILE);
+    if let Ok(hex) = fs::read_to_string(&path) {
+        let hex = hex.trim();
+        let key: Option<Vec<u8>> = (0..hex.len()).step_by(2)
+            .map(|i| hex.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok()))
+            .collect();
+        match key {
+            Some(key) if key.len() == 32 => return Ok(key),
+            _ => bail!("{} is damaged; delete it to start afresh", path.display()),
+        }
+    }
+
+    let mut key = vec![0u8; 32];
+    File::open("/dev/urandom")?.read_exact(&mut key)?;
+    fs::create_dir_all(path.parent().unwrap())?;
+    let mut opts = fs::OpenOptions::new();
+    opts.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut opts, 0o600);
+    let hex: String = key.iter().map(|b| format!("{:02x}", b)).collect();
+    writeln!(opts.open(&path)?, "{}", hex)?;
+    Ok(key)
+}
+
+fn encrypt(recipient: &str, data: &[u8]) -> Result<Vec<u8>> {
+    run(Command::new("age").arg("-r").arg(recipient), Some(data))
+}
+
+fn decrypt(identity: &str, data: &[u8]) -> Result<Vec<u8>> {
+    run(Command::new("age").arg("-d").arg("-i").arg(identity), Some(data))
+}
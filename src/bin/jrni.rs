@@ -1,33 +1,463 @@
 #[macro_use]
 extern crate error_chain;
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
-use std::collections::{HashMap, HashSet};
-use std::fs::File;
-use std::io::{Read, Write, stdin};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, IsTerminal, Read, Write, stdin};
 use std::env;
+use std::ffi::OsString;
 
-use clap::{Arg, App, SubCommand};
-use jrni::{Entry, walk_journal, datetime};
+use chrono::{DateTime, Local, NaiveDate, TimeZone};
+use clap::{Arg, App, AppSettings, ArgMatches, SubCommand};
+use jrni::{Entry, datetime, filedb};
+use jrni::activity;
+use jrni::anonymize::Anonymizer;
+use jrni::archive;
+use jrni::assets;
+use jrni::autotag::AutoTagger;
+use jrni::board;
+use jrni::datetime::Period;
+use jrni::config::{self, Config};
+use jrni::crypt;
+use jrni::diff;
+use jrni::editor::{self, Editor};
+use jrni::entry_types::{self, EntryType};
+use jrni::exif;
+use jrni::export::{self, activitypub, site};
+use jrni::fmt;
+use jrni::footnotes;
+use jrni::git;
+use jrni::import;
+use jrni::journal::{self, Draft, NewEntry};
+use jrni::line_format::LineFormat;
+use jrni::lint;
+use jrni::locale::{Locale, Msg};
+use jrni::matcher;
+use jrni::migrate;
+use jrni::placeholders::Placeholders;
+use jrni::prompt::{self, Confirm};
+use jrni::schedule;
+use jrni::retention;
+use jrni::setup::Setup;
+use jrni::similar::{self, Embedder};
+use jrni::stats::{self, Agg, Rule};
+use jrni::storage::{FileStorage, Storage};
+use jrni::scope::{Scope, Visibility};
+use jrni::serve::Server;
+use jrni::tags;
+use jrni::templates;
+use jrni::term;
+use jrni::transclude::Transcluder;
 use jrni::error::*;
 
 
+/// Everything a subcommand needs to know about how jrni was invoked.
+///
+pub struct Ctx {
+    /// The journal contents directory.
+    pub path: PathBuf,
+    /// Where entries are loaded from: the directory at `path`.
+    pub storage: Box<dyn Storage>,
+    pub config: Config,
+    pub confirm: Confirm,
+    pub locale: Locale,
+    /// Print listings as JSON rather than for people.
+    pub json: bool,
+    /// Commit changes to the journal's git repository after each command.
+    pub git: bool,
+    /// Include archived entries; see `archive`.
+    pub all: bool,
+    /// Whether entries that couldn't be read have been reported yet.
+    reported: Cell<bool>,
+    /// Entries already loaded (by `--strict` or the reminder check), for the
+    /// next that needs them.
+    loaded: RefCell<Option<Vec<Entry>>>,
+}
+
 fn run() -> Result<()> {
+    #[cfg(all(feature = "clipboard", target_os = "linux"))]
+    {
+        if env::var_os(CLIPBOARD_HOLDER).is_some() {
+            return hold_clipboard();
+        }
+    }
+
     let new_sub = SubCommand::with_name("n")
         .about("create a new entry")
         .arg(Arg::from_usage("-t --tags=[tags] 'tags to apply'"))
         .arg(Arg::from_usage("--stdin 'read body from stdin'"))
+        .arg(Arg::from_usage("--from-audio=[FILE] 'transcribe an audio memo into the body'"))
+        .arg(Arg::from_usage("--transcriber=[CMD] 'transcription command; {} is replaced with the audio path'"))
+        .arg(Arg::from_usage("--template=[NAME] 'start from a template in .templates/'"))
+        .arg(Arg::from_usage("--type=[TYPE] 'make an entry of a configured type, e.g. task'"))
+        .arg(Arg::from_usage("--encrypt 'encrypt the body with age'"))
+        .arg(Arg::from_usage("--dry-run 'print the path and frontmatter instead of writing the entry'")
+             .conflicts_with("from-audio"))
+        .arg(Arg::from_usage("--print 'print the whole entry instead of writing it'")
+             .conflicts_with_all(&["from-audio", "encrypt", "dry-run"]))
         .arg(Arg::from_usage("<entryname> 'filename of the entry'"));
                              
+    let fuzzy_sub = SubCommand::with_name("e")
+        .about("edit the entry best matching a fuzzy pattern, or create one")
+        .arg(Arg::from_usage("-s --search 'search entry text for the pattern instead, choosing among several matches'"))
+        .arg(Arg::from_usage("-i --ignore-case 'with --search, match case-insensitively'")
+             .requires("search"))
+        .arg(Arg::from_usage("<pattern> 'matched against filenames, ids and titles'"));
+
+    let pick_sub = SubCommand::with_name("pick")
+        .about("choose an entry to edit, in the terminal or through a launcher like dmenu or rofi")
+        .arg(Arg::from_usage("--dmenu 'print one line per entry, or with --resolve, read one back'"))
+        .arg(Arg::from_usage("--resolve 'read a chosen line on stdin and print its path'")
+             .requires("dmenu"))
+        .arg(Arg::from_usage("--edit 'with --resolve, open the entry instead'")
+             .requires("resolve"));
+
+    let init_sub = SubCommand::with_name("init")
+        .about("set up a journal and write the config for it")
+        .arg(Arg::from_usage("-i, --interactive 'ask about each setting'"));
+
+    let quick_sub = SubCommand::with_name("a")
+        .about("quickly create an entry without opening an editor")
+        .arg(Arg::from_usage("-t --tags=[tags] 'tags to apply'"))
+        .arg(Arg::from_usage("<text>... 'entry text, optionally prefixed with a time, e.g. \"yesterday 9pm: ...\"'"));
+
+    let today_sub = SubCommand::with_name("today")
+        .about("open today's entry, creating it if need be")
+        .arg(Arg::from_usage("--append=[TEXT] 'add a timestamped line instead of opening the editor'"));
+
+    let append_sub = SubCommand::with_name("append")
+        .about("add text to the end of an entry without opening the editor")
+        .arg(Arg::from_usage("--timestamp 'put a heading with the date and time above it'"))
+        .arg(Arg::from_usage("<id> 'the entry'"))
+        .arg(Arg::from_usage("[text]... 'what to add (default: read from stdin)'"));
+
+    let photos_sub = SubCommand::with_name("photos")
+        .about("file photos into entries for the days they were taken")
+        .arg(Arg::from_usage("<files>... 'photos to import'"));
+
+    let search_sub = SubCommand::with_name("s")
+        .about("search entry text and frontmatter")
+        .args(&filter_args())
+        .args(&page_args())
+        .arg(Arg::from_usage("-i --ignore-case 'match case-insensitively'"))
+        .arg(Arg::from_usage("--regex 'treat the query as a regular expression'"))
+        .arg(Arg::from_usage("-C --context=[N] 'lines of context to show (default 1)'"))
+        .arg(Arg::from_usage("--format=[FORMAT] 'output format (default text)'")
+             .possible_values(&["text", "alfred"]))
+        .arg(Arg::from_usage("<query> 'text to search for'"));
+
+    let l_sub = SubCommand::with_name("l")
+        .about("list entries with their date, id and title")
+        .args(&filter_args())
+        .args(&page_args());
+
+    let between_sub = SubCommand::with_name("between")
+        .about("list entries from a range of dates")
+        .args(&filter_args())
+        .args(&page_args())
+        .arg(Arg::from_usage("--since=[DURATION] 'from this long ago until today, e.g. 2w'")
+             .conflicts_with_all(&["start", "last-month"]))
+        .arg(Arg::from_usage("--last-month 'the previous calendar month'")
+             .conflicts_with("start"))
+        .arg(Arg::from_usage("[start] 'first day, e.g. 2024-05-01 or yesterday'"))
+        .arg(Arg::from_usage("[end] 'last day (default today)'"));
+
+    let ls_sub = SubCommand::with_name("ls")
+        .about("list entries, one row each")
+        .args(&filter_args())
+        .args(&page_args())
+        .arg(Arg::from_usage("--columns=[COLUMNS] 'comma-separated fields to print (default pubdate,id,path)'"))
+        .arg(Arg::from_usage("--format=[FORMAT] 'output format (default tsv)'")
+             .possible_values(&["tsv", "csv"]))
+        .arg(Arg::from_usage("--header 'print a header row'"))
+        .arg(Arg::from_usage("--template=[TEMPLATE] 'line format like \"{pubdate}  {id:<20} {tags}\", or the name of one in the config'")
+             .conflicts_with_all(&["columns", "format", "header"]))
+        .arg(Arg::from_usage("--sort=[COLUMN] 'column to sort by, numerically if it can be (default pubdate)'"))
+        .arg(Arg::from_usage("--max-preview=[CHARS] 'longest the preview column gets (default 80)'"));
+
+    let stats_sub = SubCommand::with_name("stats")
+        .about("summarize writing habits, or aggregate entries grouped by a frontmatter field")
+        .args(&filter_args())
+        .arg(Arg::from_usage("--weekly 'show words per ISO week rather than per month'")
+             .conflicts_with_all(&["group-by", "agg"]))
+        .arg(Arg::from_usage("--group-by=[FIELD] 'frontmatter field to group by'"))
+        .arg(Arg::from_usage("--agg=[AGGS] 'comma-separated: count, words, <field> (summed), avg:<field> (default count,words)'"));
+
+    let check_sub = SubCommand::with_name("check")
+        .about("check the journal's frontmatter, or rules about journaling, exiting non-zero if any fail")
+        .arg(Arg::from_usage("--rule=[RULE]... 'e.g. \"words_today >= 200\" or \"streak >= 3\"'")
+             .number_of_values(1));
+
+    let fmt_sub = SubCommand::with_name("fmt")
+        .about("rewrite entries' frontmatter in a canonical style, leaving bodies alone")
+        .args(&filter_args())
+        .arg(Arg::from_usage("--check 'list entries that would change, and fail if any would'"));
+
+    let status_sub = SubCommand::with_name("status")
+        .about("print a one-line summary for status bars and prompts")
+        .arg(Arg::from_usage("--format=[FORMAT] 'e.g. \"{streak}d {words_today}w\" (the default)'"));
+
     let tags_sub = SubCommand::with_name("t")
-        .about("get a listing of tags with associated entry count");
+        .about("get a listing of tags with associated entry count")
+        .args(&filter_args())
+        .args(&page_args())
+        .arg(Arg::from_usage("--tree 'show tags nested under their parents'"));
 
     let id_sub = SubCommand::with_name("id")
         .about("query for id")
+        .args(&filter_args())
+        .args(&page_args())
         .arg(Arg::from_usage("[id] 'if specified, edit the file with this shortname'"));
+
+    let toc_sub = SubCommand::with_name("toc")
+        .about("list the headings of an entry")
+        .arg(Arg::from_usage("--edit 'open the entry in $EDITOR'"))
+        .arg(Arg::from_usage("--section=[HEADING] 'with --edit, open at this heading'")
+             .requires("edit"))
+        .arg(Arg::from_usage("<id> 'id of the entry'"));
+
+    let path_sub = SubCommand::with_name("path")
+        .about("print the path of an entry")
+        .arg(Arg::from_usage("--copy 'copy it to the clipboard instead'"))
+        .arg(Arg::from_usage("<id> 'id of the entry'"));
+
+    let cat_sub = SubCommand::with_name("cat")
+        .about("print an entry")
+        .arg(Arg::from_usage("--copy 'copy it to the clipboard instead'"))
+        .arg(Arg::from_usage("<id> 'id of the entry'"));
+
+    let show_sub = SubCommand::with_name("show")
+        .about("print an entry for reading, with its Markdown rendered for the terminal")
+        .arg(Arg::from_usage("<id> 'id of the entry'"));
+
+    let diff_sub = SubCommand::with_name("diff")
+        .about("show a word-level diff of two entries")
+        .arg(Arg::from_usage("<id1> 'id of the first entry'"))
+        .arg(Arg::from_usage("<id2> 'id of the second entry'"));
+
+    let todo_sub = SubCommand::with_name("todo")
+        .about("list open tasks across entries")
+        .arg(Arg::from_usage("--by-entry 'summarize task progress per entry instead'"));
+
+    let authors_sub = SubCommand::with_name("authors")
+        .about("get a listing of authors with entry and word counts")
+        .args(&filter_args())
+        .args(&page_args());
+
+    let serve_sub = SubCommand::with_name("serve")
+        .about("serve a read-only HTML view of the journal")
+        .arg(Arg::from_usage("--addr=[ADDR] 'address to listen on (default 127.0.0.1:8000)'"))
+        .arg(Arg::from_usage("--auth=[USER:PASS] 'require HTTP basic auth'"));
+
+    let export_sub = SubCommand::with_name("export")
+        .about("export public entries")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(SubCommand::with_name("activitypub")
+            .about("write an ActivityPub actor and outbox")
+            .arg(Arg::from_usage("--base-url=<URL> 'where the output will be served from'"))
+            .arg(Arg::from_usage("--username=[NAME] 'actor name; defaults to the configured author'"))
+            .arg(Arg::from_usage("--anonymize 'replace the names in the anonymize config'"))
+            .arg(Arg::from_usage("<outdir> 'directory to write to'")))
+        .subcommand(SubCommand::with_name("html")
+            .about("write a static HTML site with an index and a page per tag")
+            .arg(Arg::from_usage("--audience=[WHO] 'who the site is for; defaults to public'")
+                 .possible_values(&["local", "team", "public"]))
+            .arg(Arg::from_usage("--anonymize 'replace the names in the anonymize config'"))
+            .arg(Arg::from_usage("<outdir> 'directory to write to'")))
+        .subcommand(SubCommand::with_name("md")
+            .about("write entries as Markdown, a file each or all in one")
+            .args(&filter_args())
+            .arg(Arg::from_usage("--audience=[WHO] 'who the output is for; defaults to local, i.e. everything'")
+                 .possible_values(&["local", "team", "public"]))
+            .arg(Arg::from_usage("--since=[WHEN] 'only entries from this day on, e.g. 2024, 2024-05 or 2w'"))
+            .arg(Arg::from_usage("--single 'write one document with a table of contents'"))
+            .arg(Arg::from_usage("--anonymize 'replace the names in the anonymize config'"))
+            .arg(Arg::from_usage("[out] 'directory to write to, or with --single, a file (default stdout)'")
+                 .required_unless("single")));
+
+    let attach_sub = SubCommand::with_name("attach")
+        .about("attach a file to an entry, linking it at the end of the body")
+        .arg(Arg::from_usage("<id> 'the entry to attach to'"))
+        .arg(Arg::from_usage("<file> 'the file to attach'"));
+
+    let verify_sub = SubCommand::with_name("verify")
+        .about("check entries for bad frontmatter, missing pubdates, taken ids and unknown tags")
+        .arg(Arg::from_usage("--staged 'check the entries staged in git, as staged'")
+             .conflicts_with("files"))
+        .arg(Arg::from_usage("[files]... 'entry files to check (default all)'"));
+
+    let hooks_sub = SubCommand::with_name("hooks")
+        .about("install hooks that run jrni from other tools")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(SubCommand::with_name("install-git")
+            .about("install a git pre-commit hook that runs `jrni verify --staged`")
+            .arg(Arg::from_usage("--force 'replace a pre-commit hook jrni didn't install'")));
+
+    let recent_sub = SubCommand::with_name("recent")
+        .about("list the most recently created or modified entries")
+        .arg(Arg::from_usage("-n [N] 'how many to list (default 10)'"));
+
+    let archive_sub = SubCommand::with_name("archive")
+        .about("move an entry under archive/, out of listings, search and tags (see --all)")
+        .arg(Arg::from_usage("<id> 'the entry'"));
+
+    let unarchive_sub = SubCommand::with_name("unarchive")
+        .about("bring an archived entry back")
+        .arg(Arg::from_usage("<id> 'the entry'"));
+
+    let gc_sub = SubCommand::with_name("gc")
+        .about("list entries due for deletion or archiving under the retention rules")
+        .arg(Arg::from_usage("--apply-retention 'delete and archive them, after confirmation'"));
+
+    let import_sub = SubCommand::with_name("import")
+        .about("import entries from another journaling tool")
+        .arg(Arg::from_usage("<format> 'what wrote them'").possible_values(import::FORMATS))
+        .arg(Arg::from_usage("<path> 'the export file, or for markdown a directory'"));
+
+    let rollup_sub = SubCommand::with_name("rollup")
+        .about("create an entry linking every entry from a week or month, grouped by tag")
+        .arg(Arg::from_usage("--week=[WEEK] 'an ISO week, e.g. 2024-W19'"))
+        .arg(Arg::from_usage("--month=[MONTH] 'a month, e.g. 2024-04'")
+             .conflicts_with("week"))
+        .group(clap::ArgGroup::with_name("period").args(&["week", "month"]).required(true));
+
+    let summarize_sub = SubCommand::with_name("summarize")
+        .about("write a summary of an entry into its summary field, using an external command")
+        .arg(Arg::from_usage("--command=[CMD] 'shell command reading the body on stdin and printing a summary'"))
+        .arg(Arg::from_usage("<id> 'the entry'"));
+
+    let publish_sub = SubCommand::with_name("publish")
+        .about("check an entry is fit to publish, and if so mark it published")
+        .arg(Arg::from_usage("--force 'publish even if checks fail'"))
+        .arg(Arg::from_usage("<id> 'the entry'"));
+
+    let similar_sub = SubCommand::with_name("similar")
+        .about("list the entries most similar to an entry, by comparing embeddings")
+        .arg(Arg::from_usage("-n [N] 'how many to list (default 10)'"))
+        .arg(Arg::from_usage("--command=[CMD] 'shell command reading text on stdin and printing its embedding'"))
+        .arg(Arg::from_usage("<id> 'the entry'"));
+
+    let links_sub = SubCommand::with_name("links")
+        .about("list the entries an entry links to with [[id]]")
+        .arg(Arg::from_usage("<id> 'the entry'"));
+
+    let backlinks_sub = SubCommand::with_name("backlinks")
+        .about("list the entries that link to an entry with [[id]]")
+        .arg(Arg::from_usage("<id> 'the entry'"));
+
+    let attachments_sub = SubCommand::with_name("attachments")
+        .about("list the files attached to an entry")
+        .arg(Arg::from_usage("<id> 'the entry'"));
+
+    let retag_sub = SubCommand::with_name("retag")
+        .about("rename a tag on every entry that has it")
+        .arg(Arg::from_usage("--from=<TAG> 'tag to rename'"))
+        .arg(Arg::from_usage("--to=[TAG] 'new name; without it, the tag is removed'"));
+
+    let sync_sub = SubCommand::with_name("sync")
+        .about("commit any changes, then pull and push the journal's git repository");
+
+    let migrate_sub = SubCommand::with_name("migrate")
+        .about("upgrade the journal to the format this jrni uses")
+        .arg(Arg::from_usage("--apply 'back up the journal and migrate it, rather than showing the plan'"));
+
+    let conflicts_sub = SubCommand::with_name("conflicts")
+        .about("list copies left behind by sync tool conflicts")
+        .arg(Arg::from_usage("--sync 'review each sync conflict and merge it into its entry'"));
+
+    let activity_sub = SubCommand::with_name("activity")
+        .about("show the history of entry creations, edits and deletions")
+        .arg(Arg::from_usage("--since=[DURATION] 'only show changes this recent, e.g. 7d'"));
+
+    let schedule_sub = SubCommand::with_name("schedule")
+        .about("print crontab or systemd snippets for configured recurring actions")
+        .arg(Arg::from_usage("--print=<FORMAT> 'snippet format'")
+             .possible_values(&["systemd", "cron"]));
+
+    let board_sub = SubCommand::with_name("board")
+        .about("show entries as a board, in columns by the value of a frontmatter field")
+        .args(&filter_args())
+        .arg(Arg::from_usage("--field=[FIELD] 'the field to group by (default status)'"))
+        .arg(Arg::from_usage("--columns=[VALUES] 'comma-separated values to show, in order (default all)'"))
+        .arg(Arg::from_usage("--width=[CHARS] 'width of each column (default 24)'"));
+
+    let wc_sub = SubCommand::with_name("wc")
+        .about("count the words in each entry, and today's progress towards a daily target")
+        .arg(Arg::from_usage("--target=[N] 'words to write a day; defaults to daily_word_target'"));
+
+    let agenda_sub = SubCommand::with_name("agenda")
+        .about("list entries whose remind: date has arrived")
+        .arg(Arg::from_usage("--done=[ID] 'dismiss the reminder on an entry'"));
+
+    let remind_sub = SubCommand::with_name("remind")
+        .about("send a desktop notification if there's no entry for today yet");
+
+    let habits_sub = SubCommand::with_name("habits")
+        .about("show a completion grid per habit")
+        .arg(Arg::from_usage("--since=[DURATION] 'how far back to look (default 1m)'"));
+
+    let template_sub = SubCommand::with_name("template")
+        .about("manage entry templates")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(SubCommand::with_name("list")
+            .about("list available templates"))
+        .subcommand(SubCommand::with_name("show")
+            .about("print a template")
+            .arg(Arg::from_usage("<name> 'template name'")))
+        .subcommand(SubCommand::with_name("new")
+            .about("create a template and open it in $EDITOR")
+            .arg(Arg::from_usage("<name> 'template name'")))
+        .subcommand(SubCommand::with_name("edit")
+            .about("open a template in $EDITOR")
+            .arg(Arg::from_usage("<name> 'template name'")))
+        .subcommand(SubCommand::with_name("from")
+            .about("create a template from an existing entry")
+            .arg(Arg::from_usage("<id> 'id of the entry to copy'"))
+            .arg(Arg::from_usage("[name] 'template name; defaults to the id'")));
+
+    let fm_target = [
+        Arg::from_usage("--file=[PATH] 'the entry file'")
+            .conflicts_with("id")
+            .required_unless("id"),
+        Arg::from_usage("--id=[ID] 'id of the entry'"),
+    ];
+    let fm_sub = SubCommand::with_name("fm")
+        .about("read or write an entry's frontmatter fields")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(SubCommand::with_name("get")
+            .about("print a field")
+            .args(&fm_target)
+            .arg(Arg::from_usage("<key> 'field name'")))
+        .subcommand(SubCommand::with_name("set")
+            .about("set a field, leaving the rest of the file as it is")
+            .args(&fm_target)
+            .arg(Arg::from_usage("<key> 'field name'"))
+            .arg(Arg::from_usage("<value> 'new value; comma-separated for tags'")));
+
+    let doctor_sub = SubCommand::with_name("doctor")
+        .about("find problems in the journal")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(SubCommand::with_name("expired")
+            .about("list entries whose temporary tags have lapsed"))
+        .subcommand(SubCommand::with_name("tags")
+            .about("list entries using tags outside the configured vocabulary"))
+        .subcommand(SubCommand::with_name("autotag")
+            .about("list entries missing tags from autotag rules")
+            .arg(Arg::from_usage("--apply 'add the missing tags'")))
+        .subcommand(SubCommand::with_name("assets")
+            .about("list attachments not yet in the deduplicating asset store")
+            .arg(Arg::from_usage("--apply 'move them into it and rewrite links'")))
+        .subcommand(SubCommand::with_name("footnotes")
+            .about("list undefined, unused and duplicate footnotes")
+            .arg(Arg::from_usage("--renumber 'renumber footnotes in reading order'")))
+        .subcommand(SubCommand::with_name("filenames")
+            .about("list entries whose filenames break the configured rules")
+            .arg(Arg::from_usage("--fix 'rename them to fit'")));
                              
-    let matches = App::new("jrni")
+    let app = App::new("jrni")
         .version("1.0")
         .arg(Arg::with_name("path")
              .short("p")
@@ -35,170 +465,3375 @@ fn run() -> Result<()> {
              .value_name("DIR")
              .help("path to the journal contents directory")
              .takes_value(true))
+        .arg(Arg::with_name("yes")
+             .short("f")
+             .long("yes")
+             .global(true)
+             .help("don't ask for confirmation before destructive operations"))
+        .arg(Arg::with_name("json")
+             .long("json")
+             .global(true)
+             .help("print listings and search results as JSON"))
+        .arg(Arg::with_name("git")
+             .long("git")
+             .global(true)
+             .help("commit any changes to the journal's git repository"))
+        .arg(Arg::with_name("all")
+             .long("all")
+             .visible_alias("include-archived")
+             .global(true)
+             .help("include archived entries in listings, search, tag counts and stats"))
+        .arg(Arg::with_name("strict")
+             .long("strict")
+             .global(true)
+             .help("fail if any entry can't be read or has bad frontmatter"))
+        .subcommand(init_sub)
         .subcommand(new_sub)
+        .subcommand(quick_sub)
+        .subcommand(today_sub)
+        .subcommand(append_sub)
+        .subcommand(fuzzy_sub)
+        .subcommand(pick_sub)
+        .subcommand(photos_sub)
+        .subcommand(search_sub)
+        .subcommand(l_sub)
+        .subcommand(between_sub)
+        .subcommand(ls_sub)
+        .subcommand(stats_sub)
+        .subcommand(check_sub)
+        .subcommand(fmt_sub)
+        .subcommand(status_sub)
         .subcommand(tags_sub)
         .subcommand(id_sub)
-        .get_matches();
+        .subcommand(toc_sub)
+        .subcommand(path_sub)
+        .subcommand(cat_sub)
+        .subcommand(show_sub)
+        .subcommand(diff_sub)
+        .subcommand(todo_sub)
+        .subcommand(habits_sub)
+        .subcommand(authors_sub)
+        .subcommand(template_sub)
+        .subcommand(serve_sub)
+        .subcommand(export_sub)
+        .subcommand(conflicts_sub)
+        .subcommand(activity_sub)
+        .subcommand(schedule_sub)
+        .subcommand(agenda_sub)
+        .subcommand(wc_sub)
+        .subcommand(board_sub)
+        .subcommand(remind_sub)
+        .subcommand(gc_sub)
+        .subcommand(import_sub)
+        .subcommand(verify_sub)
+        .subcommand(hooks_sub)
+        .subcommand(recent_sub)
+        .subcommand(archive_sub)
+        .subcommand(unarchive_sub)
+        .subcommand(rollup_sub)
+        .subcommand(summarize_sub)
+        .subcommand(publish_sub)
+        .subcommand(similar_sub)
+        .subcommand(links_sub)
+        .subcommand(backlinks_sub)
+        .subcommand(attach_sub)
+        .subcommand(attachments_sub)
+        .subcommand(retag_sub)
+        .subcommand(fm_sub)
+        .subcommand(migrate_sub)
+        .subcommand(sync_sub)
+        .subcommand(doctor_sub);
+
+    #[cfg(feature = "backup")]
+    let app = app.subcommand(SubCommand::with_name("backup")
+        .about("back up the journal, encrypted, to S3-compatible storage")
+        .arg(Arg::from_usage("--to=[URL] 'where to back up to, e.g. s3://bucket/journal'"))
+        .arg(Arg::from_usage("--verify 'check the remote copy instead of backing up'")));
+
+    let mut app = app;
+    let args: Vec<OsString> = env::args_os().collect();
+    let matches = app.get_matches_from_safe_borrow(&args).unwrap_or_else(|e| e.exit());
 
     // Take the journal path from
     //
     //   - `-p`, the path argument, or
+    //   - the nearest directory above the working directory with a
+    //     `.jrni.toml`, or
     //   - JRNI_PATH, the environment variable, or
+    //   - `path` in the global config, or
     //   - default to `~/sink/journal`, which is probably relevant for no one 
     //     but me.
     //
-    let default_path = dirs::home_dir().unwrap().join("sink/journal");
-    let path = match matches.value_of("path") {
-        Some(v) => String::from(v),
-        None => match env::var_os("JRNI_PATH") {
+    let default_path = match Config::load()?.path {
+        Some(p) => config::expand_home(&p),
+        None => dirs::home_dir().unwrap().join("sink/journal"),
+    };
+    let nearest = env::current_dir().ok().and_then(|d| config::find_journal(&d));
+    let path = match (matches.value_of("path"), nearest) {
+        (Some(v), _) => String::from(v),
+        (None, Some(v)) => String::from(v.to_str().unwrap()),
+        (None, None) => match env::var_os("JRNI_PATH") {
             Some(v2) =>  v2.into_string().unwrap(),
             None => String::from(default_path.to_str().unwrap()),
         }
     };
-    let mut path = PathBuf::from(path);
-    
+    let config = Config::load_for(Path::new(&path))?;
+
+    // Now that there's a config, parse the command line again with its
+    // defaults for the subcommand.
+    let matches = match with_command_defaults(&config, &args, &matches) {
+        Some(args) => app.get_matches_from_safe_borrow(&args).unwrap_or_else(|e| e.exit()),
+        None => matches,
+    };
+
+    let locale = Locale::detect(config.locale.as_deref());
+    let ctx = Ctx {
+        storage: Box::new(FileStorage::new(Path::new(&path))),
+        path: PathBuf::from(path),
+        confirm: Confirm::new(matches.is_present("yes"), &config, locale),
+        locale,
+        json: matches.is_present("json"),
+        git: matches.is_present("git") || config.git,
+        all: matches.is_present("all"),
+        reported: Cell::new(false),
+        loaded: RefCell::new(None),
+        config,
+    };
+
+    if matches.is_present("strict") {
+        let (entries, errors) = ctx.storage.entries_checked();
+        if !errors.is_empty() {
+            report_entry_errors(&errors);
+            bail!("{} problem(s) reading the journal; not going on, since --strict was given",
+                  errors.len());
+        }
+        ctx.reported.set(true);
+        ctx.loaded.replace(Some(entries));
+    }
+
+    // Bring up any reminders that have come due, when writing or looking
+    // through entries in a terminal; other commands don't need every entry
+    // loaded first.
+    let announces = matches.subcommand_name().is_some_and(|c| ANNOUNCES_REMINDERS.contains(&c));
+    if announces && io::stderr().is_terminal() {
+        announce_reminders(&ctx);
+    }
+
+    // Never from inside a git hook (where GIT_INDEX_FILE is set), where
+    // committing would run the hook again, nor after `verify`, which the
+    // pre-commit hook runs, or `hooks`, which would set it off.
+    let commits = ctx.git && env::var_os("GIT_INDEX_FILE").is_none()
+        && !matches!(matches.subcommand_name(), Some("sync") | Some("verify") | Some("hooks"));
+    // What's changed already, so that only what this command writes gets
+    // committed.
+    let dirty = match commits {
+        true => Some(git::dirty_files(&ctx.path)?),
+        false => None,
+    };
+
     let res: Result<_> = match matches.subcommand() {
-        ("n", Some(sub_m)) => new_entry(
-            &mut path, 
-            sub_m.value_of("entryname").unwrap(),
-            sub_m.value_of("tags"),
-            sub_m.is_present("stdin"),
-            ),
-        ("t", Some(_)) => query_tags(path),
+        ("init", Some(sub_m)) => init(&ctx, sub_m.is_present("interactive")),
+        // With nothing set up yet, a bare `jrni` is most likely someone
+        // trying it out for the first time.
+        ("", None) if Config::default_path().is_some_and(|p| !p.exists())
+            && !ctx.path.exists() && io::stdin().is_terminal() => init(&ctx, true),
+        ("n", Some(sub_m)) => {
+            let transcriber = match sub_m.value_of("transcriber") {
+                Some(v) => Some(String::from(v)),
+                None => env::var("JRNI_TRANSCRIBER").ok(),
+            };
+            let audio = match (sub_m.value_of("from-audio"), &transcriber) {
+                (Some(a), Some(t)) => Some((a, t.as_str())),
+                (Some(_), None) => bail!(
+                    "--from-audio requires --transcriber or JRNI_TRANSCRIBER"),
+                (None, _) => None,
+            };
+            new_entry(
+                &ctx, 
+                sub_m.value_of("entryname").unwrap(),
+                sub_m.value_of("tags"),
+                sub_m.is_present("stdin"),
+                audio,
+                sub_m.value_of("type"),
+                sub_m.value_of("template"),
+                sub_m.is_present("encrypt"),
+                match (sub_m.is_present("dry-run"), sub_m.is_present("print")) {
+                    (true, _) => Some(Preview::Frontmatter),
+                    (_, true) => Some(Preview::Entry),
+                    _ => None,
+                },
+                )
+        },
+        ("e", Some(sub_m)) if sub_m.is_present("search") => {
+            let pattern = regex::RegexBuilder::new(&regex::escape(sub_m.value_of("pattern").unwrap()))
+                .case_insensitive(sub_m.is_present("ignore-case"))
+                .build()?;
+            edit_search(&ctx, &pattern)
+        },
+        ("e", Some(sub_m)) => edit_fuzzy(&ctx, sub_m.value_of("pattern").unwrap()),
+        ("pick", Some(sub_m)) if !sub_m.is_present("dmenu") => pick_interactive(&ctx),
+        ("pick", Some(sub_m)) => match sub_m.is_present("resolve") {
+            true => pick_resolve(&ctx, sub_m.is_present("edit")),
+            false => pick_list(&ctx),
+        },
+        ("a", Some(sub_m)) => {
+            let text: Vec<&str> = sub_m.values_of("text").unwrap().collect();
+            quick_entry(&ctx, &text.join(" "), sub_m.value_of("tags"))
+        },
+        ("today", Some(sub_m)) => today(&ctx, sub_m.value_of("append")),
+        ("append", Some(sub_m)) => {
+            let text = match sub_m.values_of("text") {
+                Some(words) => words.collect::<Vec<_>>().join(" "),
+                None => {
+                    let mut text = String::new();
+                    stdin().read_to_string(&mut text)?;
+                    text
+                },
+            };
+            append(&ctx, sub_m.value_of("id").unwrap(), &text, sub_m.is_present("timestamp"))
+        },
+        ("photos", Some(sub_m)) => {
+            let files: Vec<&str> = sub_m.values_of("files").unwrap().collect();
+            import_photos(&ctx, &files)
+        },
+        ("s", Some(sub_m)) => {
+            let query = sub_m.value_of("query").unwrap();
+            let pattern = match sub_m.is_present("regex") {
+                true => query.to_owned(),
+                false => regex::escape(query),
+            };
+            let pattern = regex::RegexBuilder::new(&pattern)
+                .case_insensitive(sub_m.is_present("ignore-case"))
+                .build()?;
+            let context = match sub_m.value_of("context") {
+                Some(c) => c.parse::<usize>().chain_err(|| format!("bad --context '{}'", c))?,
+                None => 1,
+            };
+            search_entries(
+                &ctx,
+                &pattern,
+                context,
+                sub_m.value_of("format") == Some("alfred"),
+                &EntryFilter::from_args(&ctx, sub_m)?,
+                &Page::from_args(&ctx, sub_m)?)
+        },
+        ("l", Some(sub_m)) => query_entries(
+            &ctx, &EntryFilter::from_args(&ctx, sub_m)?, &Page::from_args(&ctx, sub_m)?),
+        ("between", Some(sub_m)) => {
+            let mut filter = EntryFilter::from_args(&ctx, sub_m)?;
+            if filter.period.is_some() {
+                bail!("--week and --quarter can't be used with between");
+            }
+            filter.period = Some(date_range(sub_m)?);
+            filter.filename_dates = true;
+            query_entries(&ctx, &filter, &Page::from_args(&ctx, sub_m)?)
+        },
+        ("ls", Some(sub_m)) => {
+            let columns: Vec<&str> = sub_m.value_of("columns")
+                .unwrap_or("pubdate,id,path")
+                .split(',').map(|c| c.trim()).filter(|c| !c.is_empty())
+                .collect();
+            let max_preview = match sub_m.value_of("max-preview") {
+                Some(n) => n.parse::<usize>().chain_err(|| format!("bad --max-preview '{}'", n))?,
+                None => 80,
+            };
+            let template = match sub_m.value_of("template") {
+                Some(t) => match ctx.config.line_formats.get(t) {
+                    Some(named) => Some(LineFormat::parse(named)?),
+                    None if t.contains('{') => Some(LineFormat::parse(t)?),
+                    None => bail!("no line format named '{}' in the config", t),
+                },
+                None => None,
+            };
+            let columns = match template {
+                Some(ref t) => t.fields(),
+                None => columns,
+            };
+            list_entries(
+                &ctx,
+                &EntryFilter::from_args(&ctx, sub_m)?,
+                &Page::from_args(&ctx, sub_m)?,
+                &columns,
+                template.as_ref(),
+                sub_m.value_of("format").unwrap_or("tsv"),
+                sub_m.is_present("header"),
+                sub_m.value_of("sort").unwrap_or("pubdate"),
+                max_preview)
+        },
+        ("stats", Some(sub_m)) if !sub_m.is_present("group-by") && !sub_m.is_present("agg") =>
+            print_summary(&ctx, &EntryFilter::from_args(&ctx, sub_m)?, sub_m.is_present("weekly")),
+        ("stats", Some(sub_m)) => {
+            let aggs = sub_m.value_of("agg").unwrap_or("count,words")
+                .split(',').map(Agg::parse).collect::<Result<Vec<Agg>>>()?;
+            print_stats(
+                &ctx,
+                &EntryFilter::from_args(&ctx, sub_m)?,
+                sub_m.value_of("group-by"),
+                &aggs)
+        },
+        ("fmt", Some(sub_m)) => fmt_entries(
+            &ctx, &EntryFilter::from_args(&ctx, sub_m)?, sub_m.is_present("check")),
+        ("check", Some(sub_m)) => match sub_m.values_of("rule") {
+            Some(rules) => check_rules(
+                &ctx, &rules.map(Rule::parse).collect::<Result<Vec<Rule>>>()?),
+            None => check_journal(&ctx),
+        },
+        ("status", Some(sub_m)) =>
+            print_status(&ctx, sub_m.value_of("format").unwrap_or("{streak}d {words_today}w")),
+        ("t", Some(sub_m)) => query_tags(
+            &ctx, &EntryFilter::from_args(&ctx, sub_m)?, &Page::from_args(&ctx, sub_m)?,
+            sub_m.is_present("tree")),
         ("id", Some(sub_m)) => {
             if sub_m.is_present("id") {
-                edit_by_id(path, sub_m.value_of("id").unwrap())
+                edit_by_id(&ctx, sub_m.value_of("id").unwrap())
             } else {
-                query_ids(path)
+                query_ids(
+                    &ctx, &EntryFilter::from_args(&ctx, sub_m)?, &Page::from_args(&ctx, sub_m)?)
             }
         }
+        ("authors", Some(sub_m)) =>
+            query_authors(
+                &ctx, &EntryFilter::from_args(&ctx, sub_m)?, &Page::from_args(&ctx, sub_m)?),
+        ("habits", Some(sub_m)) =>
+            query_habits(&ctx, sub_m.value_of("since").unwrap_or("1m")),
+        ("toc", Some(sub_m)) => {
+            let id = sub_m.value_of("id").unwrap();
+            if sub_m.is_present("edit") {
+                edit_section(&ctx, id, sub_m.value_of("section"))
+            } else {
+                query_toc(&ctx, id)
+            }
+        },
+        ("path", Some(sub_m)) =>
+            print_path(&ctx, sub_m.value_of("id").unwrap(), sub_m.is_present("copy")),
+        ("cat", Some(sub_m)) =>
+            print_entry(&ctx, sub_m.value_of("id").unwrap(), sub_m.is_present("copy")),
+        ("show", Some(sub_m)) => show_entry(&ctx, sub_m.value_of("id").unwrap()),
+        ("diff", Some(sub_m)) => diff_entries(
+            &ctx, sub_m.value_of("id1").unwrap(), sub_m.value_of("id2").unwrap()),
+        ("todo", Some(sub_m)) => query_todo(&ctx, sub_m.is_present("by-entry")),
+        ("template", Some(sub_m)) => match sub_m.subcommand() {
+            ("list", Some(_)) => template_list(&ctx),
+            ("show", Some(m)) => template_show(&ctx, m.value_of("name").unwrap()),
+            ("new", Some(m)) => template_new(&ctx, m.value_of("name").unwrap()),
+            ("edit", Some(m)) => template_edit(&ctx, m.value_of("name").unwrap()),
+            ("from", Some(m)) => {
+                let id = m.value_of("id").unwrap();
+                template_from(&ctx, id, m.value_of("name").unwrap_or(id))
+            },
+            (&_, _) => Ok(()),
+        },
+        ("serve", Some(sub_m)) => {
+            let addr = sub_m.value_of("addr")
+                .or(ctx.config.serve.addr.as_deref())
+                .unwrap_or("127.0.0.1:8000");
+            let auth = sub_m.value_of("auth")
+                .or(ctx.config.serve.auth.as_deref());
+            let audience = ctx.config.serve.audience.unwrap_or(Visibility::Team);
+            Server::new(&ctx.path, auth, Scope::from_config(&ctx.config), audience)
+                .run(addr)
+        },
+        ("export", Some(sub_m)) => match sub_m.subcommand() {
+            ("activitypub", Some(m)) => export_activitypub(
+                &ctx,
+                m.value_of("base-url").unwrap(),
+                m.value_of("username"),
+                m.is_present("anonymize"),
+                m.value_of("outdir").unwrap()),
+            ("html", Some(m)) => {
+                let audience = match m.value_of("audience") {
+                    Some("local") => Visibility::Local,
+                    Some("team") => Visibility::Team,
+                    _ => Visibility::Public,
+                };
+                export_html(&ctx, audience, m.is_present("anonymize"), m.value_of("outdir").unwrap())
+            },
+            ("md", Some(m)) => {
+                let audience = match m.value_of("audience") {
+                    Some("public") => Visibility::Public,
+                    Some("team") => Visibility::Team,
+                    _ => Visibility::Local,
+                };
+                let today = datetime::now().naive_local().date();
+                let since = match m.value_of("since") {
+                    Some(s) => Some(datetime::parse_since(s, today)?),
+                    None => None,
+                };
+                export_markdown(
+                    &ctx,
+                    audience,
+                    &EntryFilter::from_args(&ctx, m)?,
+                    since,
+                    m.is_present("single"),
+                    m.is_present("anonymize"),
+                    m.value_of("out"))
+            },
+            (&_, _) => Ok(()),
+        },
+        ("conflicts", Some(sub_m)) => {
+            if sub_m.is_present("sync") {
+                resolve_sync_conflicts(&ctx)
+            } else {
+                query_sync_conflicts(&ctx)
+            }
+        },
+        ("activity", Some(sub_m)) => query_activity(&ctx, sub_m.value_of("since")),
+        #[cfg(feature = "backup")]
+        ("backup", Some(sub_m)) =>
+            backup(&ctx, sub_m.value_of("to"), sub_m.is_present("verify")),
+        ("schedule", Some(sub_m)) => print_schedule(&ctx, sub_m.value_of("print").unwrap()),
+        ("agenda", Some(sub_m)) => match sub_m.value_of("done") {
+            Some(id) => dismiss_reminder(&ctx, id),
+            None => agenda(&ctx),
+        },
+        ("remind", Some(_)) => remind(&ctx),
+        ("board", Some(sub_m)) => {
+            let columns: Option<Vec<&str>> = sub_m.value_of("columns").map(|c| {
+                c.split(',').map(|c| c.trim()).filter(|c| !c.is_empty()).collect()
+            });
+            let width = match sub_m.value_of("width") {
+                Some(n) => n.parse::<usize>().chain_err(|| format!("bad --width '{}'", n))?,
+                None => 24,
+            };
+            show_board(
+                &ctx,
+                &EntryFilter::from_args(&ctx, sub_m)?,
+                sub_m.value_of("field").unwrap_or("status"),
+                columns.as_deref(),
+                width)
+        },
+        ("wc", Some(sub_m)) => {
+            let target = match sub_m.value_of("target") {
+                Some(n) => Some(n.parse::<usize>().chain_err(|| format!("bad --target '{}'", n))?),
+                None => ctx.config.daily_word_target,
+            };
+            word_counts(&ctx, target)
+        },
+        ("verify", Some(sub_m)) => {
+            let files: Vec<&str> = sub_m.values_of("files").map(|f| f.collect()).unwrap_or_default();
+            verify(&ctx, sub_m.is_present("staged"), &files)
+        },
+        ("hooks", Some(sub_m)) => match sub_m.subcommand() {
+            ("install-git", Some(m)) => {
+                let hook = git::install_hook(&ctx.path, &env::current_exe()?, m.is_present("force"))?;
+                println!("{}", hook.display());
+                Ok(())
+            },
+            (&_, _) => Ok(()),
+        },
+        ("recent", Some(sub_m)) => {
+            let n = match sub_m.value_of("n") {
+                Some(n) => n.parse::<usize>().chain_err(|| format!("bad -n '{}'", n))?,
+                None => 10,
+            };
+            query_recent(&ctx, n)
+        },
+        ("archive", Some(sub_m)) => archive_entry(&ctx, sub_m.value_of("id").unwrap()),
+        ("unarchive", Some(sub_m)) => unarchive_entry(&ctx, sub_m.value_of("id").unwrap()),
+        ("gc", Some(sub_m)) => gc(&ctx, sub_m.is_present("apply-retention")),
+        ("import", Some(sub_m)) => import_entries(
+            &ctx, sub_m.value_of("format").unwrap(), Path::new(sub_m.value_of("path").unwrap())),
+        ("rollup", Some(sub_m)) => match (sub_m.value_of("week"), sub_m.value_of("month")) {
+            (Some(week), _) => rollup(&ctx, &format!("rollup-{}", week), Period::iso_week(week)?),
+            (_, Some(month)) =>
+                rollup(&ctx, &format!("rollup-{}", month), Period::parse_month(month)?),
+            _ => Ok(()),
+        },
+        ("summarize", Some(sub_m)) =>
+            summarize(&ctx, sub_m.value_of("id").unwrap(), sub_m.value_of("command")),
+        ("publish", Some(sub_m)) =>
+            publish(&ctx, sub_m.value_of("id").unwrap(), sub_m.is_present("force")),
+        ("similar", Some(sub_m)) => {
+            let n = match sub_m.value_of("n") {
+                Some(n) => n.parse::<usize>().chain_err(|| format!("bad -n '{}'", n))?,
+                None => 10,
+            };
+            query_similar(&ctx, sub_m.value_of("id").unwrap(), n, sub_m.value_of("command"))
+        },
+        ("links", Some(sub_m)) => query_links(&ctx, sub_m.value_of("id").unwrap()),
+        ("backlinks", Some(sub_m)) => query_backlinks(&ctx, sub_m.value_of("id").unwrap()),
+        ("attach", Some(sub_m)) => attach(
+            &ctx, sub_m.value_of("id").unwrap(), Path::new(sub_m.value_of("file").unwrap())),
+        ("attachments", Some(sub_m)) => list_attachments(&ctx, sub_m.value_of("id").unwrap()),
+        ("retag", Some(sub_m)) =>
+            retag(&ctx, sub_m.value_of("from").unwrap(), sub_m.value_of("to")),
+        ("migrate", Some(sub_m)) => migrate_journal(&ctx, sub_m.is_present("apply")),
+        ("sync", Some(_)) => Ok(git::sync(&ctx.path)?),
+        ("fm", Some(sub_m)) => match sub_m.subcommand() {
+            ("get", Some(m)) => fm_get(&fm_path(&ctx, m)?, m.value_of("key").unwrap()),
+            ("set", Some(m)) => fm_set(
+                &fm_path(&ctx, m)?, m.value_of("key").unwrap(), m.value_of("value").unwrap()),
+            (&_, _) => Ok(()),
+        },
+        ("doctor", Some(sub_m)) => match sub_m.subcommand() {
+            ("expired", Some(_)) => doctor_expired(&ctx),
+            ("tags", Some(_)) => doctor_tags(&ctx),
+            ("autotag", Some(m)) => doctor_autotag(&ctx, m.is_present("apply")),
+            ("assets", Some(m)) => doctor_assets(&ctx, m.is_present("apply")),
+            ("footnotes", Some(m)) => doctor_footnotes(&ctx, m.is_present("renumber")),
+            ("filenames", Some(m)) => doctor_filenames(&ctx, m.is_present("fix")),
+            (&_, _) => Ok(()),
+        },
         (&_, _) => Ok(()),
     };
 
+    // Stamp anything edited by this command (or since the last run) while
+    // it's fresh. `activity` has already done so itself.
+    if res.is_ok() && (ctx.config.track_modified || ctx.config.track_stats)
+        && matches.subcommand_name() != Some("activity")
+    {
+        activity::record_changes(&ctx.path, stamps(&ctx))?;
+    }
+    if let (true, Some(dirty)) = (res.is_ok(), dirty) {
+        git::commit_written(&ctx.path, &dirty)?;
+    }
     res
 }
 
 quick_main!(run);
- 
-fn get_entries(files_path: &PathBuf) -> impl Iterator<Item = Entry> {
-    walk_journal(
-        &files_path.to_str().unwrap(), 
-        |p| Entry::from_path(&p)
-    )
-        .into_iter()
-        .filter_map(|e| match e {
-            Ok(e) => Some(e),
-            Err(_) => { None // TODO error log 
+
+/// The command line `args`, parsed as `matches`, with the flags from the
+/// config's `[command.<name>]` section for the subcommand added after its
+/// name, unless they were given. None if there's nothing to add.
+///
+/// `true` adds a bare flag, `false` nothing, and an array the flag once per
+/// value.
+///
+fn with_command_defaults(
+    config: &Config,
+    args: &[OsString],
+    matches: &ArgMatches,
+) -> Option<Vec<OsString>> {
+    let (name, sub_m) = matches.subcommand();
+    let (defaults, sub_m) = (config.command.get(name)?, sub_m?);
+
+    let plain = |v: &toml::Value| match v {
+        toml::Value::String(s) => s.clone(),
+        v => v.to_string(),
+    };
+    let mut flags = Vec::new();
+    for (key, value) in defaults.iter() {
+        if sub_m.occurrences_of(key) > 0 {
+            continue;
+        }
+        match value {
+            toml::Value::Boolean(true) => flags.push(format!("--{}", key)),
+            toml::Value::Boolean(false) => (),
+            toml::Value::Array(values) =>
+                flags.extend(values.iter().map(|v| format!("--{}={}", key, plain(v)))),
+            v => flags.push(format!("--{}={}", key, plain(v))),
+        }
+    }
+    if flags.is_empty() {
+        return None;
+    }
+
+    let at = args.iter().skip(1).position(|a| a == name)? + 2;
+    let mut with = args[..at].to_vec();
+    with.extend(flags.into_iter().map(OsString::from));
+    with.extend_from_slice(&args[at..]);
+    Some(with)
+}
+
+/// Filters shared by the listing subcommands.
+///
+fn filter_args() -> [Arg<'static, 'static>; 6] {
+    [
+        Arg::from_usage("--week=[WEEK] 'only entries from an ISO week, e.g. 2024-W19'")
+            .conflicts_with("quarter"),
+        Arg::from_usage("--quarter=[QUARTER] 'only entries from a (fiscal) quarter, e.g. 2024-Q2'"),
+        Arg::from_usage("--author=[AUTHOR] 'only entries by this author'"),
+        Arg::from_usage("-t --tags=[TAGS] 'only entries with any of these comma-separated tags'"),
+        Arg::from_usage("--all-tags 'with --tags, only entries with all of them'")
+            .requires("tags"),
+        Arg::from_usage("--type=[TYPE] 'only entries of a configured type, e.g. task'"),
+    ]
+}
+
+/// How much of a listing to print, shared by the listing subcommands.
+///
+fn page_args() -> [Arg<'static, 'static>; 3] {
+    [
+        Arg::from_usage("--limit=[N] 'print at most N results (0 for all)'"),
+        Arg::from_usage("--offset=[N] 'skip the first N results'")
+            .conflicts_with("page"),
+        Arg::from_usage("--page=[N] 'print the Nth page of --limit results, from 1'"),
+    ]
+}
+
+/// Which part of a listing to print, per `page_args`.
+///
+#[derive(Debug, Default)]
+pub struct Page {
+    limit: Option<usize>,
+    offset: usize,
+}
+
+impl Page {
+    fn from_args(ctx: &Ctx, sub_m: &ArgMatches) -> Result<Page> {
+        let number = |name: &str| -> Result<Option<usize>> {
+            match sub_m.value_of(name) {
+                Some(n) => Ok(Some(n.parse::<usize>()
+                    .chain_err(|| format!("bad --{} '{}'", name, n))?)),
+                None => Ok(None),
+            }
+        };
+        let limit = match number("limit")? {
+            Some(0) => None,
+            Some(n) => Some(n),
+            None => ctx.config.default_limit.filter(|n| *n > 0),
+        };
+        let offset = match (number("page")?, limit) {
+            (Some(0), _) => bail!("pages are numbered from 1"),
+            (Some(page), Some(limit)) => match (page - 1).checked_mul(limit) {
+                Some(offset) => offset,
+                None => bail!("page {} is too far in", page),
             },
-        })
+            (Some(_), None) => bail!("--page needs --limit (or a default_limit)"),
+            (None, _) => number("offset")?.unwrap_or(0),
+        };
+        Ok(Page { limit, offset })
+    }
+
+    /// The part of `items` to print.
+    ///
+    fn apply<T>(&self, items: Vec<T>) -> Vec<T> {
+        items.into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect()
+    }
+}
+
+/// Which entries a listing should include, per `filter_args`.
+///
+#[derive(Debug, Default)]
+pub struct EntryFilter<'a> {
+    period: Option<Period>,
+    author: Option<String>,
+    tags: Vec<String>,
+    all_tags: bool,
+    /// The type to match, and every configured type, since which an entry
+    /// is depends on which other types' extensions also fit it.
+    kind: Option<(&'a str, &'a HashMap<String, EntryType>)>,
+    /// Whether an entry without a pubdate falls within `period` by the date
+    /// its filename starts with, as for `between`; see `Entry::date`.
+    filename_dates: bool,
+}
+
+impl<'a> EntryFilter<'a> {
+    fn from_args(ctx: &'a Ctx, sub_m: &'a ArgMatches) -> Result<EntryFilter<'a>> {
+        let mut filter = EntryFilter::default();
+
+        if let Some(week) = sub_m.value_of("week") {
+            filter.period = Some(Period::iso_week(week)?);
+        }
+        if let Some(quarter) = sub_m.value_of("quarter") {
+            let fiscal_start = ctx.config.fiscal_year_start.unwrap_or(1);
+            filter.period = Some(Period::quarter(quarter, fiscal_start)?);
+        }
+        filter.author = sub_m.value_of("author").map(String::from);
+        filter.tags = tags::parse(sub_m.value_of("tags").unwrap_or(""));
+        filter.all_tags = sub_m.is_present("all-tags");
+        if let Some(kind) = sub_m.value_of("type") {
+            entry_types::get(&ctx.config.types, kind)?;
+            filter.kind = Some((kind, &ctx.config.types));
+        }
+        Ok(filter)
+    }
+
+    /// Entries without a date never fall within a period, and those
+    /// without an author never match an author.
+    ///
+    fn matches(&self, e: &Entry) -> bool {
+        let date = match self.filename_dates {
+            true => e.date(),
+            false => e.pubdate().map(|d| d.naive_local().date()),
+        };
+        let in_period = match self.period {
+            None => true,
+            Some(p) => date.is_some_and(|d| p.contains(d)),
+        };
+        let by_author = match self.author {
+            None => true,
+            Some(ref a) => e.author() == Some(a.as_str()),
+        };
+        let tags = e.get_tags().unwrap_or_default();
+        let has = |t: &String| tags.iter().any(|e_t| tags::is_within(e_t, t));
+        let tagged = match (self.tags.is_empty(), self.all_tags) {
+            (true, _) => true,
+            (false, true) => self.tags.iter().all(has),
+            (false, false) => self.tags.iter().any(has),
+        };
+        let of_kind = match self.kind {
+            None => true,
+            Some((kind, types)) => entry_types::type_of(types, &e.path) == Some(kind),
+        };
+        in_period && by_author && tagged && of_kind
+    }
+}
+ 
+/// The journal's entries, leaving out archived ones unless `--all` was
+/// given.
+///
+fn get_entries(ctx: &Ctx) -> impl Iterator<Item = Entry> + '_ {
+    get_all_entries(ctx).filter(move |e| ctx.all || !archive::is_archived(&ctx.path, e))
+}
+
+/// Every entry in the journal, archived or not. Entries that can't be read
+/// are reported on stderr, the first time only.
+///
+fn get_all_entries(ctx: &Ctx) -> impl Iterator<Item = Entry> {
+    if let Some(entries) = ctx.loaded.take() {
+        return entries.into_iter();
+    }
+    let (entries, errors) = ctx.storage.entries_checked();
+    if !ctx.reported.replace(true) {
+        report_entry_errors(&errors);
+    }
+    entries.into_iter()
+}
+
+/// The ids of every entry in the journal, archived or not.
+///
+fn entry_ids(ctx: &Ctx) -> HashSet<String> {
+    get_all_entries(ctx).filter_map(|e| e.get_id().map(String::from)).collect()
+}
+
+fn report_entry_errors(errors: &[Error]) {
+    for e in errors.iter() {
+        eprintln!("warning: {}", e);
+    }
+}
+
+/// Open the configured editor (or $EDITOR) on `path`.
+///
+fn edit(ctx: &Ctx, path: &Path) -> Result<()> {
+    edit_at(ctx, path, None)
+}
+
+/// Like `edit`, but positioned at `line` if given.
+///
+fn edit_at(ctx: &Ctx, path: &Path, line: Option<usize>) -> Result<()> {
+    Editor::new(ctx.config.editor.as_deref())?.open(path, line)?;
+    println!("{}", path.display());
+    Ok(())
 }
 
-fn edit(path: &str) {
-    let editor = match env::var_os("EDITOR") {
-        Some(v) => v.into_string().unwrap(),
-        // Fall back to "nvim" for the default editor.
-        None => String::from("nvim"),
+/// Run an external transcription command over an audio file and return
+/// whatever it printed to stdout.
+///
+/// Any `{}` in the command is replaced with the audio path; if there is none,
+/// the path is passed as the final argument.
+///
+fn transcribe(audio_path: &str, transcriber: &str) -> Result<String> {
+    let mut parts = transcriber.split_whitespace();
+    let prog = match parts.next() {
+        Some(p) => p,
+        None => bail!("empty transcriber command"),
     };
-    Command::new(editor)
-        .arg(path)
-        .stdout(Stdio::inherit())
+    let mut args: Vec<String> = parts
+        .map(|a| a.replace("{}", audio_path)).collect();
+    if !transcriber.contains("{}") {
+        args.push(audio_path.to_owned());
+    }
+
+    let output = Command::new(prog)
+        .args(&args)
         .stderr(Stdio::inherit())
-        .output()
-        .expect("uhoh");
-    println!("{}", path); 
+        .output()?;
+    if !output.status.success() {
+        bail!("transcriber '{}' failed: {}", transcriber, output.status);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+/// Copy a file into the journal's asset store and return its path relative
+/// to the journal root, suitable for linking from an entry body.
+///
+fn attach_file(ctx: &Ctx, file: &Path) -> Result<String> {
+    assets::store(&ctx.path, file)
+}
+
+/// Attach `file` to the entry `id`, appending a link to it (an image, for
+/// images) to the body. The file goes into the shared store (see `assets`)
+/// rather than a directory of the entry's own, so that it's kept once
+/// however many entries it's attached to.
+///
+pub fn attach(ctx: &Ctx, id: &str, file: &Path) -> Result<()> {
+    let entry = entry_by_id(ctx, id)?;
+    if entry.is_encrypted {
+        bail!("{} is encrypted; attach files while editing it instead", id);
+    }
+    let rel = attach_file(ctx, file)?;
+    let link = filedb::link_from(&ctx.path, &entry.path, &rel);
+    let name = file.file_name().unwrap().to_string_lossy();
+    let link = match assets::is_image(&rel) {
+        true => format!("![{}]({})", name, link),
+        false => format!("[{}]({})", name, link),
+    };
+
+    filedb::append_body(&entry.path, &link)?;
+    println!("{}", rel);
+    Ok(())
+}
+
+/// Print the stored files the entry `id` links to, with the names they were
+/// attached under.
+///
+pub fn list_attachments(ctx: &Ctx, id: &str) -> Result<()> {
+    let entry = entry_by_id(ctx, id)?;
+    let names = assets::names(&ctx.path)?;
+    let mut seen = HashSet::new();
+    for target in entry.local_links() {
+        let path = match entry.resolve_link(&ctx.path, target) {
+            Some(p) => p,
+            None => continue,
+        };
+        let rel = match path.strip_prefix(&ctx.path) {
+            Ok(rel) if assets::is_asset(rel) => rel.to_string_lossy().into_owned(),
+            _ => continue,
+        };
+        if !seen.insert(rel.clone()) {
+            continue;
+        }
+        let name = names.get(&rel).map(|n| n.join(", ")).unwrap_or_default();
+        println!("{}\t{}", rel, name);
+    }
+    Ok(())
 }
 
 /// Crate a new entry, populating it with front matter, and open $EDITOR.
 ///
-/// Optionally populate it with input from stdin.
+/// Optionally start from a template, and populate it with input from stdin
+/// or with the transcription of an audio memo (which is kept alongside the
+/// entry as an attachment).
 ///
+#[allow(clippy::too_many_arguments)]
 pub fn new_entry(
-    files_path: &mut PathBuf, 
+    ctx: &Ctx, 
     name: &str, 
     tags: Option<&str>,
     read_body_from_stdin: bool,
+    audio: Option<(&str, &str)>,
+    kind: Option<&str>,
+    template: Option<&str>,
+    encrypt: bool,
+    preview: Option<Preview>,
 ) -> Result<()> {
     let now = datetime::now();
-    let filename = format!("{}-{}.md", now.format("%F"), name);
-    files_path.push(filename);
+    let kind = match kind {
+        Some(k) => Some(entry_types::get(&ctx.config.types, k)?),
+        None => None,
+    };
+    let template = template
+        .or(kind.and_then(|k| k.template.as_deref()))
+        .or(ctx.config.default_template.as_deref());
 
     let mut body = String::new();
+    let mut template_tags = None;
+    let mut fields = Vec::new();
+    if let Some(template_name) = template {
+        let t = templates::load(&template_dir(ctx), template_name)?;
+        body.push_str(t.body.trim());
+        body.push('\n');
+        template_tags = t.get_tags().map(|tags| tags.join(","));
+
+        let prompts = templates::prompts(&t)?;
+        if read_body_from_stdin && !prompts.is_empty() {
+            bail!("template '{}' asks questions, so --stdin can't be used", template_name);
+        }
+        for (field, question) in prompts {
+            fields.push((field, prompt::ask(&question)?));
+        }
+    }
+    let type_tags = kind.filter(|k| !k.tags.is_empty()).map(|k| k.tags.join(","));
+    let tags = tags.or(template_tags.as_deref()).or(type_tags.as_deref());
+
     if read_body_from_stdin {
         stdin().read_to_string(&mut body)?;
     }
-     
-    let pathstr = files_path.to_str().unwrap();
- 
-    if files_path.exists() {
-        bail!("file with path {} already exists", pathstr);
+
+    if !fields.iter().any(|(k, _)| k == "title") {
+        fields.insert(0, ("title".to_owned(), title_from_name(name)));
+    }
+
+    let path = entry_path(ctx, name, now, kind)?;
+    if path.exists() {
+        bail!(ctx.locale.fmt(Msg::FileExists, &path.display().to_string()));
     }                       
 
-    let entries: Vec<Entry> = get_entries(files_path).collect();
-    let existing_ids: HashSet<&str> = entries.iter()
-        .filter_map(|e| e.get_id()).collect();
+    if let Some((audio_path, transcriber)) = audio {
+        body.push_str(&transcribe(audio_path, transcriber)?);
+        let link = attach_file(ctx, Path::new(audio_path))?;
+        body.push_str(&format!("\n\n[audio memo]({})", link));
+    }
 
-    let id = match existing_ids.contains(name) {
-        true => "",
-        false => name,
-    };
+    if let Some(preview) = preview {
+        let draft = render_entry(ctx, name, kind, tags, now, &body, &fields, None)?;
+        let contents = &draft.contents;
+        let frontmatter_len = contents.find("---\n").map_or(contents.len(), |i| i + 4);
+        match preview {
+            Preview::Frontmatter => print!("{}\n{}", draft.path.display(), &contents[..frontmatter_len]),
+            Preview::Entry => print!("{}", contents),
+        }
+        return Ok(());
+    }
 
-    let mut output = File::create(pathstr)?;
-    let contents =  format!(
-        "tags: {}\nid: {}\npubdate: {}\n---\n\n{}\n", 
-        tags.unwrap_or(""), id, datetime::to_str(now), body);
-    write!(output, "{}", contents).expect("write failed");
-    edit(pathstr);
+    if encrypt {
+        // Never let the plaintext touch the journal.
+        let body = crypt::encrypt(encryption_recipient(ctx)?, &body)?;
+        let path = create_entry(ctx, name, kind, tags, now, body.trim_end(), &fields)?;
+        edit_encrypted(ctx, &path)?;
+        return Ok(());
+    }
+    let path = create_entry(ctx, name, kind, tags, now, &body, &fields)?;
+    let line = editor::start_of_body(&Entry::from_path(&path)?);
+    edit_at(ctx, &path, Some(line))?;
     Ok(())
 }
 
-/// Print tags sorted by related entry count.
-/// 
-pub fn query_tags(files_path: PathBuf) -> Result<()> {
-    let entries = get_entries(&files_path);
-    let mut counts: HashMap<String, i32> = HashMap::new();
+/// What `jrni n` prints instead of writing the entry.
+///
+#[derive(Debug, Clone, Copy)]
+pub enum Preview {
+    /// The path the entry would be written to, and its frontmatter.
+    Frontmatter,
+    /// The entry as it would be written.
+    Entry,
+}
+
+fn encryption_recipient(ctx: &Ctx) -> Result<&str> {
+    match ctx.config.encrypt.recipient {
+        Some(ref r) => Ok(r),
+        None => bail!("set encrypt.recipient in the config to encrypt entries"),
+    }
+}
 
-    for e in entries {
-        if let Some(tags) = e.get_tags() {
-            for t in tags.into_iter() {
-                *counts.entry(t.to_owned()).or_insert(0) += 1;
-            }
-        }        
+/// Open `e` in the editor, at `line` if given, by way of `edit_encrypted`
+/// if its body is encrypted.
+///
+fn edit_entry(ctx: &Ctx, e: &Entry, line: Option<usize>) -> Result<()> {
+    match e.is_encrypted {
+        true => edit_encrypted(ctx, &e.path),
+        false => edit_at(ctx, &e.path, line),
+    }
+}
+
+/// Open $EDITOR on the decrypted body of an encrypted entry, kept in a
+/// temporary file for the duration, and encrypt it again afterwards if it
+/// was changed.
+///
+fn edit_encrypted(ctx: &Ctx, path: &Path) -> Result<()> {
+    let identity = match ctx.config.encrypt.identity {
+        Some(ref i) => i,
+        None => bail!("set encrypt.identity in the config to edit encrypted entries"),
+    };
+    let raw = fs::read_to_string(path)?;
+    let (frontmatter, ciphertext) = crypt::split_entry(&raw);
+    let plaintext = crypt::decrypt(identity, ciphertext)?;
+
+    // Outside the journal, so it's never synced or backed up with it, in a
+    // fresh directory only we can read.
+    let dir = private_dir(&format!("jrni-decrypted-{}", std::process::id()))?;
+    let tmp = dir.join(path.file_name().unwrap());
+    let edited = edit_decrypted(ctx, &tmp, &plaintext);
+    fs::remove_dir_all(&dir)?;
+    let (modified, edited) = edited?;
+
+    if modified {
+        let ciphertext = crypt::encrypt(encryption_recipient(ctx)?, &edited)?;
+        filedb::write_atomic(path, &format!("{}{}", frontmatter, ciphertext))?;
     }
+    println!("{}", path.display());
+    Ok(())
+}
 
-    let mut sorted: Vec<(String, i32)> = counts.into_iter().collect();
+/// Write `plaintext` to `tmp`, open the editor on it, and read it back,
+/// with whether the editor says it was changed.
+///
+fn edit_decrypted(ctx: &Ctx, tmp: &Path, plaintext: &str) -> Result<(bool, String)> {
+    let mut opts = fs::OpenOptions::new();
+    opts.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut opts, 0o600);
+    opts.open(tmp)?.write_all(plaintext.as_bytes())?;
+    let modified = Editor::new(ctx.config.editor.as_deref())?.open(tmp, None)?;
+    Ok((modified, fs::read_to_string(tmp)?))
+}
 
-    sorted.sort_unstable_by_key(|v| v.1);
+/// A new directory `name` that only its owner can use, in $XDG_RUNTIME_DIR
+/// if set (which is usually kept in memory) or else the temporary directory.
+/// Fails rather than reuse one that's already there.
+///
+fn private_dir(name: &str) -> Result<PathBuf> {
+    let base = match env::var_os("XDG_RUNTIME_DIR") {
+        Some(d) if !d.is_empty() => PathBuf::from(d),
+        _ => env::temp_dir(),
+    };
+    let dir = base.join(name);
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(&dir)
+        .chain_err(|| format!("can't create {}", dir.display()))?;
+    Ok(dir)
+}
 
-    for (tag, count) in sorted.iter() {
-        println!("{} {}", tag, count);
+/// Create a quick entry from a single line of text without opening $EDITOR.
+///
+/// A leading date expression, jrnl-style ("yesterday 9pm: ..."), is used as
+/// the pubdate and stripped from the body. The entry name is derived from
+/// the first few words of the text.
+///
+pub fn quick_entry(ctx: &Ctx, text: &str, tags: Option<&str>) -> Result<()> {
+    let now = datetime::now();
+    let (when, body) = match datetime::split_leading(text, now) {
+        Some((when, rest)) => (when, rest),
+        None => (now, text),
+    };
+
+    let name = slugify(&body.split_whitespace().take(5)
+        .collect::<Vec<&str>>().join(" "));
+    if name.is_empty() {
+        bail!("refusing to create an empty entry");
     }
 
+    let path = create_entry(ctx, &name, None, tags, when, body, &[])?;
+    println!("{}", path.display());
+    Ok(())
+}
+
+/// Add `text` to the end of the entry `id`, under a heading with the time
+/// if `timestamp`.
+///
+pub fn append(ctx: &Ctx, id: &str, text: &str, timestamp: bool) -> Result<()> {
+    if text.trim().is_empty() {
+        bail!("nothing to append");
+    }
+    let entry = entry_by_id(ctx, id)?;
+    if entry.is_encrypted {
+        bail!("'{}' is encrypted; use `jrni e` to edit it", id);
+    }
+    let text = match timestamp {
+        true => format!("## {}\n\n{}", datetime::now().format("%F %R"), text.trim()),
+        false => text.trim().to_owned(),
+    };
+    filedb::append_body(&entry.path, &text)?;
+    println!("{}", entry.path.display());
     Ok(())
 }
 
-pub fn edit_by_id(files_path: PathBuf, id: &str) -> Result<()> {
-    let entries = get_entries(&files_path);
+/// Name of the entry `jrni today` keeps for each day.
+const DAILY_NAME: &str = "daily";
 
-    for e in entries.into_iter() {
-        if let Some(e_id) = e.get_id() {
-            if e_id == id {
-                edit(e.path.to_str().unwrap());
-                return Ok(());
-            }
+/// Open today's entry, creating it first if there isn't one, or with
+/// `append`, add a timestamped line to it without opening the editor.
+///
+/// Today's entry is the daily entry dated today. New ones get the date in
+/// their id, `daily-2024-01-02`, so each day's is distinct.
+///
+pub fn today(ctx: &Ctx, append: Option<&str>) -> Result<()> {
+    let now = datetime::now();
+    let day = now.date_naive();
+    let mut taken = HashSet::new();
+    let mut existing = None;
+    for e in get_all_entries(ctx) {
+        let id = e.get_id().unwrap_or("");
+        let daily = id == DAILY_NAME || id.starts_with(&format!("{}-", DAILY_NAME));
+        let dated = e.pubdate().map(|d| d.with_timezone(&Local).date_naive());
+        if existing.is_none() && daily && dated == Some(day) {
+            existing = Some(e.path.clone());
+        }
+        taken.insert(id.to_owned());
+    }
+
+    let path = match existing {
+        Some(path) => path,
+        None => {
+            let id = format!("{}-{}", DAILY_NAME, day.format("%F"));
+            let draft = journal::draft(&ctx.path, &ctx.config, &NewEntry {
+                name: DAILY_NAME,
+                id: Some(&id),
+                kind: None,
+                tags: None,
+                when: now,
+                body: "",
+                fields: &[],
+                taken_ids: Some(&taken),
+            })?;
+            journal::write(&draft)?
+        },
+    };
+
+    let entry = Entry::from_path(&path)?;
+    let text = match append {
+        Some(text) => text,
+        None => {
+            edit_entry(ctx, &entry, None)?;
+            return Ok(());
+        },
+    };
+    if entry.is_encrypted {
+        bail!("today's entry is encrypted; use `jrni today` to edit it");
+    }
+    filedb::append_line(&path, &format!("- {} {}", now.format("%H:%M"), text))?;
+    println!("{}", path.display());
+    Ok(())
+}
+
+/// Date photos by their EXIF data and file them into the journal: photos
+/// from a day that already has an entry are linked from it, and otherwise a
+/// new entry is offered for that day.
+///
+pub fn import_photos(ctx: &Ctx, files: &[&str]) -> Result<()> {
+    let paths: Vec<&Path> = files.iter().map(Path::new).collect();
+    let mut by_day: BTreeMap<NaiveDate, Vec<exif::PhotoMeta>> = BTreeMap::new();
+    for photo in exif::read(&paths)? {
+        match photo.taken {
+            Some(t) => by_day.entry(t.date()).or_default().push(photo),
+            None => eprintln!("{}: no date in its EXIF data, skipping", photo.path.display()),
         }
     }
 
-    println!("Couldn't find entry by id '{}'", id);
+    let mut entries: Vec<Entry> = get_entries(ctx).collect();
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+    for (day, mut photos) in by_day {
+        photos.sort_by_key(|p| p.taken);
+        let existing = entries.iter()
+            .find(|e| e.pubdate().map(|d| d.naive_local().date()) == Some(day));
+
+        match existing {
+            Some(e) => {
+                if e.is_encrypted {
+                    eprintln!("{} is encrypted; not linking photos from {} into it",
+                              e.path.display(), day);
+                    continue;
+                }
+                if !ctx.confirm.ask("photos", &format!(
+                        "link {} photos from {} into {}?",
+                        photos.len(), day, e.path.display()))? {
+                    continue;
+                }
+                let mut links = String::new();
+                for p in photos.iter() {
+                    let rel = attach_file(ctx, &p.path)?;
+                    links.push_str(&photo_link(&filedb::link_from(&ctx.path, &e.path, &rel), p));
+                }
+                filedb::append_body(&e.path, &links)?;
+                println!("{}", e.path.display());
+            },
+            None => {
+                if !ctx.confirm.ask("photos", &format!(
+                        "create an entry for {} with {} photos?", day, photos.len()))? {
+                    continue;
+                }
+                let taken = photos[0].taken.unwrap();
+                let when = Local.from_local_datetime(&taken).earliest()
+                    .unwrap_or_else(datetime::now);
+                let path = entry_path(ctx, "photos", when, None)?;
+                let mut body = String::new();
+                for p in photos.iter() {
+                    let rel = attach_file(ctx, &p.path)?;
+                    body.push_str(&photo_link(&filedb::link_from(&ctx.path, &path, &rel), p));
+                }
+                let fields: Vec<(String, String)> = photos.iter()
+                    .filter_map(|p| p.gps)
+                    .map(|(lat, lon)| ("location".to_owned(), format!("{:.5},{:.5}", lat, lon)))
+                    .take(1)
+                    .collect();
+                let path = create_entry(ctx, "photos", None, None, when, body.trim_end(), &fields)?;
+                println!("{}", path.display());
+            },
+        }
+    }
     Ok(())
 }
 
-/// Print the id associated with each entry.
+/// An image link for an attached photo, captioned with the time it was taken
+/// and followed by where, if known.
+///
+fn photo_link(link: &str, p: &exif::PhotoMeta) -> String {
+    let time = p.taken.map(|t| t.format("%H:%M").to_string()).unwrap_or_default();
+    match p.gps {
+        Some((lat, lon)) => format!("![{}]({}) ({:.5}, {:.5})\n", time, link, lat, lon),
+        None => format!("![{}]({})\n", time, link),
+    }
+}
+
+/// Lowercase some text and collapse anything that isn't alphanumeric into
+/// single dashes, e.g. "Had a great dinner!" -> "had-a-great-dinner".
 ///
-pub fn query_ids(files_path: PathBuf) -> Result<()> {
-    for e in get_entries(&files_path).into_iter() {
-        if let Some(id) = e.get_id() {
-            println!("{}", id);
+fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.chars().flat_map(|c| c.to_lowercase()) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
         }
     }
+    slug.trim_end_matches('-').to_owned()
+}
+
+/// Undo `slugify`, as far as it can be: "had-a-great-dinner" -> "Had a
+/// great dinner".
+///
+fn title_from_name(name: &str) -> String {
+    let words = name.replace(['-', '_'], " ");
+    let mut chars = words.trim().chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Where a new entry `name` written at `when` goes; see `journal::entry_path`.
+///
+fn entry_path(ctx: &Ctx, name: &str, when: DateTime<Local>, kind: Option<&EntryType>) -> Result<PathBuf> {
+    journal::entry_path(&ctx.path, &ctx.config, name, when, kind)
+}
+
+/// `name`, or if an entry's already at its path, the first of `name-2`,
+/// `name-3`, ... that isn't.
+///
+fn unique_name(ctx: &Ctx, name: &str, when: DateTime<Local>) -> Result<String> {
+    let mut unique = name.to_owned();
+    let mut n = 2;
+    while entry_path(ctx, &unique, when, None)?.exists() {
+        unique = format!("{}-{}", name, n);
+        n += 1;
+    }
+    Ok(unique)
+}
+
+/// Create the journal at the given path and write a config pointing to it,
+/// asking about each setting if `interactive`.
+///
+pub fn init(ctx: &Ctx, interactive: bool) -> Result<()> {
+    let config_path = match Config::default_path() {
+        Some(p) => p,
+        None => bail!("can't tell where the config should go on this system"),
+    };
+    if config_path.exists() {
+        bail!("{} exists already; edit it, or move it aside to start over",
+              config_path.display());
+    }
+    let setup = match interactive {
+        true => Setup::ask(&ctx.path, &ctx.locale)?,
+        false => Setup::defaults(&ctx.path),
+    };
+    let path = setup.path.clone();
+    setup.apply(&config_path)?;
+    println!("wrote {}", config_path.display());
+    println!("journal is at {}; try `jrni n first-entry`", path.display());
+    Ok(())
+}
+
+/// Write a new entry with frontmatter into the journal and return its path.
+///
+fn create_entry(
+    ctx: &Ctx,
+    name: &str,
+    kind: Option<&EntryType>,
+    tags: Option<&str>,
+    when: DateTime<Local>,
+    body: &str,
+    fields: &[(String, String)],
+) -> Result<PathBuf> {
+    journal::write(&render_entry(ctx, name, kind, tags, when, body, fields, None)?)
+}
+
+/// A new entry as it would be written; see `journal::draft`. Warns about
+/// a taken id and about tags outside the configured vocabulary.
+///
+#[allow(clippy::too_many_arguments)]
+fn render_entry(
+    ctx: &Ctx,
+    name: &str,
+    kind: Option<&EntryType>,
+    tags: Option<&str>,
+    when: DateTime<Local>,
+    body: &str,
+    fields: &[(String, String)],
+    taken_ids: Option<&HashSet<String>>,
+) -> Result<Draft> {
+    let path = entry_path(ctx, name, when, kind)?;
+    if path.exists() {
+        bail!(ctx.locale.fmt(Msg::FileExists, &path.display().to_string()));
+    }
+
+    // Rather than `filedb::ids`, which won't go on past an unreadable file.
+    let all_ids;
+    let taken_ids = match taken_ids {
+        Some(taken) => taken,
+        None => {
+            all_ids = entry_ids(ctx);
+            &all_ids
+        },
+    };
+    let draft = journal::draft(&ctx.path, &ctx.config, &NewEntry {
+        name,
+        id: None,
+        kind,
+        tags: tags.map(tags::parse),
+        when,
+        body,
+        fields,
+        taken_ids: Some(taken_ids),
+    })?;
+    if draft.id != name {
+        eprintln!("id '{}' is taken; using '{}'", name, draft.id);
+    }
+    for t in draft.tags.iter().filter(|t| !ctx.config.is_known_tag(t)) {
+        eprintln!("warning: tag '{}' isn't in the configured vocabulary", t);
+    }
+    Ok(draft)
+}
+
+/// Print each entry with lines matching `pattern`, grep-style: matching
+/// lines are marked with `:` and context lines with `-`.
+///
+/// With `alfred`, print JSON for an Alfred or Raycast script filter instead.
+///
+pub fn search_entries(
+    ctx: &Ctx,
+    pattern: &regex::Regex,
+    context: usize,
+    alfred: bool,
+    filter: &EntryFilter,
+    page: &Page,
+) -> Result<()> {
+    let entries: Vec<Entry> = get_entries(ctx).filter(|e| filter.matches(e)).collect();
+    let results = page.apply(ctx.storage.search(&entries, pattern, context));
+
+    if alfred {
+        // Alfred opens `arg` from somewhere else entirely, so it has to be
+        // absolute whatever `--path` was.
+        let cwd = env::current_dir()?;
+        let items: Vec<serde_json::Value> = results.iter().map(|(e, blocks)| {
+            let path = fs::canonicalize(&e.path).unwrap_or_else(|_| cwd.join(&e.path));
+            let path = path.display().to_string();
+            let snippet = blocks.iter().flat_map(|b| b.lines.iter())
+                .find(|(_, matched, _)| *matched)
+                .map(|(_, _, text)| text.trim())
+                .unwrap_or("");
+            serde_json::json!({
+                "uid": path,
+                "type": "file",
+                "title": e.title(),
+                "subtitle": snippet,
+                "arg": path,
+            })
+        }).collect();
+        println!("{}", serde_json::json!({ "items": items }));
+        return Ok(());
+    }
+    if ctx.json {
+        let found: Vec<serde_json::Value> = results.iter().map(|(e, blocks)| {
+            let mut found = entry_json(e);
+            found["matches"] = blocks.iter().flat_map(|b| b.lines.iter())
+                .filter(|(_, matched, _)| *matched)
+                .map(|(n, _, text)| serde_json::json!({ "line": n, "text": text }))
+                .collect();
+            found
+        }).collect();
+        println!("{}", serde_json::Value::from(found));
+        return Ok(());
+    }
+
+    for (e, blocks) in results {
+        let rel = e.path.strip_prefix(&ctx.path).unwrap_or(&e.path);
+        println!("{}{} ({})", e.title(), progress_suffix(e), rel.display());
+        for (i, block) in blocks.iter().enumerate() {
+            if i > 0 {
+                println!("   --");
+            }
+            for (n, matched, text) in block.lines.iter() {
+                println!("{:>5}{}{}", n, if *matched { ':' } else { '-' }, text);
+            }
+        }
+        println!();
+    }
+    Ok(())
+}
+
+/// The range of days `between` was asked for.
+///
+fn date_range(sub_m: &ArgMatches) -> Result<Period> {
+    let now = datetime::now();
+    let today = now.naive_local().date();
+    let day = |s: &str| match datetime::parse_human(s, now) {
+        Some(d) => Ok(d.naive_local().date()),
+        None => Err(Error::from(format!("can't understand the date '{}'", s))),
+    };
+
+    if let Some(since) = sub_m.value_of("since") {
+        return Period::between(datetime::before(today, since)?, today);
+    }
+    if sub_m.is_present("last-month") {
+        return Period::previous_month(today);
+    }
+    match (sub_m.value_of("start"), sub_m.value_of("end")) {
+        (Some(start), end) => Period::between(day(start)?, end.map_or(Ok(today), day)?),
+        (None, _) => bail!("give a start date, --since, or --last-month"),
+    }
+}
+
+/// Print the date, id and title of each entry, oldest first.
+///
+pub fn query_entries(ctx: &Ctx, filter: &EntryFilter, page: &Page) -> Result<()> {
+    let mut entries: Vec<Entry> = get_entries(ctx).filter(|e| filter.matches(e)).collect();
+    entries.sort_by(|a, b| a.date().cmp(&b.date()).then(a.pubdate().cmp(&b.pubdate()))
+        .then(a.path.cmp(&b.path)));
+    let entries = page.apply(entries);
+
+    if ctx.json {
+        return print_entries_json(&entries);
+    }
+    for e in entries.iter() {
+        println!("{}", entry_row(e));
+    }
+    Ok(())
+}
+
+/// An entry's date, id and title, as `l` lists it.
+///
+fn entry_row(e: &Entry) -> String {
+    let date = e.date().map(|d| d.to_string()).unwrap_or_default();
+    format!("{:10}  {:20}  {}", date, e.get_id().unwrap_or("-"), e.title())
+}
+
+/// List the `n` entries most recently created or modified, most recent
+/// first, with when that was.
+///
+pub fn query_recent(ctx: &Ctx, n: usize) -> Result<()> {
+    let touched = |e: &Entry| e.modified().max(e.created());
+    let mut entries: Vec<Entry> = get_entries(ctx).filter(|e| touched(e).is_some()).collect();
+    entries.sort_by(|a, b| touched(b).cmp(&touched(a)).then(a.path.cmp(&b.path)));
+    entries.truncate(n);
+
+    if ctx.json {
+        let rows: Vec<serde_json::Value> = entries.iter().map(|e| {
+            let mut row = entry_json(e);
+            row["touched"] = DateTime::<Local>::from(touched(e).unwrap()).to_rfc3339().into();
+            row
+        }).collect();
+        println!("{}", serde_json::Value::from(rows));
+        return Ok(());
+    }
+    for e in entries.iter() {
+        let when = DateTime::<Local>::from(touched(e).unwrap());
+        println!("{}  {}", when.format("%F %R"), entry_row(e));
+    }
+    Ok(())
+}
+
+/// Archive the entry `id`, moving it under `archive/`.
+///
+pub fn archive_entry(ctx: &Ctx, id: &str) -> Result<()> {
+    let entry = entry_by_id(ctx, id)?;
+    if archive::is_archived(&ctx.path, &entry) {
+        bail!("'{}' is already archived", id);
+    }
+    println!("{}", archive::archive(&ctx.path, &entry.path)?.display());
+    Ok(())
+}
+
+/// Bring the archived entry `id` back: out of `archive/` if it's there, and
+/// unmarked if its frontmatter says it's archived.
+///
+pub fn unarchive_entry(ctx: &Ctx, id: &str) -> Result<()> {
+    let entry = entry_by_id(ctx, id)?;
+    if !archive::is_archived(&ctx.path, &entry) {
+        bail!("'{}' isn't archived", id);
+    }
+    let mut path = entry.path.clone();
+    if archive::is_under_archive(&ctx.path, &path) {
+        path = archive::unarchive(&ctx.path, &path)?;
+    }
+    if entry.frontmatter.extra.contains_key("archived") {
+        filedb::set_field(&path, "archived", "false")?;
+    }
+    println!("{}", path.display());
+    Ok(())
+}
+
+/// List what the retention rules would delete or archive, and with `apply`,
+/// do it once confirmed. The list is always shown first.
+///
+pub fn gc(ctx: &Ctx, apply: bool) -> Result<()> {
+    let entries: Vec<Entry> = get_all_entries(ctx).collect();
+    let today = datetime::now().naive_local().date();
+    let planned = retention::plan(&ctx.config.retention, &ctx.path, &entries, today)?;
+
+    for (action, e) in planned.iter() {
+        let rel = e.path.strip_prefix(&ctx.path).unwrap_or(&e.path);
+        println!("{:<8} {}", action.as_str(), rel.display());
+    }
+    if !apply || planned.is_empty() {
+        return Ok(());
+    }
+    let deleting = planned.iter().filter(|(a, _)| *a == retention::Action::Delete).count();
+    let action = format!("delete {} and archive {} entries?", deleting, planned.len() - deleting);
+    if !ctx.confirm.ask("gc", &action)? {
+        return Ok(());
+    }
+    retention::apply(&ctx.path, &planned)?;
+    Ok(())
+}
+
+/// Tag of rollup entries, which are left out of later rollups.
+const ROLLUP_TAG: &str = "rollup";
+
+/// How the days a rollup covers are written in its heading.
+const ROLLUP_DATE: &str = "%A %-d %B %Y";
+
+/// Create an entry `name` linking to every entry from `period`, with a line
+/// about each, grouped by tag. Entries with several tags are listed under
+/// each.
+///
+pub fn rollup(ctx: &Ctx, name: &str, period: Period) -> Result<()> {
+    let mut entries: Vec<Entry> = get_entries(ctx)
+        .filter(|e| e.date().is_some_and(|d| period.contains(d)))
+        .filter(|e| !e.get_tags().unwrap_or_default().contains(&ROLLUP_TAG))
+        .collect();
+    let last = period.end.pred_opt().unwrap_or(period.end);
+    if entries.is_empty() {
+        bail!("no entries from {} to {}", period.start, last);
+    }
+    entries.sort_by(|a, b| a.date().cmp(&b.date()).then(a.path.cmp(&b.path)));
+
+    let mut by_tag: BTreeMap<&str, Vec<&Entry>> = BTreeMap::new();
+    for e in entries.iter() {
+        let tags = e.get_tags().unwrap_or_default();
+        if tags.is_empty() {
+            by_tag.entry("untagged").or_default().push(e);
+        }
+        for t in tags {
+            by_tag.entry(t).or_default().push(e);
+        }
+    }
+
+    let now = datetime::now();
+    let path = entry_path(ctx, name, now, None)?;
+    let mut body = format!("# {} – {}\n",
+        ctx.locale.format_day(period.start, ROLLUP_DATE), ctx.locale.format_day(last, ROLLUP_DATE));
+    for (tag, tagged) in by_tag.iter() {
+        body.push_str(&format!("\n## {}\n\n", tag));
+        for e in tagged.iter() {
+            let link = match e.get_id() {
+                Some(id) => format!("[[{}]]", id),
+                None => {
+                    let rel = e.path.strip_prefix(&ctx.path).unwrap_or(&e.path);
+                    let target = filedb::link_from(&ctx.path, &path, &rel.to_string_lossy());
+                    format!("[{}]({})", e.title(), target)
+                },
+            };
+            let about = match e.summary() {
+                Some(s) => s.to_owned(),
+                None => preview(&e.body, 80),
+            };
+            let date = e.date().map(|d| d.to_string()).unwrap_or_default();
+            match about.is_empty() {
+                true => body.push_str(&format!("- {} {}\n", date, link)),
+                false => body.push_str(&format!("- {} {}: {}\n", date, link, about)),
+            }
+        }
+    }
+
+    let path = create_entry(ctx, name, None, Some(ROLLUP_TAG), now, body.trim_end(), &[])?;
+    println!("{}", path.display());
+    Ok(())
+}
+
+/// Import every entry at `path`, written by another tool in `format`, as a
+/// new entry dated when the original was written.
+///
+pub fn import_entries(ctx: &Ctx, format: &str, path: &Path) -> Result<()> {
+    let imported = import::read(format, path)?;
+    if imported.is_empty() {
+        bail!("found nothing to import in {}", path.display());
+    }
+    // Read once, rather than for every entry imported.
+    let mut taken = entry_ids(ctx);
+    for e in imported.iter() {
+        let slug = [e.id.as_deref(), e.title.as_deref(), e.name.as_deref()].iter()
+            .flatten()
+            .map(|s| slugify(s))
+            .chain(Some(slugify(&preview(&e.body, 40))))
+            .find(|s| !s.is_empty())
+            .unwrap_or_else(|| e.when.format("%H%M").to_string());
+        let name = unique_name(ctx, &slug, e.when)?;
+        let fields: Vec<(String, String)> = e.title.iter()
+            .map(|t| ("title".to_owned(), t.clone()))
+            .chain(e.fields.iter().cloned())
+            .collect();
+        let draft = render_entry(
+            ctx, &name, None, Some(&e.tags.join(",")), e.when, &e.body, &fields, Some(&taken))?;
+        let created = journal::write(&draft)?;
+        taken.insert(draft.id);
+        println!("{}", created.display());
+    }
+    eprintln!("imported {} entries", imported.len());
+    Ok(())
+}
+
+/// Pipe the body of the entry `id` through a summarizer command and store
+/// what it prints as the entry's `summary`.
+///
+pub fn summarize(ctx: &Ctx, id: &str, command: Option<&str>) -> Result<()> {
+    let command = match command.or(ctx.config.summarizer.as_deref()) {
+        Some(c) => c,
+        None => bail!("no summarizer; pass --command or set summarizer in the config"),
+    };
+    let entry = entry_by_id(ctx, id)?;
+    if entry.is_encrypted {
+        bail!("{} is encrypted; not sending it to a summarizer", id);
+    }
+
+    let mut child = Command::new("sh")
+        .arg("-c").arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()?;
+    // Write from another thread, so a summarizer that prints as it reads
+    // can't deadlock us.
+    let mut input = child.stdin.take().unwrap();
+    let body = entry.body.clone();
+    let writer = std::thread::spawn(move || input.write_all(body.as_bytes()));
+    let output = child.wait_with_output()?;
+    writer.join().map_err(|_| Error::from("stdin writer panicked"))??;
+    if !output.status.success() {
+        bail!("summarizer '{}' failed: {}", command, output.status);
+    }
+
+    // Keep it to one line, so it sits well in frontmatter and listings.
+    let summary = String::from_utf8_lossy(&output.stdout)
+        .split_whitespace().collect::<Vec<_>>().join(" ");
+    if summary.is_empty() {
+        bail!("summarizer '{}' printed nothing", command);
+    }
+    filedb::set_field(&entry.path, "summary", &summary)?;
+    println!("{}", summary);
+    Ok(())
+}
+
+/// Run the `lint` checks on the entry `id`, and unless any fail (or `force`
+/// is given), stamp it with a `published` date.
+///
+pub fn publish(ctx: &Ctx, id: &str, force: bool) -> Result<()> {
+    let entries: Vec<Entry> = get_entries(ctx).collect();
+    let entry = match entries.iter().find(|e| e.get_id() == Some(id)) {
+        Some(e) => e,
+        None => bail!(ctx.locale.fmt(Msg::EntryNotFound, id)),
+    };
+
+    let problems = lint::check(&ctx.config.publish, &ctx.path, entry, &entries)?;
+    for p in problems.iter() {
+        eprintln!("{}: {}: {}", id, p.check, p.message);
+    }
+    if !problems.is_empty() && !force {
+        bail!("{} isn't ready to publish; fix the above or pass --force", id);
+    }
+    filedb::set_field(&entry.path, "published", &datetime::to_str(datetime::now()))
+}
+
+/// List the `n` entries most similar to `id`, with their similarity.
+///
+pub fn query_similar(ctx: &Ctx, id: &str, n: usize, command: Option<&str>) -> Result<()> {
+    let command = match command.or(ctx.config.embedder.as_deref()) {
+        Some(c) => c,
+        None => bail!("no embedder; pass --command or set embedder in the config"),
+    };
+    let entries: Vec<Entry> = get_entries(ctx).collect();
+    let entry = match entries.iter().find(|e| e.get_id() == Some(id)) {
+        Some(e) => e,
+        None => bail!(ctx.locale.fmt(Msg::EntryNotFound, id)),
+    };
+    if entry.is_encrypted {
+        bail!("{} is encrypted; not sending it to an embedder", id);
+    }
+
+    let mut embedder = Embedder::new(&ctx.path, command);
+    let found = similar::most_similar(&mut embedder, entry, &entries, n);
+    // Keep whatever was embedded before any failure, and once every entry's
+    // been embedded, only what's current.
+    embedder.save(found.is_ok())?;
+    let found = found?;
+
+    if ctx.json {
+        let found: Vec<serde_json::Value> = found.iter().map(|(score, e)| {
+            let mut found = entry_json(e);
+            found["similarity"] = serde_json::json!(score);
+            found
+        }).collect();
+        println!("{}", serde_json::Value::from(found));
+        return Ok(());
+    }
+    for (score, e) in found {
+        println!("{:.3}  {}", score, entry_row(e));
+    }
+    Ok(())
+}
+
+/// List the entries `id` links to, in the order it links to them. Links to
+/// ids no entry has are listed as missing.
+///
+pub fn query_links(ctx: &Ctx, id: &str) -> Result<()> {
+    let entries: Vec<Entry> = get_entries(ctx).collect();
+    let entry = match entries.iter().find(|e| e.get_id() == Some(id)) {
+        Some(e) => e,
+        None => bail!(ctx.locale.fmt(Msg::EntryNotFound, id)),
+    };
+    let by_id: HashMap<&str, &Entry> = entries.iter()
+        .filter_map(|e| Some((e.get_id()?, e)))
+        .collect();
+
+    let links = entry.wiki_links();
+    if ctx.json {
+        let linked: Vec<serde_json::Value> = links.iter()
+            .map(|l| match by_id.get(l) {
+                Some(e) => entry_json(e),
+                None => serde_json::json!({ "id": l, "missing": true }),
+            })
+            .collect();
+        println!("{}", serde_json::Value::from(linked));
+        return Ok(());
+    }
+    for l in links {
+        match by_id.get(l) {
+            Some(e) => println!("{}", entry_row(e)),
+            None => println!("{:10}  {:20}  (missing)", "", l),
+        }
+    }
+    Ok(())
+}
+
+/// List the entries that link to `id`, oldest first.
+///
+pub fn query_backlinks(ctx: &Ctx, id: &str) -> Result<()> {
+    let mut entries: Vec<Entry> = get_entries(ctx)
+        .filter(|e| e.get_id() != Some(id) && e.wiki_links().contains(&id))
+        .collect();
+    entries.sort_by(|a, b| a.date().cmp(&b.date()).then(a.path.cmp(&b.path)));
+
+    if ctx.json {
+        return print_entries_json(&entries);
+    }
+    for e in entries.iter() {
+        println!("{}", entry_row(e));
+    }
+    Ok(())
+}
+
+/// Print a row of `columns` per entry, oldest first or by `sort`, or a line
+/// per entry laid out by `template`. Besides frontmatter fields, columns can
+/// be `path`, `title`, `wordcount`, `progress` or `preview`.
+///
+#[allow(clippy::too_many_arguments)]
+pub fn list_entries(
+    ctx: &Ctx,
+    filter: &EntryFilter,
+    page: &Page,
+    columns: &[&str],
+    template: Option<&LineFormat>,
+    format: &str,
+    header: bool,
+    sort: &str,
+    max_preview: usize,
+) -> Result<()> {
+    let mut entries: Vec<Entry> = get_entries(ctx).filter(|e| filter.matches(e)).collect();
+    entries.sort_by(|a, b| a.pubdate().cmp(&b.pubdate()).then(a.path.cmp(&b.path)));
+    if sort != "pubdate" {
+        // Stable, so ties stay oldest first.
+        entries.sort_by(|a, b| {
+            let (a, b) = (column(a, sort, max_preview), column(b, sort, max_preview));
+            match (a.parse::<f64>(), b.parse::<f64>()) {
+                (Ok(x), Ok(y)) => x.partial_cmp(&y).unwrap_or(std::cmp::Ordering::Equal),
+                _ => a.cmp(&b),
+            }
+        });
+    }
+    let entries = page.apply(entries);
+
+    if ctx.json {
+        let rows: Vec<serde_json::Value> = entries.iter().map(|e| {
+            columns.iter()
+                .map(|c| (c.to_string(), serde_json::Value::from(column(e, c, max_preview))))
+                .collect::<serde_json::Map<_, _>>()
+                .into()
+        }).collect();
+        println!("{}", serde_json::Value::from(rows));
+        return Ok(());
+    }
+    if let Some(template) = template {
+        for e in entries.iter() {
+            println!("{}", template.render(|field| column(e, field, max_preview)));
+        }
+        return Ok(());
+    }
+    let sep = match format {
+        "csv" => ",",
+        _ => "\t",
+    };
+    if header {
+        let row: Vec<String> = columns.iter().map(|c| format_cell(c, format)).collect();
+        println!("{}", row.join(sep));
+    }
+    for e in entries.iter() {
+        let row: Vec<String> = columns.iter()
+            .map(|c| format_cell(&column(e, c, max_preview), format))
+            .collect();
+        println!("{}", row.join(sep));
+    }
+    Ok(())
+}
+
+fn column(e: &Entry, name: &str, max_preview: usize) -> String {
+    match name {
+        "path" => e.path.display().to_string(),
+        "title" => e.title().to_owned(),
+        "wordcount" => e.word_count().to_string(),
+        "preview" => preview(&e.body, max_preview),
+        "progress" => match e.task_progress() {
+            (_, 0) => String::new(),
+            (done, total) => format!("{}/{}", done, total),
+        },
+        _ => e.frontmatter.get(name).map(|v| filedb::inline_value(&v)).unwrap_or_default(),
+    }
+}
+
+/// The start of `body` on one line, cut off after `max` characters. Only as
+/// much of the body is looked at as is needed, however long the entry.
+///
+fn preview(body: &str, max: usize) -> String {
+    let mut out = String::new();
+    let mut used = 0;
+    for word in body.split_whitespace() {
+        let room = max.saturating_sub(used + (used > 0) as usize);
+        if room == 0 {
+            break;
+        }
+        if used > 0 {
+            out.push(' ');
+            used += 1;
+        }
+        let taken: String = word.chars().take(room).collect();
+        used += taken.chars().count();
+        out.push_str(&taken);
+        if taken.len() < word.len() {
+            break;
+        }
+    }
+    out
+}
+
+/// Quote or flatten a value so it stays in its cell.
+///
+fn format_cell(value: &str, format: &str) -> String {
+    match format {
+        "csv" if value.contains([',', '"', '\n']) =>
+            format!("\"{}\"", value.replace('"', "\"\"")),
+        "csv" => value.to_owned(),
+        _ => value.replace(['\t', '\n'], " "),
+    }
+}
+
+/// The entry `fm` should operate on, by `--file` or `--id`.
+///
+fn fm_path(ctx: &Ctx, sub_m: &ArgMatches) -> Result<PathBuf> {
+    match (sub_m.value_of("file"), sub_m.value_of("id")) {
+        (Some(file), _) => Ok(PathBuf::from(file)),
+        (None, Some(id)) => Ok(entry_by_id(ctx, id)?.path),
+        (None, None) => bail!("give --file or --id"),
+    }
+}
+
+/// Print a frontmatter field, lists comma-separated. Prints nothing and
+/// fails if the field isn't set.
+///
+pub fn fm_get(path: &Path, key: &str) -> Result<()> {
+    let entry = Entry::from_path(path)?;
+    if let Some(err) = entry.frontmatter_err {
+        bail!("couldn't parse the frontmatter of {}: {}", path.display(), err);
+    }
+    match entry.frontmatter.get(key) {
+        Some(v) => println!("{}", filedb::inline_value(&v)),
+        None => bail!("{} has no '{}' field", path.display(), key),
+    }
+    Ok(())
+}
+
+/// Set a frontmatter field, e.g. from an editor autocommand.
+///
+pub fn fm_set(path: &Path, key: &str, value: &str) -> Result<()> {
+    filedb::set_field(path, key, value)
+}
+
+/// Print a table of aggregates, a row per group.
+///
+pub fn print_stats(
+    ctx: &Ctx,
+    filter: &EntryFilter,
+    group_by: Option<&str>,
+    aggs: &[Agg],
+) -> Result<()> {
+    let entries: Vec<Entry> = get_entries(ctx).filter(|e| filter.matches(e)).collect();
+    let rows = stats::aggregate(&entries, group_by, aggs);
+
+    let mut table: Vec<Vec<String>> = vec![
+        std::iter::once(group_by.unwrap_or("").to_owned())
+            .chain(aggs.iter().map(|a| a.to_string()))
+            .collect()];
+    for row in rows {
+        table.push(std::iter::once(row.group)
+            .chain(row.values.into_iter().map(|v| v.map(stats::format_number).unwrap_or_default()))
+            .collect());
+    }
+
+    let widths: Vec<usize> = (0..=aggs.len())
+        .map(|i| table.iter().map(|r| r[i].chars().count()).max().unwrap_or(0))
+        .collect();
+    for row in table.iter() {
+        let cells: Vec<String> = row.iter().zip(widths.iter()).enumerate()
+            .map(|(i, (cell, w))| match i {
+                0 => format!("{:<w$}", cell, w = w),
+                _ => format!("{:>w$}", cell, w = w),
+            })
+            .collect();
+        println!("{}", cells.join("  ").trim_end());
+    }
+    Ok(())
+}
+
+/// Print an overview of writing habits: totals, words per month (or per ISO
+/// week if `weekly`), streaks, and the most used tags.
+///
+pub fn print_summary(ctx: &Ctx, filter: &EntryFilter, weekly: bool) -> Result<()> {
+    let entries: Vec<Entry> = get_entries(ctx).filter(|e| filter.matches(e)).collect();
+    let summary = stats::summarize(&entries, datetime::now().naive_local().date(), 10);
+
+    println!("entries          {}", summary.entries);
+    println!("words            {}", summary.words);
+    println!("words per entry  {:.0}", summary.average_words());
+    println!("current streak   {} days", summary.streak);
+    println!("longest streak   {} days", summary.longest_streak);
+
+    if weekly && !summary.words_by_week.is_empty() {
+        println!("\nwords per week");
+        for (monday, words) in summary.words_by_week.iter() {
+            println!("  {:<10}  {:>7}", monday.format("%G-W%V"), words);
+        }
+    } else if !summary.words_by_month.is_empty() {
+        println!("\nwords per month");
+        for (month, words) in summary.words_by_month.iter() {
+            println!("  {:<10}  {:>7}", ctx.locale.format_day(*month, "%b %Y"), words);
+        }
+    }
+    if !summary.top_tags.is_empty() {
+        println!("\ntop tags");
+        let width = summary.top_tags.iter().map(|(t, _)| t.chars().count()).max().unwrap_or(0);
+        for (tag, n) in summary.top_tags.iter() {
+            println!("  {:<w$}  {}", tag, n, w = width);
+        }
+    }
+    Ok(())
+}
+
+/// Evaluate each rule, printing those that fail, and fail if any do.
+///
+pub fn check_rules(ctx: &Ctx, rules: &[Rule]) -> Result<()> {
+    let entries: Vec<Entry> = get_entries(ctx).collect();
+    let today = datetime::now().naive_local().date();
+
+    let mut failed = 0;
+    for rule in rules {
+        let value = stats::metric(&rule.metric, &entries, today)?;
+        if !rule.holds(value) {
+            println!("failed: {} (is {})", rule, stats::format_number(value));
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        bail!("{} of {} rules failed", failed, rules.len());
+    }
+    Ok(())
+}
+
+/// Report problems with the frontmatter of every entry, failing if there
+/// are any, e.g. in a pre-commit hook.
+///
+pub fn check_journal(ctx: &Ctx) -> Result<()> {
+    let entries: Vec<Entry> = get_all_entries(ctx).collect();
+    let problems = lint::check_journal(&ctx.path, &entries);
+    for p in problems.iter() {
+        println!("{}: {}", p.check, p.message);
+    }
+    if !problems.is_empty() {
+        bail!("{} problems in {} entries", problems.len(), entries.len());
+    }
+    Ok(())
+}
+
+/// Check the entries staged in git (as staged), or `files`, or else every
+/// entry, against the rest of the journal; see `lint::verify`.
+///
+pub fn verify(ctx: &Ctx, staged: bool, files: &[&str]) -> Result<()> {
+    let checked: Vec<Entry> = if staged {
+        git::staged_entries(&ctx.path)?.iter()
+            .map(|(path, contents)| Entry::parse(path, contents))
+            .collect()
+    } else if !files.is_empty() {
+        let mut checked = Vec::new();
+        for f in files {
+            let path = fs::canonicalize(f).chain_err(|| format!("can't read {}", f))?;
+            checked.push(Entry::from_path(&path)?);
+        }
+        checked
+    } else {
+        get_all_entries(ctx).collect()
+    };
+    // Paths given on the command line may be spelled differently.
+    let canonical = |p: &Path| fs::canonicalize(p).unwrap_or_else(|_| p.to_owned());
+    let paths: HashSet<PathBuf> = checked.iter().map(|e| canonical(&e.path)).collect();
+    let others: Vec<Entry> = get_all_entries(ctx)
+        .filter(|e| !paths.contains(&canonical(&e.path)))
+        .collect();
+
+    let problems = lint::verify(&ctx.path, &checked, &others, |t| ctx.config.is_known_tag(t));
+    for p in problems.iter() {
+        println!("{}: {}", p.check, p.message);
+    }
+    if !problems.is_empty() {
+        bail!("{} problems in {} entries", problems.len(), checked.len());
+    }
+    Ok(())
+}
+
+pub fn print_status(ctx: &Ctx, format: &str) -> Result<()> {
+    let entries: Vec<Entry> = get_entries(ctx).collect();
+    let today = datetime::now().naive_local().date();
+    println!("{}", stats::render_status(format, &entries, today)?);
+    Ok(())
+}
+
+/// Rewrite the frontmatter of matching entries in canonical style, listing
+/// those that change. With `check`, only list them, failing if there are
+/// any.
+///
+pub fn fmt_entries(ctx: &Ctx, filter: &EntryFilter, check: bool) -> Result<()> {
+    let key_order: Vec<String> = match ctx.config.fmt.key_order {
+        Some(ref order) => order.clone(),
+        None => fmt::DEFAULT_KEY_ORDER.iter().map(|k| k.to_string()).collect(),
+    };
+    let mut entries: Vec<Entry> = get_entries(ctx).filter(|e| filter.matches(e)).collect();
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let mut rewrite: Vec<(PathBuf, String)> = Vec::new();
+    for e in entries.iter() {
+        let raw = fs::read_to_string(&e.path)?;
+        match fmt::format(&raw, &key_order) {
+            Ok(Some(formatted)) if formatted != raw => rewrite.push((e.path.clone(), formatted)),
+            Ok(_) => (),
+            Err(err) => eprintln!("{}: {}", e.path.display(), err),
+        }
+    }
+    for (path, _) in rewrite.iter() {
+        println!("{}", path.display());
+    }
+
+    if check {
+        if !rewrite.is_empty() {
+            bail!("{} entries aren't formatted", rewrite.len());
+        }
+        return Ok(());
+    }
+    if rewrite.is_empty() || !ctx.confirm.ask(
+            "fmt", &format!("reformat the frontmatter of {} entries?", rewrite.len()))? {
+        return Ok(());
+    }
+    for (path, formatted) in rewrite.iter() {
+        filedb::write_atomic(path, formatted)?;
+    }
+    Ok(())
+}
+
+/// Count entries per tag, an entry under `work/project-x` counting towards
+/// `work` as well. With `tree`, tags are shown beneath their parents, and
+/// paging applies to the top level.
+///
+pub fn query_tags(ctx: &Ctx, filter: &EntryFilter, page: &Page, tree: bool) -> Result<()> {
+    let entries: Vec<Entry> = get_entries(ctx).filter(|e| filter.matches(e)).collect();
+    let counts = tags::rolled_up_counts(entries.iter().map(|e| e.get_tags().unwrap_or_default()));
+
+    if tree {
+        let nodes = page.apply(tags::tree(&counts));
+        if ctx.json {
+            println!("{}", serde_json::Value::from(nodes.iter().map(tag_node_json).collect::<Vec<_>>()));
+            return Ok(());
+        }
+        fn print(node: &tags::Node, depth: usize) {
+            println!("{}{} {}", "  ".repeat(depth), node.name, node.count);
+            for child in node.children.iter() {
+                print(child, depth + 1);
+            }
+        }
+        for node in nodes.iter() {
+            print(node, 0);
+        }
+        return Ok(());
+    }
+
+    let mut sorted: Vec<(String, usize)> = counts.into_iter().collect();
+
+    sorted.sort_by_key(|v| v.1);
+    let sorted = page.apply(sorted);
+
+    if ctx.json {
+        let counts: Vec<serde_json::Value> = sorted.iter()
+            .map(|(tag, count)| serde_json::json!({ "tag": tag, "count": count }))
+            .collect();
+        println!("{}", serde_json::Value::from(counts));
+        return Ok(());
+    }
+    for (tag, count) in sorted.iter() {
+        println!("{} {}", tag, count);
+    }
+
+    Ok(())
+}
+
+fn tag_node_json(node: &tags::Node) -> serde_json::Value {
+    serde_json::json!({
+        "tag": node.tag,
+        "count": node.count,
+        "children": node.children.iter().map(tag_node_json).collect::<Vec<_>>(),
+    })
+}
+
+/// Open the entry with the id `id`, stopping the walk through the journal
+/// as soon as it's found. The walk is in order, so if several entries have
+/// the id, it's always the same one.
+///
+pub fn edit_by_id(ctx: &Ctx, id: &str) -> Result<()> {
+    let mut unread = Vec::new();
+    let found = filedb::walk_journal_iter(ctx.path.to_str().unwrap(), |p| Entry::from_path(&p))
+        .filter_map(|e| e.map_err(|e| unread.push(e)).ok())
+        .find(|e| e.get_id() == Some(id));
+    // Only worth mentioning if one of them might have been it.
+    if found.is_none() {
+        report_entry_errors(&unread);
+    }
+
+    match found {
+        Some(e) => {
+            edit_entry(ctx, &e, None)?;
+            Ok(())
+        },
+        None => {
+            println!("{}", ctx.locale.fmt(Msg::EntryNotFound, id));
+            Ok(())
+        },
+    }
+}
+
+/// Open the entry whose filename, id or title best matches `pattern`, or
+/// if none do, offer to create a new entry named after it.
+///
+pub fn edit_fuzzy(ctx: &Ctx, pattern: &str) -> Result<()> {
+    let best = get_entries(ctx)
+        .filter_map(|e| {
+            let stem = e.path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+            let names = Some(stem).into_iter().chain(e.get_id()).chain(Some(e.title()));
+            let score = matcher::best_score(pattern, names)?;
+            Some((score, e))
+        })
+        .max_by(|a, b| a.0.cmp(&b.0).then(b.1.path.cmp(&a.1.path)));
+
+    match best {
+        Some((_, e)) => {
+            edit_entry(ctx, &e, None)?;
+            Ok(())
+        },
+        None => {
+            let name = slugify(pattern);
+            if name.is_empty() || !ctx.confirm.ask(
+                    "create", &format!("nothing matches '{}'; create {}?", pattern, name))? {
+                return Ok(());
+            }
+            new_entry(ctx, &name, None, false, None, None, None, false, None)
+        },
+    }
+}
+
+/// Search entries for `pattern` and open the one that matches, or if several
+/// do, ask which.
+///
+pub fn edit_search(ctx: &Ctx, pattern: &regex::Regex) -> Result<()> {
+    let entries: Vec<Entry> = get_entries(ctx).collect();
+    let mut found: Vec<&Entry> = ctx.storage.search(&entries, pattern, 0).into_iter()
+        .map(|(e, _)| e)
+        .collect();
+    found.sort_by(|a, b| b.date().cmp(&a.date()).then(a.path.cmp(&b.path)));
+
+    let chosen = match found.as_slice() {
+        [] => bail!("no entries match '{}'", pattern),
+        [e] => *e,
+        _ => match pick_one(ctx, &found)? {
+            Some(e) => e,
+            None => return Ok(()),
+        },
+    };
+    edit_entry(ctx, chosen, None)?;
+    Ok(())
+}
+
+/// Ask which of `entries` to use, in the full-screen picker on a terminal.
+///
+#[cfg(feature = "tui")]
+fn pick_one<'a>(ctx: &Ctx, entries: &[&'a Entry]) -> Result<Option<&'a Entry>> {
+    match io::stdin().is_terminal() && io::stderr().is_terminal() {
+        true => jrni::picker::pick(entries),
+        false => choose(ctx, entries),
+    }
+}
+
+#[cfg(not(feature = "tui"))]
+fn pick_one<'a>(ctx: &Ctx, entries: &[&'a Entry]) -> Result<Option<&'a Entry>> {
+    choose(ctx, entries)
+}
+
+/// List `entries` numbered on the terminal and ask for one. None if the
+/// answer is empty.
+///
+fn choose<'a>(ctx: &Ctx, entries: &[&'a Entry]) -> Result<Option<&'a Entry>> {
+    for (i, e) in entries.iter().enumerate() {
+        let date = e.date().map(|d| d.to_string()).unwrap_or_default();
+        let rel = e.path.strip_prefix(&ctx.path).unwrap_or(&e.path);
+        eprintln!("{:>3}) {} {}{}{}", i + 1, date, e.title(), PICK_SEP, rel.display());
+    }
+    loop {
+        let answer = prompt::ask(&format!("which one (1-{})?", entries.len()))?;
+        if answer.is_empty() {
+            return Ok(None);
+        }
+        match answer.parse::<usize>() {
+            Ok(n) if n >= 1 && n <= entries.len() => return Ok(Some(entries[n - 1])),
+            _ => eprintln!("'{}' isn't one of the entries", answer),
+        }
+    }
+}
+
+/// Choose an entry in a full-screen picker, and open it.
+///
+#[cfg(feature = "tui")]
+pub fn pick_interactive(ctx: &Ctx) -> Result<()> {
+    let mut entries: Vec<Entry> = get_entries(ctx).collect();
+    entries.sort_by(|a, b| b.date().cmp(&a.date()).then(a.path.cmp(&b.path)));
+
+    let entries: Vec<&Entry> = entries.iter().collect();
+    if let Some(e) = jrni::picker::pick(&entries)? {
+        edit_entry(ctx, e, None)?;
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "tui"))]
+pub fn pick_interactive(_ctx: &Ctx) -> Result<()> {
+    bail!("this jrni was built without the picker (the tui feature); use pick --dmenu")
+}
+
+/// Separates the description of an entry from its path in `pick` lines.
+const PICK_SEP: &str = " | ";
+
+/// Print a line per entry for dmenu and friends, newest first, e.g.
+/// `2024-05-10 standup | 2024-05-10-standup.md`:
+///
+///     jrni pick --dmenu | dmenu | jrni pick --dmenu --resolve --edit
+///
+pub fn pick_list(ctx: &Ctx) -> Result<()> {
+    let mut entries: Vec<Entry> = get_entries(ctx).collect();
+    entries.sort_by(|a, b| b.date().cmp(&a.date()).then(a.path.cmp(&b.path)));
+
+    for e in entries.iter() {
+        let date = e.date().map(|d| d.to_string()).unwrap_or_default();
+        let rel = e.path.strip_prefix(&ctx.path).unwrap_or(&e.path);
+        println!("{} {}{}{}{}", date, e.title(), progress_suffix(e), PICK_SEP, rel.display());
+    }
+    Ok(())
+}
+
+/// Read a line printed by `pick_list` from stdin and print the path of its
+/// entry, or open it.
+///
+pub fn pick_resolve(ctx: &Ctx, open: bool) -> Result<()> {
+    let mut line = String::new();
+    stdin().read_line(&mut line)?;
+    let line = line.trim_end_matches(['\n', '\r']);
+    if line.is_empty() {
+        // Nothing chosen, e.g. dmenu was dismissed.
+        return Ok(());
+    }
+
+    let rel = match line.rfind(PICK_SEP) {
+        Some(i) => &line[i + PICK_SEP.len()..],
+        None => line,
+    };
+    let path = ctx.path.join(rel);
+    if !path.is_file() {
+        bail!(ctx.locale.fmt(Msg::EntryNotFound, rel));
+    }
+    match open {
+        true => { edit_entry(ctx, &Entry::from_path(&path)?, None)?; },
+        false => println!("{}", path.display()),
+    }
+    Ok(())
+}
+
+/// Print each author with the number of entries and words they've written,
+/// most prolific first.
+///
+pub fn query_authors(ctx: &Ctx, filter: &EntryFilter, page: &Page) -> Result<()> {
+    let mut counts: HashMap<String, (usize, usize)> = HashMap::new();
+
+    for e in get_entries(ctx).filter(|e| filter.matches(e)) {
+        if let Some(author) = e.author() {
+            let c = counts.entry(author.to_owned()).or_insert((0, 0));
+            c.0 += 1;
+            c.1 += e.word_count();
+        }
+    }
+
+    let mut sorted: Vec<(String, (usize, usize))> = counts.into_iter().collect();
+    sorted.sort_by(|a, b| (b.1).0.cmp(&(a.1).0).then(a.0.cmp(&b.0)));
+    let sorted = page.apply(sorted);
+
+    if ctx.json {
+        let counts: Vec<serde_json::Value> = sorted.iter()
+            .map(|(author, (entries, words))| serde_json::json!({
+                "author": author, "entries": entries, "words": words,
+            }))
+            .collect();
+        println!("{}", serde_json::Value::from(counts));
+        return Ok(());
+    }
+    for (author, (entries, words)) in sorted.iter() {
+        println!("{} {} {}", author, entries, words);
+    }
+    Ok(())
+}
+
+/// Print the id associated with each entry.
+///
+pub fn query_ids(ctx: &Ctx, filter: &EntryFilter, page: &Page) -> Result<()> {
+    let entries: Vec<Entry> = page.apply(get_entries(ctx)
+        .filter(|e| filter.matches(e) && e.get_id().is_some())
+        .collect());
+    if ctx.json {
+        return print_entries_json(&entries);
+    }
+    for e in entries.iter() {
+        println!("{}{}", e.get_id().unwrap(), progress_suffix(e));
+    }
+    Ok(())
+}
+
+/// The entries `audience` may see, less archived ones unless `--all` was
+/// given, and only published ones if `[publish] only_published` says so,
+/// anonymized per the config if asked.
+///
+fn exportable(ctx: &Ctx, audience: Visibility, anonymize: bool) -> Result<Vec<Entry>> {
+    let only_published = ctx.config.publish.only_published.unwrap_or(false);
+    let entries: Vec<Entry> = export::publishable(
+            get_all_entries(ctx).collect(), &Scope::from_config(&ctx.config), audience)
+        .into_iter()
+        .filter(|e| !only_published || export::is_published(e))
+        .filter(|e| ctx.all || !archive::is_archived(&ctx.path, e))
+        .collect();
+    match anonymize {
+        true => anonymized(ctx, entries, true),
+        false => Ok(entries),
+    }
+}
+
+/// `entries` anonymized per the config, keeping links to images in the
+/// journal with `images`, for exports that copy them.
+///
+fn anonymized(ctx: &Ctx, entries: Vec<Entry>, images: bool) -> Result<Vec<Entry>> {
+    if ctx.config.anonymize.is_empty() {
+        bail!("nothing to anonymize; list names to replace under [anonymize] in the config");
+    }
+    let jrnl_path = Some(ctx.path.as_path()).filter(|_| images);
+    let anonymizer = Anonymizer::new(&ctx.config.anonymize, jrnl_path)?;
+    Ok(anonymizer.entries(entries))
+}
+
+/// Export public entries as a static ActivityPub actor and outbox, and
+/// record each one's object URL in it.
+///
+pub fn export_activitypub(
+    ctx: &Ctx,
+    base_url: &str,
+    username: Option<&str>,
+    anonymize: bool,
+    outdir: &str,
+) -> Result<()> {
+    let default_user = ctx.config.default_author()
+        .or_else(|| env::var("USER").ok())
+        .unwrap_or_else(|| String::from("jrni"));
+    let actor = activitypub::Actor {
+        base_url: base_url.trim_end_matches('/'),
+        username: username.unwrap_or(&default_user),
+    };
+    let entries = exportable(ctx, Visibility::Public, anonymize)?;
+
+    for (path, url) in activitypub::export(&actor, &entries, &ctx.path, Path::new(outdir))? {
+        filedb::set_field(&path, activitypub::URL_FIELD, &url)?;
+    }
+    println!("{}", outdir);
+    Ok(())
+}
+
+/// Export the entries `audience` may see as a static HTML site.
+///
+pub fn export_html(ctx: &Ctx, audience: Visibility, anonymize: bool, outdir: &str) -> Result<()> {
+    let entries = exportable(ctx, audience, anonymize)?;
+    site::export(&entries, &ctx.path, Path::new(outdir))?;
+    println!("{}", Path::new(outdir).join("index.html").display());
+    Ok(())
+}
+
+/// Export entries `audience` may see, matching `filter` and dated `since`
+/// or later, as Markdown: all into one document at `out` (or stdout) with
+/// `single`, otherwise a file each in the directory `out`. Encrypted
+/// entries are left out.
+///
+pub fn export_markdown(
+    ctx: &Ctx,
+    audience: Visibility,
+    filter: &EntryFilter,
+    since: Option<NaiveDate>,
+    single: bool,
+    anonymize: bool,
+    out: Option<&str>,
+) -> Result<()> {
+    let mut entries: Vec<Entry> = exportable(ctx, audience, false)?
+        .into_iter()
+        .filter(|e| !e.is_encrypted && filter.matches(e))
+        .filter(|e| since.is_none_or(|s| e.date().is_some_and(|d| d >= s)))
+        .collect();
+    // Only once they're chosen, so filters see the real tags and authors.
+    if anonymize {
+        entries = anonymized(ctx, entries, false)?;
+    }
+    if entries.is_empty() {
+        bail!("no entries to export");
+    }
+
+    if !single {
+        let outdir = Path::new(out.unwrap());
+        export::markdown::per_entry(outdir, &entries)?;
+        println!("{}", outdir.display());
+        return Ok(());
+    }
+    let title = match since {
+        Some(s) => format!("Journal since {}", s),
+        None => "Journal".to_owned(),
+    };
+    match out {
+        Some(path) => {
+            let mut f = io::BufWriter::new(File::create(path)?);
+            export::markdown::single(&mut f, &title, &entries)?;
+            f.flush()?;
+            eprintln!("wrote {} entries to {}", entries.len(), path);
+        },
+        None => {
+            let stdout = io::stdout();
+            let mut lock = io::BufWriter::new(stdout.lock());
+            export::markdown::single(&mut lock, &title, &entries)?;
+            lock.flush()?;
+        },
+    }
+    Ok(())
+}
+
+/// Where templates are kept: the configured `template_dir`, or `.templates/`
+/// in the journal.
+///
+fn template_dir(ctx: &Ctx) -> PathBuf {
+    match ctx.config.template_dir {
+        Some(ref dir) => ctx.path.join(config::expand_home(dir)),
+        None => templates::template_dir(&ctx.path),
+    }
+}
+
+pub fn template_list(ctx: &Ctx) -> Result<()> {
+    for name in templates::list(&template_dir(ctx))? {
+        println!("{}", name);
+    }
+    Ok(())
+}
+
+pub fn template_show(ctx: &Ctx, name: &str) -> Result<()> {
+    let path = templates::template_path(&template_dir(ctx), name);
+    if !path.exists() {
+        bail!("no template named '{}'", name);
+    }
+    print!("{}", fs::read_to_string(path)?);
+    Ok(())
+}
+
+pub fn template_new(ctx: &Ctx, name: &str) -> Result<()> {
+    let path = templates::template_path(&template_dir(ctx), name);
+    if path.exists() {
+        bail!(ctx.locale.fmt(Msg::FileExists, &path.display().to_string()));
+    }
+    fs::create_dir_all(template_dir(ctx))?;
+    let template = "tags: \n---\n\n";
+    fs::write(&path, template)?;
+    edit_at(ctx, &path, Some(editor::start_of_body(&Entry::parse(&path, template))))?;
+    Ok(())
+}
+
+pub fn template_edit(ctx: &Ctx, name: &str) -> Result<()> {
+    let path = templates::template_path(&template_dir(ctx), name);
+    if !path.exists() {
+        bail!("no template named '{}'", name);
+    }
+    edit(ctx, &path)?;
+    Ok(())
+}
+
+/// Save a copy of the entry with `id` as a template, minus its id and
+/// pubdate.
+///
+pub fn template_from(ctx: &Ctx, id: &str, name: &str) -> Result<()> {
+    let entry = entry_by_id(ctx, id)?;
+
+    let path = templates::template_path(&template_dir(ctx), name);
+    if path.exists() && !ctx.confirm.ask(
+            "template", &format!("overwrite {}?", path.display()))? {
+        bail!("not overwriting {}", path.display());
+    }
+
+    fs::create_dir_all(template_dir(ctx))?;
+    let raw = fs::read_to_string(&entry.path)?;
+    fs::write(&path, templates::from_entry_text(&raw))?;
+    println!("{}", path.display());
+    Ok(())
+}
+
+fn entry_by_id(ctx: &Ctx, id: &str) -> Result<Entry> {
+    match get_all_entries(ctx).find(|e| e.get_id() == Some(id)) {
+        Some(e) => Ok(e),
+        None => bail!(ctx.locale.fmt(Msg::EntryNotFound, id)),
+    }
+}
+
+/// Print an entry's headings, indented by level, with their line numbers.
+///
+pub fn query_toc(ctx: &Ctx, id: &str) -> Result<()> {
+    let entry = entry_by_id(ctx, id)?;
+    for h in entry.headings() {
+        println!("{:>5} {}{}", h.line, "  ".repeat(h.level - 1), h.title);
+    }
+    Ok(())
+}
+
+/// Open an entry in $EDITOR, at the heading titled `section` if given.
+///
+pub fn edit_section(ctx: &Ctx, id: &str, section: Option<&str>) -> Result<()> {
+    let entry = entry_by_id(ctx, id)?;
+    if entry.is_encrypted && section.is_some() {
+        bail!("'{}' is encrypted, so its sections can't be found; leave out --section", id);
+    }
+    let line = match section {
+        None => None,
+        Some(s) => match entry.headings().iter()
+                .find(|h| h.title.eq_ignore_ascii_case(s.trim())) {
+            Some(h) => Some(h.line),
+            None => bail!("no section '{}' in {}", s, id),
+        },
+    };
+    edit_entry(ctx, &entry, line)?;
+    Ok(())
+}
+
+pub fn print_path(ctx: &Ctx, id: &str, copy: bool) -> Result<()> {
+    let entry = entry_by_id(ctx, id)?;
+    output(&entry.path.display().to_string(), copy)
+}
+
+pub fn print_entry(ctx: &Ctx, id: &str, copy: bool) -> Result<()> {
+    let entry = entry_by_id(ctx, id)?;
+    if copy {
+        return output(fs::read_to_string(&entry.path)?.trim_end(), copy);
+    }
+    std::io::copy(&mut File::open(&entry.path)?, &mut std::io::stdout())?;
+    Ok(())
+}
+
+/// Print an entry for reading: its frontmatter in a box, then its body
+/// with transclusions and placeholders filled in and the Markdown rendered
+/// with ANSI styling. The styling's left out if `NO_COLOR` is set or stdout
+/// isn't a terminal.
+///
+pub fn show_entry(ctx: &Ctx, id: &str) -> Result<()> {
+    let archived = |e: &Entry| !ctx.all && archive::is_archived(&ctx.path, e);
+    let mut entries: Vec<Entry> = get_all_entries(ctx).collect();
+    // Those not archived first, so they can be counted on their own.
+    entries.sort_by_key(|e| archived(e));
+    let entry = match entries.iter().find(|e| e.get_id() == Some(id)) {
+        Some(e) => e,
+        None => bail!(ctx.locale.fmt(Msg::EntryNotFound, id)),
+    };
+    let color = env::var_os("NO_COLOR").is_none() && io::stdout().is_terminal();
+    let width = env::var("COLUMNS").ok().and_then(|c| c.parse().ok()).unwrap_or(80usize).min(100);
+
+    print!("{}", term::header(entry, width, color));
+    if entry.is_encrypted {
+        println!("\n(encrypted; `jrni id {}` to read it)", id);
+        return Ok(());
+    }
+    // Any entry can be transcluded, but only those not archived count
+    // towards backlinks, as in `jrni backlinks`.
+    let active = &entries[..entries.partition_point(|e| !archived(e))];
+    let body = Transcluder::new(&entries).expand(entry)?;
+    let body = Placeholders::new(active, datetime::now().naive_local().date()).render(entry, &body);
+    print!("\n{}", term::markdown(&body, width, color));
+    Ok(())
+}
+
+/// Print how the frontmatter of two entries differs, then a word diff of
+/// their bodies. Colors are left out if `NO_COLOR` is set.
+///
+pub fn diff_entries(ctx: &Ctx, id1: &str, id2: &str) -> Result<()> {
+    let a = entry_by_id(ctx, id1)?;
+    let b = entry_by_id(ctx, id2)?;
+    let color = env::var_os("NO_COLOR").is_none();
+
+    print!("{}", diff::frontmatter(&a, &b, color));
+    println!("{}", diff::render(&diff::words(&a.body, &b.body), color));
+    Ok(())
+}
+
+/// Print `text`, or with `copy`, put it on the clipboard instead.
+///
+fn output(text: &str, copy: bool) -> Result<()> {
+    match copy {
+        true => copy_to_clipboard(text),
+        false => { println!("{}", text); Ok(()) },
+    }
+}
+
+#[cfg(all(feature = "clipboard", not(target_os = "linux")))]
+fn copy_to_clipboard(text: &str) -> Result<()> {
+    arboard::Clipboard::new()
+        .and_then(|mut c| c.set_text(text))
+        .map_err(|e| Error::from(format!("couldn't copy to the clipboard: {}", e)))?;
+    Ok(())
+}
+
+/// Set in the environment of the process `copy_to_clipboard` starts to hold
+/// the clipboard.
+///
+#[cfg(all(feature = "clipboard", target_os = "linux"))]
+const CLIPBOARD_HOLDER: &str = "JRNI_CLIPBOARD_HOLDER";
+
+/// On X11 and Wayland the clipboard's contents are served by the process
+/// that set them, and go when it exits; so hand `text` to a copy of jrni
+/// that holds it until something else is copied, once it says it has it.
+///
+#[cfg(all(feature = "clipboard", target_os = "linux"))]
+fn copy_to_clipboard(text: &str) -> Result<()> {
+    use std::io::BufRead;
+
+    let mut holder = Command::new(env::current_exe()?)
+        .env(CLIPBOARD_HOLDER, "1")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .chain_err(|| "couldn't start a process to hold the clipboard")?;
+    if let Some(mut input) = holder.stdin.take() {
+        input.write_all(text.as_bytes())?;
+    }
+    // `ok` once the text is on the clipboard, or why it isn't.
+    let mut reply = String::new();
+    if let Some(out) = holder.stdout.take() {
+        io::BufReader::new(out).read_line(&mut reply)?;
+    }
+    match reply.trim_end() {
+        "ok" => Ok(()),
+        why => {
+            holder.wait()?;
+            match why {
+                "" => bail!("couldn't copy to the clipboard: the process holding it exited"),
+                why => bail!("couldn't copy to the clipboard: {}", why),
+            }
+        },
+    }
+}
+
+/// Put stdin on the clipboard, say so (or why not) on stdout, and serve it
+/// until something replaces it.
+///
+#[cfg(all(feature = "clipboard", target_os = "linux"))]
+fn hold_clipboard() -> Result<()> {
+    use arboard::SetExtLinux;
+
+    let mut text = String::new();
+    stdin().read_to_string(&mut text)?;
+    let mut clipboard = match arboard::Clipboard::new().and_then(|mut c| c.set_text(text.clone()).map(|_| c)) {
+        Ok(c) => c,
+        Err(e) => {
+            println!("{}", e.to_string().replace('\n', " "));
+            return Ok(());
+        },
+    };
+    println!("ok");
+    io::stdout().flush()?;
+    clipboard.set().wait().text(text)
+        .map_err(|e| Error::from(format!("couldn't copy to the clipboard: {}", e)))?;
+    Ok(())
+}
+
+#[cfg(not(feature = "clipboard"))]
+fn copy_to_clipboard(_text: &str) -> Result<()> {
+    bail!("jrni was built without clipboard support");
+}
+
+/// Show task completion like ` [3/7]`, or nothing if there are no tasks.
+///
+fn progress_suffix(e: &Entry) -> String {
+    match e.task_progress() {
+        (_, 0) => String::new(),
+        (done, total) => format!(" [{}/{}]", done, total),
+    }
+}
+
+/// What `--json` prints for an entry.
+///
+fn entry_json(e: &Entry) -> serde_json::Value {
+    serde_json::json!({
+        "path": e.path.display().to_string(),
+        "id": e.get_id(),
+        "tags": e.get_tags().unwrap_or_default(),
+        "pubdate": e.pubdate().map(|d| d.to_rfc3339()),
+        "title": e.title(),
+        "summary": e.summary(),
+    })
+}
+
+fn print_entries_json(entries: &[Entry]) -> Result<()> {
+    let entries: Vec<serde_json::Value> = entries.iter().map(entry_json).collect();
+    println!("{}", serde_json::Value::from(entries));
+    Ok(())
+}
+
+/// Print a row per habit with a cell per day since `since`: `#` where the
+/// habit was done, `.` where it was recorded as not done, and blank where
+/// nothing was recorded.
+///
+pub fn query_habits(ctx: &Ctx, since: &str) -> Result<()> {
+    let today = datetime::now().naive_local().date();
+    let start = datetime::before(today, since)?;
+    let days = (today - start).num_days() as usize + 1;
+
+    // habit -> day index -> done
+    let mut grid: HashMap<String, HashMap<usize, bool>> = HashMap::new();
+    for e in get_entries(ctx) {
+        let day = match e.pubdate() {
+            Some(d) if d.naive_local().date() >= start => d.naive_local().date(),
+            _ => continue,
+        };
+        let idx = (day - start).num_days() as usize;
+        for (habit, done) in e.habits() {
+            let cell = grid.entry(habit).or_default()
+                .entry(idx).or_insert(false);
+            *cell = *cell || done;
+        }
+    }
+
+    let mut habits: Vec<&String> = grid.keys().collect();
+    habits.sort();
+    let width = habits.iter().map(|h| h.chars().count()).max().unwrap_or(0);
+
+    println!("{:width$} {} .. {}", "", start, today, width = width);
+    for habit in habits {
+        let record = &grid[habit];
+        let cells: String = (0..days).map(|i| match record.get(&i) {
+            Some(true) => '#',
+            Some(false) => '.',
+            None => ' ',
+        }).collect();
+        let done = record.values().filter(|d| **d).count();
+        println!("{:width$} {} {}/{}", habit, cells, done, days, width = width);
+    }
+    Ok(())
+}
+
+/// Print open tasks grouped by entry, or with `by_entry`, each entry's task
+/// progress.
+///
+pub fn query_todo(ctx: &Ctx, by_entry: bool) -> Result<()> {
+    let mut entries: Vec<Entry> = get_entries(ctx)
+        .filter(|e| e.task_progress().1 > 0)
+        .collect();
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+    for e in entries.iter() {
+        if by_entry {
+            let (done, total) = e.task_progress();
+            println!("[{}/{}] {}", done, total, e.title());
+            continue;
+        }
+
+        let open: Vec<_> = e.tasks().into_iter().filter(|t| !t.done).collect();
+        if open.is_empty() {
+            continue;
+        }
+        println!("{}", e.title());
+        for task in open {
+            println!("  - [ ] {}", task.text);
+        }
+    }
+    Ok(())
+}
+
+/// List entries whose temporary tags (`until:` tags or an `expires:` field)
+/// have lapsed, oldest first.
+///
+pub fn doctor_expired(ctx: &Ctx) -> Result<()> {
+    let today = datetime::now().naive_local().date();
+    let mut expired: Vec<(NaiveDate, PathBuf)> = get_all_entries(ctx)
+        .filter_map(|e| match e.expires() {
+            Some(d) if d < today => Some((d, e.path)),
+            _ => None,
+        })
+        .collect();
+
+    expired.sort();
+
+    for (date, path) in expired.iter() {
+        println!("{} {}", date, path.display());
+    }
+    Ok(())
+}
+
+/// List entries that use tags outside of the configured vocabulary.
+///
+pub fn doctor_tags(ctx: &Ctx) -> Result<()> {
+    if ctx.config.allowed_tags.is_none() {
+        bail!("no tag vocabulary configured; set allowed_tags in the config");
+    }
+
+    let mut entries: Vec<Entry> = get_all_entries(ctx).collect();
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+    for e in entries.iter() {
+        let unknown: Vec<&str> = e.get_tags().unwrap_or_default().into_iter()
+            .filter(|t| !ctx.config.is_known_tag(t))
+            .collect();
+        if !unknown.is_empty() {
+            println!("{}: {}", e.path.display(), unknown.join(", "));
+        }
+    }
+    Ok(())
+}
+
+/// Report entries that are missing tags their autotag rules call for, and
+/// with `apply`, add them.
+///
+pub fn doctor_autotag(ctx: &Ctx, apply: bool) -> Result<()> {
+    let tagger = AutoTagger::new(&ctx.config.autotag)?;
+    let mut retag: Vec<(PathBuf, Vec<String>)> = Vec::new();
+
+    for e in get_all_entries(ctx) {
+        let mut tags: Vec<String> = e.get_tags().unwrap_or_default()
+            .into_iter().map(String::from).collect();
+        let missing: Vec<String> = tagger.tags_for(&ctx.path, &e.path, &e.body)
+            .into_iter().filter(|t| !tags.contains(t)).collect();
+        if missing.is_empty() {
+            continue;
+        }
+
+        println!("{} +{}", e.path.display(), missing.join(" +"));
+        tags.extend(missing);
+        retag.push((e.path, tags));
+    }
+
+    if !apply || retag.is_empty() {
+        return Ok(());
+    }
+    if !ctx.confirm.ask("autotag", &format!("retag {} entries?", retag.len()))? {
+        return Ok(());
+    }
+    for (path, tags) in retag.iter() {
+        filedb::rewrite_tags(path, tags)?;
+    }
+    Ok(())
+}
+
+/// Rename the tag `from` to `to` on every entry, or remove it if there's no
+/// `to`, along with the tags beneath it, so `work/x` becomes `job/x`.
+/// Everything in the entries but their tags is left as it was.
+///
+pub fn retag(ctx: &Ctx, from: &str, to: Option<&str>) -> Result<()> {
+    let mut retag: Vec<(PathBuf, Vec<String>)> = Vec::new();
+    for e in get_all_entries(ctx) {
+        let tags = e.get_tags().unwrap_or_default();
+        if !tags.iter().any(|t| tags::is_within(t, from)) {
+            continue;
+        }
+        let mut renamed: Vec<String> = Vec::new();
+        for t in tags.into_iter() {
+            let t = match tags::is_within(t, from) {
+                true => to.map(|to| format!("{}{}", to, &t[from.len()..])),
+                false => Some(t.to_owned()),
+            };
+            if let Some(t) = t.filter(|t| !renamed.contains(t)) {
+                renamed.push(t);
+            }
+        }
+        println!("{}", e.path.display());
+        retag.push((e.path, renamed));
+    }
+
+    if retag.is_empty() {
+        return Ok(());
+    }
+    if let Some(to) = to.filter(|t| !ctx.config.is_known_tag(t)) {
+        eprintln!("warning: tag '{}' isn't in the configured vocabulary", to);
+    }
+    let action = match to {
+        Some(to) => format!("retag '{}' as '{}' in {} entries?", from, to, retag.len()),
+        None => format!("remove '{}' from {} entries?", from, retag.len()),
+    };
+    if !ctx.confirm.ask("retag", &action)? {
+        return Ok(());
+    }
+    for (path, tags) in retag.iter() {
+        filedb::rewrite_tags(path, tags)?;
+    }
+    Ok(())
+}
+
+/// Show what migrating the journal to the current format involves, and with
+/// `apply`, do it.
+///
+pub fn migrate_journal(ctx: &Ctx, apply: bool) -> Result<()> {
+    let pending = migrate::pending(&ctx.path)?;
+    if pending.is_empty() {
+        println!("the journal is at format version {}", migrate::FORMAT_VERSION);
+        return Ok(());
+    }
+
+    for m in pending.iter() {
+        println!("version {}: {}", m.to, m.summary);
+        for change in m.plan(&ctx.path)? {
+            println!("    {}", change);
+        }
+    }
+
+    if !apply {
+        return Ok(());
+    }
+    if !ctx.confirm.ask("migrate", &format!(
+            "apply {} migrations?", pending.len()))? {
+        return Ok(());
+    }
+    let backup = migrate::run(&ctx.path, &pending)?;
+    println!("migrated; the journal as it was is in {}", backup.display());
+    Ok(())
+}
+
+/// List attachments that aren't in the asset store, and with `apply`, move
+/// them into it, merging duplicates and rewriting links.
+///
+pub fn doctor_assets(ctx: &Ctx, apply: bool) -> Result<()> {
+    let moves = assets::unstored(&ctx.path)?;
+    for m in moves.iter() {
+        println!("{} -> {}", m.from, m.to);
+    }
+
+    if !apply || moves.is_empty() {
+        return Ok(());
+    }
+    if !ctx.confirm.ask("assets", &format!("move {} attachments?", moves.len()))? {
+        return Ok(());
+    }
+    // Every entry, or links to the attachments could be left behind.
+    let entries = filedb::load_all_entries(&ctx.path)?;
+    for path in assets::migrate(&ctx.path, &moves, &entries)? {
+        println!("{}", path.display());
+    }
+    Ok(())
+}
+
+/// Report footnote problems in each entry, and with `renumber`, relabel
+/// footnotes sequentially wherever they aren't already.
+///
+pub fn doctor_footnotes(ctx: &Ctx, renumber: bool) -> Result<()> {
+    let mut entries: Vec<Entry> = get_all_entries(ctx).collect();
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    let mut rewrite: Vec<(PathBuf, String)> = Vec::new();
+
+    for e in entries.iter() {
+        for problem in footnotes::check(&e.body) {
+            println!("{}: {}", e.path.display(), problem);
+        }
+        if renumber {
+            let raw = fs::read_to_string(&e.path)?;
+            let renumbered = footnotes::renumber(&raw);
+            if renumbered != raw {
+                rewrite.push((e.path.clone(), renumbered));
+            }
+        }
+    }
+
+    if rewrite.is_empty() {
+        return Ok(());
+    }
+    if !ctx.confirm.ask(
+            "footnotes", &format!("renumber footnotes in {} entries?", rewrite.len()))? {
+        return Ok(());
+    }
+    for (path, text) in rewrite.iter() {
+        filedb::write_atomic(path, text)?;
+        println!("{}", path.display());
+    }
+    Ok(())
+}
+
+/// List entries whose paths break the `[filenames]` rules, and with `fix`,
+/// rename them to fit. An entry whose new name is taken is left alone.
+///
+pub fn doctor_filenames(ctx: &Ctx, fix: bool) -> Result<()> {
+    let rules = &ctx.config.filenames;
+    let mut entries: Vec<Entry> = get_all_entries(ctx).collect();
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    let mut renames: Vec<(PathBuf, PathBuf)> = Vec::new();
+
+    for e in entries.iter() {
+        let rel = match e.path.strip_prefix(&ctx.path) {
+            Ok(rel) => rel,
+            Err(_) => continue,
+        };
+        if !rules.allows(rel) {
+            let fixed = rules.sanitize_path(rel);
+            println!("{} -> {}", rel.display(), fixed.display());
+            renames.push((e.path.clone(), ctx.path.join(fixed)));
+        }
+    }
+
+    if !fix || renames.is_empty() {
+        return Ok(());
+    }
+    if !ctx.confirm.ask("filenames", &format!("rename {} entries?", renames.len()))? {
+        return Ok(());
+    }
+    for (from, to) in renames.iter() {
+        if to.exists() {
+            eprintln!("{}: not renamed, {} is in the way", from.display(), to.display());
+            continue;
+        }
+        fs::create_dir_all(to.parent().unwrap())?;
+        fs::rename(from, to)?;
+        println!("{}", to.display());
+    }
+    Ok(())
+}
+
+pub fn query_sync_conflicts(ctx: &Ctx) -> Result<()> {
+    for (conflict, original) in filedb::sync_conflicts(ctx.path.to_str().unwrap()) {
+        println!("{} -> {}", conflict.display(), original.display());
+    }
+    Ok(())
+}
+
+/// Walk through each sync conflict copy, show how it differs from its entry,
+/// and ask what to do with it.
+///
+pub fn resolve_sync_conflicts(ctx: &Ctx) -> Result<()> {
+    for (conflict, original) in filedb::sync_conflicts(ctx.path.to_str().unwrap()) {
+        println!("\n{}\n  conflicts with {}", conflict.display(), original.display());
+
+        if !original.exists() {
+            if prompt::ask_yes_no("original is gone; restore it from the copy?", &ctx.locale)? {
+                fs::rename(&conflict, &original)?;
+            }
+            continue;
+        }
+
+        // Best effort: not every system has diff(1).
+        let _ = Command::new("diff").arg("-u").arg(&original).arg(&conflict).status();
+
+        let choice = prompt::ask(
+            "[k]eep entry and delete copy, [t]ake copy, [a]ppend copy's new lines, [s]kip?")?;
+        match choice.as_str() {
+            "k" => fs::remove_file(&conflict)?,
+            "t" => fs::rename(&conflict, &original)?,
+            "a" => {
+                let entry = Entry::from_path(&original)?;
+                let copy = Entry::from_path(&conflict)?;
+                if entry.is_encrypted || copy.is_encrypted {
+                    println!("encrypted entries can't be merged line by line; skipped");
+                    continue;
+                }
+                // Only the copy's body: its frontmatter is the entry's own,
+                // or near enough.
+                let existing: HashSet<&str> = entry.body.lines().collect();
+                let new_lines: Vec<&str> = copy.body.lines()
+                    .filter(|l| !l.trim().is_empty() && !existing.contains(l))
+                    .collect();
+                if !new_lines.is_empty() {
+                    filedb::append_body(&original, &new_lines.join("\n"))?;
+                }
+                fs::remove_file(&conflict)?;
+            },
+            _ => println!("skipped"),
+        }
+    }
+    Ok(())
+}
+
+/// The fields the config asks for changed entries to be stamped with.
+///
+fn stamps(ctx: &Ctx) -> activity::Stamps {
+    activity::Stamps { modified: ctx.config.track_modified, stats: ctx.config.track_stats }
+}
+
+/// Bring the activity log up to date and print it, optionally limited to
+/// the last `since` (e.g. "7d").
+///
+pub fn query_activity(ctx: &Ctx, since: Option<&str>) -> Result<()> {
+    let cutoff = match since {
+        Some(s) => match datetime::now().checked_sub_signed(datetime::parse_relative(s)?) {
+            Some(cutoff) => Some(cutoff),
+            None => bail!("'{}' is too long ago", s),
+        },
+        None => None,
+    };
+
+    activity::record_changes(&ctx.path, stamps(ctx))?;
+    for e in activity::read_log(&ctx.path)? {
+        if cutoff.is_none_or(|c| e.when >= c) {
+            println!("{} {:<8} {}", e.when.format("%F %R"), e.kind.as_str(), e.path);
+        }
+    }
+    Ok(())
+}
+
+/// Back up the journal to `to` (or the configured target), or with `verify`,
+/// check that the remote copy is complete.
+///
+#[cfg(feature = "backup")]
+pub fn backup(ctx: &Ctx, to: Option<&str>, verify: bool) -> Result<()> {
+    use jrni::backup::{self, Target};
+
+    let conf = &ctx.config.backup;
+    let url = match to.or(conf.to.as_deref()) {
+        Some(u) => u,
+        None => bail!("no backup target; pass --to or set backup.to in the config"),
+    };
+    let target = Target::new(url, conf.endpoint.as_deref())?;
+
+    if verify {
+        let problems = backup::verify(
+            &ctx.path, &target, conf.identity.as_deref())?;
+        for p in problems.iter() {
+            println!("{}", p);
+        }
+        if !problems.is_empty() {
+            bail!("backup at {} is incomplete", url);
+        }
+        println!("backup at {} is complete", url);
+        return Ok(());
+    }
+
+    let recipient = match conf.recipient {
+        Some(ref r) => r,
+        None => bail!("set backup.recipient to the age public key to encrypt to"),
+    };
+    let report = backup::backup(&ctx.path, &target, recipient)?;
+    println!("{} files, {} uploaded, manifest {}", report.files, report.uploaded, report.manifest);
+    Ok(())
+}
+
+/// Print ready-to-install scheduler snippets for the configured recurring
+/// actions.
+///
+pub fn print_schedule(ctx: &Ctx, format: &str) -> Result<()> {
+    let actions = &ctx.config.schedule;
+    if actions.is_empty() {
+        bail!("no recurring actions configured; add [[schedule]] sections to the config");
+    }
+
+    let exe = env::current_exe()?;
+    let jrni = exe.to_str().unwrap();
+    let jrnl_path = ctx.path.to_str().unwrap();
+    let snippet = match format {
+        "cron" => schedule::crontab(jrni, jrnl_path, actions)?,
+        _ => schedule::systemd(jrni, jrnl_path, actions)?,
+    };
+    print!("{}", snippet);
+    Ok(())
+}
+
+/// Print entries matching `filter` as a board, in a column per value of
+/// `field`, oldest first within each.
+///
+pub fn show_board(
+    ctx: &Ctx,
+    filter: &EntryFilter,
+    field: &str,
+    columns: Option<&[&str]>,
+    width: usize,
+) -> Result<()> {
+    let mut entries: Vec<Entry> = get_entries(ctx).filter(|e| filter.matches(e)).collect();
+    entries.sort_by(|a, b| a.date().cmp(&b.date()).then(a.path.cmp(&b.path)));
+    let board = board::group(&entries, field, columns);
+    if board.iter().all(|c| c.entries.is_empty()) {
+        bail!("no entries have a '{}' field{}", field,
+              if columns.is_some() { " with those values" } else { "" });
+    }
+
+    if ctx.json {
+        let json: serde_json::Map<String, serde_json::Value> = board.iter()
+            .map(|c| (c.name.clone(), c.entries.iter().map(|e| entry_json(e)).collect()))
+            .collect();
+        println!("{}", serde_json::Value::from(json));
+        return Ok(());
+    }
+    print!("{}", board::render(&board, width, |e| e.title().to_owned()));
+    Ok(())
+}
+
+/// Print each entry's word count, oldest first, then the total, and with a
+/// `target`, how far today's entries have got towards it.
+///
+pub fn word_counts(ctx: &Ctx, target: Option<usize>) -> Result<()> {
+    let mut entries: Vec<Entry> = get_entries(ctx).collect();
+    entries.sort_by(|a, b| a.date().cmp(&b.date()).then(a.path.cmp(&b.path)));
+
+    let today = datetime::now().naive_local().date();
+    let mut total = 0;
+    let mut today_total = 0;
+    for e in entries.iter() {
+        let count = e.word_count();
+        total += count;
+        if e.date() == Some(today) {
+            today_total += count;
+        }
+        println!("{:>7}  {}", count, entry_row(e));
+    }
+    println!("{:>7}  total in {} entries", total, entries.len());
+
+    if let Some(target) = target.filter(|t| *t > 0) {
+        let percent = today_total * 100 / target;
+        let done = if today_total >= target { " - done!" } else { "" };
+        println!("today: {} of {} words ({}%){}", today_total, target, percent, done);
+    }
+    Ok(())
+}
+
+/// Entries with a reminder that's come due by `today`, oldest first.
+///
+fn due_reminders<'a>(ctx: &Ctx, entries: &'a [Entry], today: NaiveDate) -> Vec<&'a Entry> {
+    let mut due: Vec<&Entry> = entries.iter()
+        .filter(|e| ctx.all || !archive::is_archived(&ctx.path, e))
+        .filter(|e| e.reminder().is_some_and(|d| d <= today))
+        .collect();
+    due.sort_by(|a, b| a.reminder().cmp(&b.reminder()).then(a.path.cmp(&b.path)));
+    due
+}
+
+/// List the entries whose reminders have come due, with the day each was
+/// for.
+///
+pub fn agenda(ctx: &Ctx) -> Result<()> {
+    let today = datetime::now().naive_local().date();
+    let entries: Vec<Entry> = get_all_entries(ctx).collect();
+    for e in due_reminders(ctx, &entries, today) {
+        println!("{}  {}", e.reminder().unwrap(), entry_row(e));
+    }
+    Ok(())
+}
+
+/// Stop reminding about the entry `id`, by stamping it `reminded:` today.
+///
+pub fn dismiss_reminder(ctx: &Ctx, id: &str) -> Result<()> {
+    let entry = entry_by_id(ctx, id)?;
+    if entry.reminder().is_none() {
+        bail!("'{}' has no reminder to dismiss", id);
+    }
+    let today = datetime::now().naive_local().date();
+    filedb::set_field(&entry.path, "reminded", &today.format("%F").to_string())
+}
+
+/// The commands that create or list entries, before which `run` mentions
+/// reminders that are due. Not `agenda`, which lists them anyway.
+///
+const ANNOUNCES_REMINDERS: &[&str] = &[
+    "n", "e", "a", "today", "append", "s", "l", "ls", "between", "recent", "todo", "board",
+];
+
+/// Mention reminders that have come due on stderr, before a command's own
+/// output. The entries are left in `ctx` for the command, so the journal's
+/// still only loaded once.
+///
+fn announce_reminders(ctx: &Ctx) {
+    let entries: Vec<Entry> = get_all_entries(ctx).collect();
+    let due = due_reminders(ctx, &entries, datetime::now().naive_local().date());
+    if !due.is_empty() {
+        eprintln!("{} reminder(s) due (see `jrni agenda`; dismiss with `jrni agenda --done <id>`):",
+                  due.len());
+        for e in due.iter() {
+            eprintln!("  {}", entry_row(e));
+        }
+    }
+    ctx.loaded.replace(Some(entries));
+}
+
+/// Nudge the user to write if nothing has been written today, unless it's
+/// quiet hours. Meant to be run from a timer (see `jrni schedule`).
+///
+pub fn remind(ctx: &Ctx) -> Result<()> {
+    let now = datetime::now();
+    if let Some(ref quiet) = ctx.config.remind.quiet_hours {
+        if datetime::in_daily_window(quiet, now.naive_local().time())? {
+            return Ok(());
+        }
+    }
+
+    let today = now.naive_local().date();
+    let written = get_entries(ctx)
+        .any(|e| e.pubdate().is_some_and(|d| d.naive_local().date() == today));
+    if written {
+        return Ok(());
+    }
+
+    let message = ctx.config.remind.message.as_deref()
+        .unwrap_or("You haven't written anything today.");
+    notify(message)
+}
+
+#[cfg(feature = "notify")]
+fn notify(message: &str) -> Result<()> {
+    notify_rust::Notification::new()
+        .summary("jrni")
+        .body(message)
+        .show()
+        .map_err(|e| Error::from(format!("couldn't show notification: {}", e)))?;
+    Ok(())
+}
+
+#[cfg(not(feature = "notify"))]
+fn notify(message: &str) -> Result<()> {
+    println!("{}", message);
     Ok(())
 }
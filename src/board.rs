@@ -0,0 +1,65 @@
+//! A kanban-style board: entries grouped into columns by the value of a
+//! frontmatter field, e.g. `status: active`, and laid out side by side.
+//!
+use crate::filedb::{inline_value, Entry};
+
+
+/// A column of the board: a value of the field, and the entries that have
+/// it.
+///
+pub struct Column<'a> {
+    pub name: String,
+    pub entries: Vec<&'a Entry>,
+}
+
+/// Group `entries` by their `field` into `columns`, in that order. Without
+/// `columns`, there's one per value found, sorted. Entries without the
+/// field, or with a value that isn't one of `columns`, are left off.
+///
+pub fn group<'a>(entries: &'a [Entry], field: &str, columns: Option<&[&str]>) -> Vec<Column<'a>> {
+    let value = |e: &Entry| e.frontmatter.get(field).map(|v| inline_value(&v).trim().to_owned());
+
+    let names: Vec<String> = match columns {
+        Some(cols) => cols.iter().map(|c| c.to_string()).collect(),
+        None => {
+            let mut found: Vec<String> = entries.iter()
+                .filter_map(value)
+                .filter(|v| !v.is_empty())
+                .collect();
+            found.sort();
+            found.dedup();
+            found
+        },
+    };
+
+    names.into_iter().map(|name| {
+        let entries = entries.iter()
+            .filter(|e| value(e).is_some_and(|v| v.eq_ignore_ascii_case(&name)))
+            .collect();
+        Column { name, entries }
+    }).collect()
+}
+
+/// The board as plain text, each column `width` characters wide, with a
+/// heading and count above its entries.
+///
+pub fn render(columns: &[Column], width: usize, card: impl Fn(&Entry) -> String) -> String {
+    let cell = |text: &str| {
+        let text: String = text.chars().take(width).collect();
+        format!("{:width$}", text, width = width)
+    };
+    let row = |cells: Vec<String>| cells.join("  ").trim_end().to_owned() + "\n";
+
+    let mut out = row(columns.iter()
+        .map(|c| cell(&format!("{} ({})", c.name, c.entries.len())))
+        .collect());
+    out += &row(columns.iter().map(|_| "-".repeat(width)).collect());
+
+    let depth = columns.iter().map(|c| c.entries.len()).max().unwrap_or(0);
+    for i in 0..depth {
+        out += &row(columns.iter()
+            .map(|c| cell(&c.entries.get(i).map(|e| card(e)).unwrap_or_default()))
+            .collect());
+    }
+    out
+}
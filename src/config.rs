@@ -0,0 +1,349 @@
+//! User configuration, read from `~/.config/jrni/config.toml`.
+//!
+//! A journal can also have its own `.jrni.toml` at its root, whose settings
+//! take precedence. This lets one directory hold several journals, each
+//! with their own settings.
+//!
+//! Every setting is optional; a missing file is the same as an empty one.
+//!
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use toml::Value;
+
+use crate::entry_types::EntryType;
+use crate::error::Result;
+use crate::filenames;
+use crate::retention;
+use crate::scope::Visibility;
+
+
+/// Name of the per-journal config file.
+pub const JOURNAL_CONFIG: &str = ".jrni.toml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// The journal, when not given by `-p`, a `.jrni.toml`, or `JRNI_PATH`.
+    /// Only read from the global config.
+    pub path: Option<String>,
+
+    /// Editor command, with any arguments (e.g. `code --wait`), taking
+    /// precedence over `$EDITOR`; see `editor`.
+    pub editor: Option<String>,
+
+    /// Where new entries go in the journal, e.g. `{date:%Y/%m}/{slug}.md`;
+    /// see `filedb::entry_filename`. Defaults to `{date:%F}-{slug}.md`.
+    pub filename: Option<String>,
+
+    /// What filenames may contain, so the journal syncs cleanly to other
+    /// systems; see `filenames`.
+    pub filenames: filenames::Rules,
+
+    /// Tags for new entries created without any.
+    pub default_tags: Vec<String>,
+
+    /// Kinds of entries, by how their filenames end; see `entry_types`.
+    pub types: HashMap<String, EntryType>,
+
+    /// Where templates are kept, relative to the journal unless absolute.
+    /// Defaults to `.templates`.
+    pub template_dir: Option<String>,
+
+    /// Template for new entries created without `--template`.
+    pub default_template: Option<String>,
+
+    /// Default flags per subcommand, e.g. `[command.ls] limit = 50`, for
+    /// those not given on the command line.
+    pub command: HashMap<String, toml::value::Table>,
+
+    /// Per-command confirmation defaults, e.g. `[confirm] retag = false`.
+    /// Commands not listed here always ask.
+    pub confirm: HashMap<String, bool>,
+
+    /// Locale for dates and messages, e.g. "de_DE". Falls back to
+    /// `LC_ALL`/`LC_TIME`/`LANG`.
+    pub locale: Option<String>,
+
+    /// Named line formats for `jrni ls --template`, e.g.
+    /// `[line_formats] brief = "{pubdate}  {id:<20} {tags}"`; see
+    /// `line_format`.
+    pub line_formats: HashMap<String, String>,
+
+    /// How many results listings print when not given `--limit`. Unlimited
+    /// if unset or 0.
+    pub default_limit: Option<usize>,
+
+    /// Month (1-12) in which the fiscal year starts, for `--quarter`.
+    pub fiscal_year_start: Option<u32>,
+
+    /// Rules for tagging entries automatically; see `autotag`.
+    pub autotag: Vec<AutoTagRule>,
+
+    /// If set, the only tags entries are expected to use.
+    pub allowed_tags: Option<Vec<String>>,
+
+    /// The journal is shared by a team, so record who wrote each entry.
+    pub shared: bool,
+
+    /// Name to record as the author of new entries. Defaults to `$USER`
+    /// for shared journals.
+    pub author: Option<String>,
+
+    pub serve: ServeConfig,
+
+    /// Widest audience for entries tagged within each namespace; see `scope`.
+    pub visibility: HashMap<String, Visibility>,
+
+    /// Visibility of entries that no `visibility` rule covers. Defaults to
+    /// local, so untagged entries aren't published by accident.
+    pub default_visibility: Option<Visibility>,
+
+    /// Names and other details to replace when exporting with
+    /// `--anonymize`, and what to replace them with; see `anonymize`.
+    pub anonymize: HashMap<String, String>,
+
+    pub backup: BackupConfig,
+
+    /// When to delete or archive entries by tag, for `jrni gc`; see
+    /// `retention`.
+    pub retention: Vec<retention::Rule>,
+
+    /// Recurring actions for `jrni schedule`; see `schedule`.
+    pub schedule: Vec<ScheduledAction>,
+
+    pub remind: RemindConfig,
+
+    pub capture: CaptureConfig,
+
+    pub encrypt: EncryptConfig,
+
+    pub publish: PublishConfig,
+
+    pub fmt: FmtConfig,
+
+    /// Command for `jrni similar`, which reads text on stdin and prints its
+    /// embedding; see `similar`.
+    pub embedder: Option<String>,
+
+    /// Words to write a day, for `jrni wc` to show progress towards.
+    pub daily_word_target: Option<usize>,
+
+    /// Command for `jrni summarize`, which reads an entry body on stdin and
+    /// prints a summary of it.
+    pub summarizer: Option<String>,
+
+    /// Keep a `modified:` field on entries, updated whenever jrni notices
+    /// that their content changed.
+    pub track_modified: bool,
+
+    /// Keep `words:` and `reading_time:` fields on entries, updated
+    /// whenever jrni notices that their content changed.
+    pub track_stats: bool,
+
+    /// Commit whatever each command changes to the journal's git
+    /// repository; see `git`.
+    pub git: bool,
+}
+
+/// The frontmatter style `jrni fmt` rewrites entries into; see `fmt`.
+///
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct FmtConfig {
+    /// Fields to put first, in this order; the rest follow alphabetically.
+    /// Defaults to tags, id, pubdate, title.
+    pub key_order: Option<Vec<String>>,
+}
+
+/// Checks entries must pass before `jrni publish`; see `lint`.
+///
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct PublishConfig {
+    /// Frontmatter fields that must be present. Defaults to title and
+    /// summary.
+    pub required: Option<Vec<String>>,
+    /// Most lines with TODO allowed. Defaults to none.
+    pub max_todos: Option<usize>,
+    /// Whether links must resolve. Defaults to true.
+    pub check_links: Option<bool>,
+    /// Command printing unknown words in the body on stdin, e.g.
+    /// "aspell list".
+    pub spellcheck: Option<String>,
+    /// Whether exports leave out entries that haven't been published.
+    /// Defaults to false.
+    pub only_published: Option<bool>,
+}
+
+/// Keys for encrypted entries; see `crypt`.
+///
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct EncryptConfig {
+    /// age public key new encrypted entries are encrypted to.
+    pub recipient: Option<String>,
+    /// age identity file, used to decrypt entries for editing.
+    pub identity: Option<String>,
+}
+
+/// What to record about the environment in the frontmatter of new entries.
+///
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct CaptureConfig {
+    pub hostname: bool,
+    /// The git branch checked out in the working directory, if any.
+    pub git_branch: bool,
+    /// The working directory.
+    pub cwd: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct RemindConfig {
+    /// Don't nag during this daily window, e.g. "22:00-08:00".
+    pub quiet_hours: Option<String>,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScheduledAction {
+    pub name: String,
+    /// e.g. "daily 20:00" or "weekly sun 18:00".
+    pub when: String,
+    /// Arguments to run jrni with, e.g. `["backup"]`.
+    pub args: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct BackupConfig {
+    /// Where `jrni backup` uploads to, e.g. `s3://bucket/journal`.
+    pub to: Option<String>,
+    /// Endpoint URL for S3-compatible stores other than AWS.
+    pub endpoint: Option<String>,
+    /// age public key backups are encrypted to.
+    pub recipient: Option<String>,
+    /// age identity file, used by `jrni backup --verify` to read manifests.
+    pub identity: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ServeConfig {
+    /// Address for `jrni serve` to listen on.
+    pub addr: Option<String>,
+    /// `user:password` required via HTTP basic auth.
+    pub auth: Option<String>,
+    /// Who the served journal is for; defaults to the team.
+    pub audience: Option<Visibility>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AutoTagRule {
+    pub tags: Vec<String>,
+    /// Regex matched against the entry's filename.
+    pub filename: Option<String>,
+    /// Directory, relative to the journal, that the entry must be under.
+    pub dir: Option<String>,
+    /// Regex matched against the entry's body.
+    pub body: Option<String>,
+}
+
+impl Config {
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|d| d.join("jrni").join("config.toml"))
+    }
+
+    /// Load the config from its default location, if it exists.
+    ///
+    pub fn load() -> Result<Config> {
+        match Config::default_path() {
+            Some(ref p) if p.exists() => Config::from_path(p),
+            _ => Ok(Config::default()),
+        }
+    }
+
+    pub fn from_path(p: &Path) -> Result<Config> {
+        Ok(toml::from_str(&fs::read_to_string(p)?)?)
+    }
+
+    /// Load the config from its default location, with the `.jrni.toml` of
+    /// the journal at `jrnl_path` (if it has one) layered over it.
+    ///
+    pub fn load_for(jrnl_path: &Path) -> Result<Config> {
+        let mut merged = match Config::default_path() {
+            Some(ref p) if p.exists() => read_value(p)?,
+            _ => Value::Table(toml::value::Table::new()),
+        };
+        let local = jrnl_path.join(JOURNAL_CONFIG);
+        if local.is_file() {
+            merge(&mut merged, read_value(&local)?);
+        }
+        Ok(merged.try_into()?)
+    }
+
+    /// The author to record on new entries, if any.
+    ///
+    pub fn default_author(&self) -> Option<String> {
+        match self.author {
+            Some(ref a) => Some(a.clone()),
+            None if self.shared => env::var("USER").ok(),
+            None => None,
+        }
+    }
+
+    /// Whether `tag` is in the tag vocabulary. Everything is when no
+    /// vocabulary is configured, as are temporary `until:` tags.
+    ///
+    pub fn is_known_tag(&self, tag: &str) -> bool {
+        match self.allowed_tags {
+            Some(ref allowed) =>
+                tag.starts_with("until:") || allowed.iter().any(|t| t == tag),
+            None => true,
+        }
+    }
+}
+
+/// `path` with a leading `~/` replaced by the home directory.
+///
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// The nearest directory at or above `dir` with a `.jrni.toml`, found the
+/// way git finds a repository.
+///
+pub fn find_journal(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|d| d.join(JOURNAL_CONFIG).is_file())
+        .map(Path::to_owned)
+}
+
+fn read_value(p: &Path) -> Result<Value> {
+    Ok(toml::from_str(&fs::read_to_string(p)?)?)
+}
+
+/// Merge `over` into `base`: tables are merged key by key, and anything else
+/// in `over` replaces what's in `base`.
+///
+fn merge(base: &mut Value, over: Value) {
+    match (base, over) {
+        (Value::Table(base), Value::Table(over)) => {
+            for (k, v) in over {
+                match base.get_mut(&k) {
+                    Some(existing) => merge(existing, v),
+                    None => { base.insert(k, v); },
+                }
+            }
+        },
+        (base, over) => *base = over,
+    }
+}
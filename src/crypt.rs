@@ -0,0 +1,76 @@
+//! Encrypted entries. The frontmatter of an encrypted entry stays in the
+//! clear, so it can still be listed and filtered by tag, while its body is
+//! encrypted with the `age` CLI. The body is ASCII-armored so that the entry
+//! remains a text file.
+//!
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use crate::error::{Error, Result};
+
+
+const ARMOR_BEGIN: &str = "-----BEGIN AGE ENCRYPTED FILE-----";
+
+/// Whether an entry body is age ciphertext.
+///
+pub fn is_encrypted(body: &str) -> bool {
+    body.trim_start().starts_with(ARMOR_BEGIN)
+}
+
+/// Encrypt an entry body to the age `recipient`.
+///
+pub fn encrypt(recipient: &str, body: &str) -> Result<String> {
+    let out = run(Command::new("age").arg("-a").arg("-r").arg(recipient), Some(body.as_bytes()))?;
+    String::from_utf8(out).map_err(|_| Error::from("age produced non-UTF-8 armor"))
+}
+
+/// Decrypt an entry body with the age `identity` file.
+///
+pub fn decrypt(identity: &str, body: &str) -> Result<String> {
+    let out = run(Command::new("age").arg("-d").arg("-i").arg(identity),
+        Some(body.trim().as_bytes()))?;
+    String::from_utf8(out).map_err(|_| Error::from("decrypted body isn't UTF-8"))
+}
+
+/// Split the raw contents of an entry into its frontmatter, up to and
+/// including the `---` line, and its body.
+///
+pub fn split_entry(raw: &str) -> (&str, &str) {
+    let mut offset = 0;
+    for line in raw.split_inclusive('\n') {
+        offset += line.len();
+        if line.trim() == "---" {
+            return raw.split_at(offset);
+        }
+    }
+    ("", raw)
+}
+
+/// Run a command, feeding it `input` on stdin, and return its stdout.
+///
+pub(crate) fn run(cmd: &mut Command, input: Option<&[u8]>) -> Result<Vec<u8>> {
+    let mut child = cmd
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()?;
+
+    // Feed stdin from another thread so a chatty command can't deadlock us.
+    let writer = match (input, child.stdin.take()) {
+        (Some(data), Some(mut stdin)) => {
+            let data = data.to_vec();
+            Some(thread::spawn(move || stdin.write_all(&data)))
+        },
+        _ => None,
+    };
+
+    let output = child.wait_with_output()?;
+    if let Some(w) = writer {
+        w.join().map_err(|_| Error::from("stdin writer panicked"))??;
+    }
+    if !output.status.success() {
+        bail!("{:?} failed: {}", cmd, output.status);
+    }
+    Ok(output.stdout)
+}
@@ -1,16 +1,475 @@
 use chrono::prelude::*;
+use chrono::Duration;
 use crate::error::Result;
 
 const FMT_STR: &str = "%F %T%.3f %z";
 
+/// Hour used when a date is given without a time, e.g. "yesterday".
+const DEFAULT_HOUR: u32 = 9;
+
 pub fn now() -> DateTime<Local> {
     Local::now()
 }
 
-pub fn to_str(dt: DateTime<Local>) -> String {
+pub fn to_str<Tz: TimeZone>(dt: DateTime<Tz>) -> String
+    where Tz::Offset: std::fmt::Display
+{
     dt.format(FMT_STR).to_string()
 }
 
-pub fn from_str<T>(s: String) -> Result<DateTime<FixedOffset>> {
+pub fn from_str(s: String) -> Result<DateTime<FixedOffset>> {
     Ok(DateTime::parse_from_str(&s, FMT_STR)?)
 }
+
+/// Parse a relative duration like "7d" or "2w". Units are h(ours),
+/// d(ays), w(eeks), m(onths, of 30 days), and y(ears, of 365 days).
+///
+pub fn parse_relative(s: &str) -> Result<Duration> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let n: i64 = match s[..split].parse() {
+        Ok(n) => n,
+        Err(_) => bail!("couldn't parse duration '{}', expected e.g. 7d", s),
+    };
+    let hours = match &s[split..] {
+        "h" => 1,
+        "d" => 24,
+        "w" => 24 * 7,
+        "m" => 24 * 30,
+        "y" => 24 * 365,
+        _ => bail!("unknown unit in duration '{}'; use h, d, w, m, or y", s),
+    };
+    match n.checked_mul(hours).and_then(Duration::try_hours) {
+        Some(d) => Ok(d),
+        None => bail!("duration '{}' is too long", s),
+    }
+}
+
+/// The day `s` (see `parse_relative`) before `day`.
+///
+pub fn before(day: NaiveDate, s: &str) -> Result<NaiveDate> {
+    match day.checked_sub_signed(parse_relative(s)?) {
+        Some(d) => Ok(d),
+        None => bail!("'{}' is too long ago", s),
+    }
+}
+
+/// The first day meant by a `--since` like "2024", "2024-05", "2024-05-10",
+/// or a duration before `today` like "2w" (see `parse_relative`).
+///
+pub fn parse_since(s: &str, today: NaiveDate) -> Result<NaiveDate> {
+    let s = s.trim();
+    let full = match s.len() {
+        4 => format!("{}-01-01", s),
+        7 => format!("{}-01", s),
+        _ => s.to_owned(),
+    };
+    match NaiveDate::parse_from_str(&full, "%Y-%m-%d") {
+        Ok(day) => Ok(day),
+        Err(_) if s.ends_with(|c: char| c.is_ascii_alphabetic()) =>
+            before(today, s),
+        Err(_) => bail!("couldn't parse '{}', expected e.g. 2024, 2024-05, 2024-05-10 or 2w", s),
+    }
+}
+
+/// Whether `t` falls within a daily window like "22:00-08:00", which may wrap
+/// around midnight.
+///
+pub fn in_daily_window(window: &str, t: NaiveTime) -> Result<bool> {
+    let bounds: Vec<Option<NaiveTime>> = window.splitn(2, '-')
+        .map(|b| NaiveTime::parse_from_str(b.trim(), "%H:%M").ok())
+        .collect();
+    let (start, end) = match bounds.as_slice() {
+        [Some(start), Some(end)] => (*start, *end),
+        _ => bail!("couldn't parse time window '{}', expected e.g. 22:00-08:00", window),
+    };
+
+    Ok(if start <= end {
+        t >= start && t < end
+    } else {
+        t >= start || t < end
+    })
+}
+
+/// A half-open range of days, `[start, end)`, used to filter entries.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Period {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+impl Period {
+    pub fn contains(&self, day: NaiveDate) -> bool {
+        day >= self.start && day < self.end
+    }
+
+    /// Parse an ISO 8601 week like "2024-W19".
+    ///
+    pub fn iso_week(s: &str) -> Result<Period> {
+        let (year, week) = split_period(s, 'W')?;
+        let start = NaiveDate::from_isoywd_opt(year, week, Weekday::Mon);
+        match start.and_then(|start| Some((start, start.checked_add_signed(Duration::days(7))?))) {
+            Some((start, end)) => Ok(Period { start, end }),
+            None => bail!("no such ISO week '{}'", s),
+        }
+    }
+
+    /// The ISO week, Monday to Sunday, `day` is in.
+    ///
+    pub fn week(day: NaiveDate) -> Result<Period> {
+        let monday = Duration::days(day.weekday().num_days_from_monday() as i64);
+        let start = day.checked_sub_signed(monday);
+        match start.and_then(|start| Some((start, start.checked_add_signed(Duration::days(7))?))) {
+            Some((start, end)) => Ok(Period { start, end }),
+            None => bail!("no such week as that of {}", day),
+        }
+    }
+
+    /// From `start` through `end`, inclusive.
+    ///
+    pub fn between(start: NaiveDate, end: NaiveDate) -> Result<Period> {
+        match end.checked_add_signed(Duration::days(1)) {
+            Some(end) => Ok(Period { start, end }),
+            None => bail!("no day after {}", end),
+        }
+    }
+
+    /// The calendar month `day` is in.
+    ///
+    pub fn month(day: NaiveDate) -> Result<Period> {
+        match (month_start(day.year(), day.month(), 0), month_start(day.year(), day.month(), 1)) {
+            (Some(start), Some(end)) => Ok(Period { start, end }),
+            _ => bail!("no such month '{}'", day.format("%Y-%m")),
+        }
+    }
+
+    /// Parse a calendar month like "2024-04".
+    ///
+    pub fn parse_month(s: &str) -> Result<Period> {
+        match NaiveDate::parse_from_str(&format!("{}-01", s.trim()), "%Y-%m-%d") {
+            Ok(day) => Period::month(day),
+            Err(_) => bail!("couldn't parse month '{}', expected e.g. 2024-04", s),
+        }
+    }
+
+    /// The calendar month before the one `day` is in.
+    ///
+    pub fn previous_month(day: NaiveDate) -> Result<Period> {
+        match month_start(day.year(), day.month(), 0).and_then(|d| d.pred_opt()) {
+            Some(last) => Period::month(last),
+            None => bail!("no month before {}", day.format("%Y-%m")),
+        }
+    }
+
+    /// Parse a quarter like "2024-Q2". Quarters are counted from the month
+    /// `fiscal_start` (1 for calendar quarters) of the given year.
+    ///
+    pub fn quarter(s: &str, fiscal_start: u32) -> Result<Period> {
+        let (year, quarter) = split_period(s, 'Q')?;
+        if !(1..=4).contains(&quarter) {
+            bail!("no such quarter '{}'", s);
+        }
+        if !(1..=12).contains(&fiscal_start) {
+            bail!("fiscal year must start in month 1-12, not {}", fiscal_start);
+        }
+        match (month_start(year, fiscal_start, (quarter - 1) * 3),
+               month_start(year, fiscal_start, quarter * 3)) {
+            (Some(start), Some(end)) => Ok(Period { start, end }),
+            _ => bail!("no such quarter '{}'", s),
+        }
+    }
+}
+
+/// The first day of the month `plus` months after `month` in `year`, if
+/// it's within the dates chrono can represent.
+///
+fn month_start(year: i32, month: u32, plus: u32) -> Option<NaiveDate> {
+    let month0 = month - 1 + plus;
+    NaiveDate::from_ymd_opt(year.checked_add((month0 / 12) as i32)?, month0 % 12 + 1, 1)
+}
+
+/// Split e.g. "2024-W19" into (2024, 19).
+///
+fn split_period(s: &str, marker: char) -> Result<(i32, u32)> {
+    let sep = format!("-{}", marker);
+    let upper = s.trim().to_uppercase();
+    if let Some(idx) = upper.find(&sep) {
+        let year = upper[..idx].parse::<i32>();
+        let num = upper[idx + sep.len()..].parse::<u32>();
+        if let (Ok(year), Ok(num)) = (year, num) {
+            return Ok((year, num));
+        }
+    }
+    bail!("couldn't parse '{}', expected something like 2024-{}2", s, marker)
+}
+
+/// Parse a loose, human date/time expression relative to `now`, e.g.
+/// "yesterday 9pm", "monday", "2020-04-05 14:30", or "9:15am".
+///
+/// Weekday names refer to the most recent such day (including today).
+///
+pub fn parse_human(s: &str, now: DateTime<Local>) -> Option<DateTime<Local>> {
+    let today = now.naive_local().date();
+    let mut date = None;
+    let mut time = None;
+    let lowered = s.trim().to_lowercase();
+    let mut tokens: Vec<&str> = lowered.split_whitespace().collect();
+
+    // Allow "9 pm" as well as "9pm".
+    if tokens.len() >= 2 {
+        let last = tokens[tokens.len() - 1];
+        if last == "am" || last == "pm" {
+            tokens.pop();
+            let joined = format!("{}{}", tokens.pop().unwrap(), last);
+            time = Some(parse_time(&joined)?);
+        }
+    }
+
+    for tok in tokens {
+        if let Some(d) = parse_day(tok, today) {
+            if date.is_some() {
+                return None;
+            }
+            if tok == "now" {
+                time = Some(now.naive_local().time());
+            }
+            date = Some(d);
+        } else if let Some(t) = parse_time(tok) {
+            if time.is_some() {
+                return None;
+            }
+            time = Some(t);
+        } else {
+            return None;
+        }
+    }
+
+    if date.is_none() && time.is_none() {
+        return None;
+    }
+    let date = date.unwrap_or(today);
+    let time = match time {
+        Some(t) => t,
+        None if date == today => now.naive_local().time(),
+        None => NaiveTime::from_hms_opt(DEFAULT_HOUR, 0, 0)?,
+    };
+
+    Local.from_local_datetime(&date.and_time(time)).earliest()
+}
+
+/// Split a jrnl-style leading timestamp off of some text, e.g.
+/// "yesterday 9pm: had dinner" yields the parsed time and "had dinner".
+///
+/// Returns None if the text doesn't start with a recognizable expression.
+///
+pub fn split_leading(text: &str, now: DateTime<Local>)
+    -> Option<(DateTime<Local>, &str)>
+{
+    let idx = text.find(": ")?;
+    let when = parse_human(&text[..idx], now)?;
+    Some((when, text[idx + 2..].trim_start()))
+}
+
+fn parse_day(tok: &str, today: NaiveDate) -> Option<NaiveDate> {
+    match tok {
+        "today" | "now" => return Some(today),
+        "yesterday" => return Some(today - Duration::days(1)),
+        "tomorrow" => return Some(today + Duration::days(1)),
+        _ => (),
+    }
+
+    if let Ok(d) = NaiveDate::parse_from_str(tok, "%Y-%m-%d") {
+        return Some(d);
+    }
+
+    let weekday = match tok {
+        "monday" | "mon" => Weekday::Mon,
+        "tuesday" | "tue" | "tues" => Weekday::Tue,
+        "wednesday" | "wed" => Weekday::Wed,
+        "thursday" | "thu" | "thurs" => Weekday::Thu,
+        "friday" | "fri" => Weekday::Fri,
+        "saturday" | "sat" => Weekday::Sat,
+        "sunday" | "sun" => Weekday::Sun,
+        _ => return None,
+    };
+    let back = (7 + today.weekday().num_days_from_monday()
+        - weekday.num_days_from_monday()) % 7;
+    Some(today - Duration::days(back as i64))
+}
+
+/// Parse "9pm", "9:30am", "noon", or a 24-hour "21:00".
+///
+fn parse_time(tok: &str) -> Option<NaiveTime> {
+    match tok {
+        "noon" => return NaiveTime::from_hms_opt(12, 0, 0),
+        "midnight" => return NaiveTime::from_hms_opt(0, 0, 0),
+        _ => (),
+    }
+
+    let (clock, pm) = if let Some(clock) = tok.strip_suffix("am") {
+        (clock, Some(false))
+    } else if let Some(clock) = tok.strip_suffix("pm") {
+        (clock, Some(true))
+    } else {
+        (tok, None)
+    };
+
+    let mut parts = clock.splitn(2, ':');
+    let hour: u32 = parts.next()?.parse().ok()?;
+    let minute: u32 = match parts.next() {
+        Some(m) => m.parse().ok()?,
+        // A bare number like "21" is too ambiguous to treat as a time.
+        None if pm.is_none() => return None,
+        None => 0,
+    };
+
+    let hour = match pm {
+        Some(_) if hour == 0 || hour > 12 => return None,
+        Some(true) => hour % 12 + 12,
+        Some(false) => hour % 12,
+        None => hour,
+    };
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn split_period_takes_year_and_number() {
+        assert_eq!(split_period("2024-W19", 'W').unwrap(), (2024, 19));
+        assert_eq!(split_period(" 2024-q2 ", 'Q').unwrap(), (2024, 2));
+        assert!(split_period("2024-19", 'W').is_err());
+        assert!(split_period("2024-Q", 'Q').is_err());
+        assert!(split_period("W19", 'W').is_err());
+    }
+
+    #[test]
+    fn quarters_count_from_the_fiscal_start() {
+        assert_eq!(Period::quarter("2024-Q1", 1).unwrap(),
+                   Period { start: day(2024, 1, 1), end: day(2024, 4, 1) });
+        assert_eq!(Period::quarter("2024-Q4", 1).unwrap(),
+                   Period { start: day(2024, 10, 1), end: day(2025, 1, 1) });
+        // Fiscal years that run into the next calendar year.
+        assert_eq!(Period::quarter("2024-Q4", 4).unwrap(),
+                   Period { start: day(2025, 1, 1), end: day(2025, 4, 1) });
+        assert_eq!(Period::quarter("2024-Q1", 10).unwrap(),
+                   Period { start: day(2024, 10, 1), end: day(2025, 1, 1) });
+        assert_eq!(Period::quarter("2024-Q2", 10).unwrap(),
+                   Period { start: day(2025, 1, 1), end: day(2025, 4, 1) });
+        assert!(Period::quarter("2024-Q0", 1).is_err());
+        assert!(Period::quarter("2024-Q5", 1).is_err());
+        assert!(Period::quarter("2024-Q1", 13).is_err());
+    }
+
+    #[test]
+    fn periods_past_the_end_of_time_are_errors() {
+        assert!(Period::quarter("999999-Q1", 1).is_err());
+        assert!(Period::quarter("262143-Q4", 1).is_err());
+        assert!(Period::quarter("2147483647-Q4", 12).is_err());
+        assert!(Period::iso_week("262143-W52").is_err());
+        assert!(Period::week(NaiveDate::MAX).is_err());
+        assert!(Period::week(NaiveDate::MIN).is_err());
+        assert!(Period::between(day(2024, 1, 1), NaiveDate::MAX).is_err());
+        assert!(Period::month(NaiveDate::MAX).is_err());
+        assert!(Period::previous_month(NaiveDate::MIN).is_err());
+    }
+
+    #[test]
+    fn weeks_run_monday_to_sunday() {
+        let w19 = Period::iso_week("2024-W19").unwrap();
+        assert_eq!(w19, Period { start: day(2024, 5, 6), end: day(2024, 5, 13) });
+        assert_eq!(Period::week(day(2024, 5, 12)).unwrap(), w19);
+        assert_eq!(Period::week(day(2024, 5, 6)).unwrap(), w19);
+        // ISO week 1 of 2025 starts in 2024.
+        assert_eq!(Period::iso_week("2025-W01").unwrap().start, day(2024, 12, 30));
+        assert!(Period::iso_week("2024-W53").is_err());
+    }
+
+    #[test]
+    fn months_and_ranges() {
+        assert_eq!(Period::parse_month("2024-02").unwrap(),
+                   Period { start: day(2024, 2, 1), end: day(2024, 3, 1) });
+        assert_eq!(Period::previous_month(day(2024, 1, 15)).unwrap(),
+                   Period { start: day(2023, 12, 1), end: day(2024, 1, 1) });
+        let p = Period::between(day(2024, 5, 1), day(2024, 5, 3)).unwrap();
+        assert!(p.contains(day(2024, 5, 3)));
+        assert!(!p.contains(day(2024, 5, 4)));
+        assert!(!p.contains(day(2024, 4, 30)));
+    }
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Local> {
+        Local.from_local_datetime(&day(y, m, d).and_hms_opt(h, min, 0).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn parse_human_days_and_times() {
+        // A Friday.
+        let now = at(2024, 5, 10, 15, 30);
+        assert_eq!(parse_human("yesterday 9pm", now), Some(at(2024, 5, 9, 21, 0)));
+        assert_eq!(parse_human("yesterday 9 pm", now), Some(at(2024, 5, 9, 21, 0)));
+        assert_eq!(parse_human("2020-04-05 14:30", now), Some(at(2020, 4, 5, 14, 30)));
+        assert_eq!(parse_human("9:15am", now), Some(at(2024, 5, 10, 9, 15)));
+        assert_eq!(parse_human("noon", now), Some(at(2024, 5, 10, 12, 0)));
+        // Weekdays are the most recent, today included; without a time,
+        // another day is at the default hour and today is now.
+        assert_eq!(parse_human("Monday", now), Some(at(2024, 5, 6, DEFAULT_HOUR, 0)));
+        assert_eq!(parse_human("fri", now), Some(now));
+        assert_eq!(parse_human("today", now), Some(now));
+    }
+
+    #[test]
+    fn parse_human_rejects_what_it_cant_read() {
+        let now = at(2024, 5, 10, 15, 30);
+        assert_eq!(parse_human("", now), None);
+        assert_eq!(parse_human("21", now), None);
+        assert_eq!(parse_human("13pm", now), None);
+        assert_eq!(parse_human("9:75", now), None);
+        assert_eq!(parse_human("today yesterday", now), None);
+        assert_eq!(parse_human("9am 10am", now), None);
+        assert_eq!(parse_human("had dinner", now), None);
+    }
+
+    #[test]
+    fn split_leading_takes_a_timestamp_before_a_colon() {
+        let now = at(2024, 5, 10, 15, 30);
+        assert_eq!(split_leading("yesterday 9pm: had dinner", now),
+                   Some((at(2024, 5, 9, 21, 0), "had dinner")));
+        assert_eq!(split_leading("note to self: call mum", now), None);
+        assert_eq!(split_leading("no colon here", now), None);
+    }
+
+    #[test]
+    fn daily_windows_may_wrap_past_midnight() {
+        let t = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        assert!(in_daily_window("09:00-17:00", t(9, 0)).unwrap());
+        assert!(!in_daily_window("09:00-17:00", t(17, 0)).unwrap());
+        assert!(!in_daily_window("09:00-17:00", t(8, 59)).unwrap());
+        assert!(in_daily_window("22:00-08:00", t(23, 30)).unwrap());
+        assert!(in_daily_window("22:00 - 08:00", t(3, 0)).unwrap());
+        assert!(!in_daily_window("22:00-08:00", t(12, 0)).unwrap());
+        assert!(in_daily_window("22:00", t(23, 0)).is_err());
+        assert!(in_daily_window("10pm-8am", t(23, 0)).is_err());
+    }
+
+    #[test]
+    fn parse_since_takes_dates_or_durations() {
+        let today = day(2024, 5, 10);
+        assert_eq!(parse_since("2024", today).unwrap(), day(2024, 1, 1));
+        assert_eq!(parse_since("2024-05", today).unwrap(), day(2024, 5, 1));
+        assert_eq!(parse_since(" 2024-05-03 ", today).unwrap(), day(2024, 5, 3));
+        assert_eq!(parse_since("2w", today).unwrap(), day(2024, 4, 26));
+        assert_eq!(parse_since("1d", today).unwrap(), day(2024, 5, 9));
+        assert!(parse_since("2024-13", today).is_err());
+        assert!(parse_since("last week", today).is_err());
+        assert!(parse_since("2x", today).is_err());
+        assert!(parse_since("999999999999y", today).is_err());
+    }
+}
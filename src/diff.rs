@@ -0,0 +1,219 @@
+//! Word-level diffs between entries.
+//!
+use std::cmp::max;
+
+use crate::filedb::{self, Entry};
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Change<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const RESET: &str = "\x1b[0m";
+
+/// Split text into alternating runs of whitespace and non-whitespace, so
+/// that joining the pieces gives back the original.
+///
+fn tokens(text: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut in_space = None;
+    for (i, c) in text.char_indices() {
+        let space = c.is_whitespace();
+        if in_space.is_some_and(|s| s != space) {
+            tokens.push(&text[start..i]);
+            start = i;
+        }
+        in_space = Some(space);
+    }
+    if start < text.len() {
+        tokens.push(&text[start..]);
+    }
+    tokens
+}
+
+/// The changes turning `a` into `b`, word by word.
+///
+pub fn words<'a>(a: &'a str, b: &'a str) -> Vec<Change<'a>> {
+    let a = tokens(a);
+    let b = tokens(b);
+
+    // Only the middle, past any common prefix and suffix, needs the
+    // quadratic treatment.
+    let prefix = a.iter().zip(b.iter()).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..].iter().rev().zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y).count();
+
+    let mut changes: Vec<Change> = a[..prefix].iter().map(|t| Change::Same(t)).collect();
+    middle(&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix], &mut changes);
+    changes.extend(a[a.len() - suffix..].iter().map(|t| Change::Same(t)));
+    changes
+}
+
+/// The changes turning `a` into `b`, by Hirschberg's algorithm: split `a`
+/// in half, find where in `b` the halves' longest common subsequences meet,
+/// and diff each side. It takes time proportional to `a.len() * b.len()`,
+/// but only linear space, so rewritten entries don't need a table of every
+/// pair of words.
+///
+fn middle<'a>(a: &[&'a str], b: &[&'a str], changes: &mut Vec<Change<'a>>) {
+    if a.is_empty() || b.is_empty() {
+        changes.extend(b.iter().copied().map(Change::Added));
+        changes.extend(a.iter().copied().map(Change::Removed));
+        return;
+    }
+    if a.len() == 1 {
+        match b.iter().position(|t| *t == a[0]) {
+            Some(k) => {
+                changes.extend(b[..k].iter().copied().map(Change::Added));
+                changes.push(Change::Same(a[0]));
+                changes.extend(b[k + 1..].iter().copied().map(Change::Added));
+            },
+            None => {
+                changes.extend(b.iter().copied().map(Change::Added));
+                changes.push(Change::Removed(a[0]));
+            },
+        }
+        return;
+    }
+
+    let mid = a.len() / 2;
+    let front = lcs_lengths(a[..mid].iter().copied(), b.iter().copied());
+    let back = lcs_lengths(a[mid..].iter().rev().copied(), b.iter().rev().copied());
+    let split = (0..=b.len())
+        .max_by_key(|&k| (front[k] + back[b.len() - k], std::cmp::Reverse(k)))
+        .unwrap_or(0);
+
+    middle(&a[..mid], &b[..split], changes);
+    middle(&a[mid..], &b[split..], changes);
+}
+
+/// The length of the longest common subsequence of `a` and each prefix of
+/// `b`, keeping only one row of the table at a time.
+///
+fn lcs_lengths<'a, A, B>(a: A, b: B) -> Vec<u32>
+where
+    A: Iterator<Item = &'a str>,
+    B: Iterator<Item = &'a str> + Clone,
+{
+    let mut row = vec![0u32; b.clone().count() + 1];
+    for x in a {
+        // Holds the previous row's value at j, before it's overwritten.
+        let mut diagonal = 0;
+        for (j, y) in b.clone().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = match x == y {
+                true => diagonal + 1,
+                false => max(above, row[j]),
+            };
+            diagonal = above;
+        }
+    }
+    row
+}
+
+/// Render changes inline, in red and green, or without `color`, marked up
+/// like `git diff --word-diff` does: `[-removed-]{+added+}`.
+///
+pub fn render(changes: &[Change], color: bool) -> String {
+    let mut out = String::new();
+    let mut i = 0;
+    while i < changes.len() {
+        // Group runs of the same kind of change.
+        let kind = std::mem::discriminant(&changes[i]);
+        let mut text = String::new();
+        let start = i;
+        while i < changes.len() && std::mem::discriminant(&changes[i]) == kind {
+            text.push_str(match changes[i] {
+                Change::Same(t) | Change::Removed(t) | Change::Added(t) => t,
+            });
+            i += 1;
+        }
+        let (open, close) = match (changes[start], color) {
+            (Change::Same(_), _) => ("", ""),
+            (Change::Removed(_), true) => (RED, RESET),
+            (Change::Added(_), true) => (GREEN, RESET),
+            (Change::Removed(_), false) => ("[-", "-]"),
+            (Change::Added(_), false) => ("{+", "+}"),
+        };
+        out.push_str(open);
+        out.push_str(&text);
+        out.push_str(close);
+    }
+    out
+}
+
+/// Frontmatter fields that differ between `a` and `b`, as `-field: old` and
+/// `+field: new` lines.
+///
+pub fn frontmatter(a: &Entry, b: &Entry, color: bool) -> String {
+    let mut keys: Vec<String> = a.frontmatter.keys();
+    keys.extend(b.frontmatter.keys());
+    keys.sort();
+    keys.dedup();
+
+    let (red, green, reset) = match color {
+        true => (RED, GREEN, RESET),
+        false => ("", "", ""),
+    };
+    let mut out = String::new();
+    for k in keys {
+        let (old, new) = (a.frontmatter.get(&k), b.frontmatter.get(&k));
+        if old == new {
+            continue;
+        }
+        if let Some(v) = old {
+            out.push_str(&format!("{}-{}: {}{}\n", red, k, filedb::inline_value(&v), reset));
+        }
+        if let Some(v) = new {
+            out.push_str(&format!("{}+{}: {}{}\n", green, k, filedb::inline_value(&v), reset));
+        }
+    }
+    out
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn side(changes: &[Change], keep_removed: bool) -> String {
+        changes.iter().filter_map(|c| match *c {
+            Change::Same(t) => Some(t),
+            Change::Removed(t) if keep_removed => Some(t),
+            Change::Added(t) if !keep_removed => Some(t),
+            _ => None,
+        }).collect()
+    }
+
+    /// The length of the longest common subsequence, the slow way.
+    fn lcs(a: &[&str], b: &[&str]) -> usize {
+        let mut t = vec![vec![0; b.len() + 1]; a.len() + 1];
+        for i in 0..a.len() {
+            for j in 0..b.len() {
+                t[i + 1][j + 1] = match a[i] == b[j] {
+                    true => t[i][j] + 1,
+                    false => max(t[i][j + 1], t[i + 1][j]),
+                };
+            }
+        }
+        t[a.len()][b.len()]
+    }
+
+    proptest! {
+        #[test]
+        fn words_is_a_minimal_diff(a in "[abc ]{0,40}", b in "[abc ]{0,40}") {
+            let changes = words(&a, &b);
+            prop_assert_eq!(side(&changes, true), a.clone());
+            prop_assert_eq!(side(&changes, false), b.clone());
+            let same = changes.iter().filter(|c| matches!(c, Change::Same(_))).count();
+            prop_assert_eq!(same, lcs(&tokens(&a), &tokens(&b)));
+        }
+    }
+}
@@ -0,0 +1,150 @@
+//! Opening files in the user's editor. The editor is the `editor` from the
+//! config, or `$EDITOR`, or failing both `nvim`, and can be given arguments,
+//! as in `editor = "code --wait"`.
+//!
+//! Editors are asked to open at a line in whichever way they understand:
+//! `+N` for vi, emacs, nano and most terminal editors, `--goto file:N` for
+//! VS Code and `file:N` for the likes of Sublime Text and Helix.
+//!
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use crate::error::Result;
+use crate::filedb::Entry;
+
+
+/// The editor used when neither the config nor `$EDITOR` names one.
+pub const DEFAULT_EDITOR: &str = "nvim";
+
+/// Editors that take `--goto file:N` to open at a line.
+const GOTO_EDITORS: &[&str] = &["code", "code-insiders", "codium", "cursor"];
+
+/// Editors that take `file:N` to open at a line.
+const SUFFIX_EDITORS: &[&str] = &["subl", "sublime_text", "hx", "helix", "zed", "kak"];
+
+#[derive(Debug)]
+pub struct Editor {
+    program: String,
+    args: Vec<String>,
+}
+
+impl Editor {
+    /// The editor `command` (a program and its arguments), or if that's
+    /// None, the one in `$EDITOR`, or `nvim`.
+    ///
+    pub fn new(command: Option<&str>) -> Result<Editor> {
+        let command = match command {
+            Some(c) => c.to_owned(),
+            None => match env::var("EDITOR") {
+                Ok(e) if !e.trim().is_empty() => e,
+                _ => DEFAULT_EDITOR.to_owned(),
+            },
+        };
+        let mut words = split_words(&command)?.into_iter();
+        match words.next() {
+            Some(program) => Ok(Editor { program, args: words.collect() }),
+            None => bail!("the editor command is empty"),
+        }
+    }
+
+    /// Open `path`, at `line` (from 1) if given, and wait for the editor to
+    /// exit. Returns whether the file was changed.
+    ///
+    pub fn open(&self, path: &Path, line: Option<usize>) -> Result<bool> {
+        let before = fs::read(path).ok();
+
+        let mut cmd = Command::new(&self.program);
+        cmd.args(&self.args);
+        match line {
+            Some(line) => { cmd.args(self.line_args(path, line)); },
+            None => { cmd.arg(path); },
+        }
+        let status = match cmd.status() {
+            Ok(status) => status,
+            Err(e) => bail!("couldn't run the editor '{}': {}", self.program, e),
+        };
+        if !status.success() {
+            bail!("the editor '{}' exited with {}", self.program, status);
+        }
+
+        Ok(fs::read(path).ok() != before)
+    }
+
+    /// Arguments that open `path` at `line`, in the form this editor takes.
+    ///
+    fn line_args(&self, path: &Path, line: usize) -> Vec<String> {
+        let name = Path::new(&self.program).file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let path = path.display().to_string();
+        if GOTO_EDITORS.contains(&name.as_str()) {
+            vec!["--goto".to_owned(), format!("{}:{}", path, line)]
+        } else if SUFFIX_EDITORS.contains(&name.as_str()) {
+            vec![format!("{}:{}", path, line)]
+        } else {
+            vec![format!("+{}", line), path]
+        }
+    }
+}
+
+/// The line (from 1) where writing in `e` starts: the first line of its
+/// body that isn't blank, or just past them if they all are. For a new
+/// entry, that's below its frontmatter.
+///
+pub fn start_of_body(e: &Entry) -> usize {
+    let blank = e.body.lines().take_while(|l| l.trim().is_empty()).count();
+    e.body_line + blank
+}
+
+/// `command` split into words as a shell would, honoring single and double
+/// quotes and backslashes, so `"C:\Program Files\..." --wait` works.
+///
+fn split_words(command: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    let mut chars = command.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') => match chars.next() {
+                Some(next @ '"') | Some(next @ '\\') => word.push(next),
+                Some(next) => {
+                    word.push('\\');
+                    word.push(next);
+                },
+                None => word.push('\\'),
+            },
+            (Some(_), c) => word.push(c),
+            (None, '\'') | (None, '"') => {
+                quote = Some(c);
+                in_word = true;
+            },
+            (None, '\\') => {
+                word.push(chars.next().unwrap_or('\\'));
+                in_word = true;
+            },
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            },
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            },
+        }
+    }
+    if let Some(q) = quote {
+        bail!("unclosed {} in the editor command '{}'", q, command);
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
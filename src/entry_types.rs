@@ -0,0 +1,76 @@
+//! Kinds of entries, told apart by the ends of their filenames, so one
+//! journal can hold notes, task lists and worklogs alike:
+//!
+//! ```toml
+//! [types.task]
+//! extension = ".todo.md"
+//! template = "todo"
+//! tags = ["todo"]
+//!
+//! [types.worklog]
+//! extension = ".log.md"
+//! ```
+//!
+//! Where extensions overlap (`.md` and `.todo.md`), the longest that fits
+//! wins. Extensions must end in `.md` or `.txt`, or the entries won't be
+//! found in the journal.
+//!
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::error::Result;
+
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct EntryType {
+    /// How filenames of entries of this type end, e.g. `.todo.md`.
+    pub extension: String,
+    /// Template new entries of this type start from, unless given another.
+    pub template: Option<String>,
+    /// Tags for new entries of this type created without any.
+    pub tags: Vec<String>,
+}
+
+/// The name of the type of the entry at `path`, if it's of any.
+///
+pub fn type_of<'a>(types: &'a HashMap<String, EntryType>, path: &Path) -> Option<&'a str> {
+    let name = path.file_name()?.to_string_lossy();
+    types.iter()
+        .filter(|(_, t)| !t.extension.is_empty() && name.ends_with(&t.extension))
+        .max_by(|a, b| a.1.extension.len().cmp(&b.1.extension.len()).then(b.0.cmp(a.0)))
+        .map(|(name, _)| name.as_str())
+}
+
+/// The type called `name`.
+///
+pub fn get<'a>(types: &'a HashMap<String, EntryType>, name: &str) -> Result<&'a EntryType> {
+    match types.get(name) {
+        Some(t) => Ok(t),
+        None => {
+            let mut known: Vec<&str> = types.keys().map(|k| k.as_str()).collect();
+            known.sort();
+            bail!("no entry type '{}'; configured types are {}", name,
+                  if known.is_empty() { "none".to_owned() } else { known.join(", ") })
+        },
+    }
+}
+
+/// `path` with its extension swapped for that of `kind`, so
+/// `2024-05-10-chores.md` becomes `2024-05-10-chores.todo.md`.
+///
+pub fn with_extension(path: &Path, kind: &EntryType) -> Result<PathBuf> {
+    let valid = kind.extension.starts_with('.') && !kind.extension.contains('/')
+        && (kind.extension.ends_with(".md") || kind.extension.ends_with(".txt"));
+    if !valid {
+        bail!("entry type extension '{}' should start with '.' and end in .md or .txt",
+              kind.extension);
+    }
+    let stem = match path.file_stem() {
+        Some(stem) => stem.to_string_lossy(),
+        None => bail!("{} has no filename", path.display()),
+    };
+    Ok(path.with_file_name(format!("{}{}", stem, kind.extension)))
+}
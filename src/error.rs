@@ -1,9 +1,30 @@
 use chrono;
 use std::io;
+use std::path::PathBuf;
 
 error_chain! {
     foreign_links {
         ChronoParse(chrono::format::ParseError);
         IO(io::Error);
+        Toml(toml::de::Error);
+        Regex(regex::Error);
+        Json(serde_json::Error);
+        Yaml(serde_yaml::Error);
+        WalkDir(walkdir::Error);
+    }
+
+    errors {
+        /// An entry that couldn't be read at all.
+        EntryUnreadable(path: PathBuf, reason: String) {
+            description("couldn't read entry")
+            display("{}: {}", path.display(), reason)
+        }
+        /// An entry whose frontmatter couldn't be parsed, at `line` of the
+        /// file if known.
+        BadFrontmatter(path: PathBuf, line: Option<usize>, reason: String) {
+            description("bad frontmatter")
+            display("{}{}: bad frontmatter: {}", path.display(),
+                    line.map(|l| format!(":{}", l)).unwrap_or_default(), reason)
+        }
     }
 }
@@ -0,0 +1,58 @@
+//! Reading when and where photos were taken, via `exiftool`.
+//!
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use chrono::NaiveDateTime;
+use serde_json::Value;
+
+use crate::error::Result;
+
+
+const EXIF_DATE_FMT: &str = "%Y:%m:%d %H:%M:%S";
+
+#[derive(Debug)]
+pub struct PhotoMeta {
+    pub path: PathBuf,
+    /// When the photo was taken, in the camera's local time.
+    pub taken: Option<NaiveDateTime>,
+    /// (latitude, longitude) in decimal degrees.
+    pub gps: Option<(f64, f64)>,
+}
+
+/// Read metadata for `paths` with a single `exiftool` run. Photos without
+/// EXIF data are returned with nothing filled in.
+///
+pub fn read(paths: &[&Path]) -> Result<Vec<PhotoMeta>> {
+    if paths.is_empty() {
+        return Ok(Vec::new());
+    }
+    let output = Command::new("exiftool")
+        .args(["-json", "-n", "-DateTimeOriginal", "-CreateDate",
+                "-GPSLatitude", "-GPSLongitude"])
+        .args(paths)
+        .stderr(Stdio::inherit())
+        .output()?;
+    // exiftool exits non-zero if any file lacks the tags, but still reports
+    // the rest, so only give up if it printed nothing.
+    if output.stdout.is_empty() {
+        bail!("exiftool failed: {}", output.status);
+    }
+
+    let records: Vec<Value> = serde_json::from_slice(&output.stdout)?;
+    Ok(records.iter().map(|r| {
+        let taken = ["DateTimeOriginal", "CreateDate"].iter()
+            .filter_map(|k| r[*k].as_str())
+            .filter_map(|s| NaiveDateTime::parse_from_str(s, EXIF_DATE_FMT).ok())
+            .next();
+        let gps = match (r["GPSLatitude"].as_f64(), r["GPSLongitude"].as_f64()) {
+            (Some(lat), Some(lon)) => Some((lat, lon)),
+            _ => None,
+        };
+        PhotoMeta {
+            path: PathBuf::from(r["SourceFile"].as_str().unwrap_or("")),
+            taken,
+            gps,
+        }
+    }).collect())
+}
@@ -0,0 +1,148 @@
+//! Export public entries as a static ActivityPub actor and outbox, so the
+//! journal can be followed from the fediverse once served from `base_url`.
+//!
+//! This only produces the static documents; signing keys, WebFinger, and
+//! an inbox are left to whatever serves them.
+//!
+//! Each entry's object URL is kept in its `activitypub_url` field, so that
+//! it stays the same on later exports even if the entry's slug changes.
+//!
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value};
+
+use crate::error::Result;
+use crate::filedb::Entry;
+use crate::datetime;
+use crate::html;
+use crate::placeholders::Placeholders;
+use crate::transclude::Transcluder;
+use super::slug;
+use super::thumbnails::Thumbnailer;
+
+
+const PUBLIC: &str = "https://www.w3.org/ns/activitystreams#Public";
+const CONTEXT: &str = "https://www.w3.org/ns/activitystreams";
+
+/// The frontmatter field an entry's object URL is kept in.
+pub const URL_FIELD: &str = "activitypub_url";
+
+pub struct Actor<'a> {
+    /// Where `actor.json` and `outbox.json` will be served from, without a
+    /// trailing slash.
+    pub base_url: &'a str,
+    pub username: &'a str,
+}
+
+impl<'a> Actor<'a> {
+    fn id(&self) -> String {
+        format!("{}/actor.json", self.base_url)
+    }
+
+    fn outbox_id(&self) -> String {
+        format!("{}/outbox.json", self.base_url)
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "@context": [CONTEXT],
+            "id": self.id(),
+            "type": "Person",
+            "preferredUsername": self.username,
+            "name": self.username,
+            "inbox": format!("{}/inbox", self.base_url),
+            "outbox": self.outbox_id(),
+        })
+    }
+
+    /// The URL of the object for `e`: the one it was given before, if that
+    /// was under `base_url`, or else one from its slug.
+    ///
+    pub fn object_url(&self, e: &Entry) -> String {
+        let prefix = format!("{}/", self.base_url);
+        match e.frontmatter.get(URL_FIELD) {
+            Some(serde_yaml::Value::String(url)) if url.starts_with(&prefix) => url,
+            _ => format!("{}e/{}", prefix, slug(e)),
+        }
+    }
+
+    /// A `Create` activity wrapping an entry, with its rendered `body`, as an
+    /// `Article`, or None if the entry has no pubdate.
+    ///
+    fn create_activity(&self, e: &Entry, body: &str) -> Option<Value> {
+        let published = e.pubdate()?.to_rfc3339();
+        let url = self.object_url(e);
+        let tags: Vec<Value> = e.get_tags().unwrap_or_default().into_iter()
+            .map(|t| json!({ "type": "Hashtag", "name": format!("#{}", t) }))
+            .collect();
+
+        let mut article = json!({
+            "id": url,
+            "type": "Article",
+            "name": e.title(),
+            "content": html::markdown(body),
+            "published": published,
+            "attributedTo": self.id(),
+            "url": url,
+            "to": [PUBLIC],
+            "tag": tags,
+        });
+        if let Some(summary) = e.summary() {
+            article["summary"] = json!(summary);
+        }
+
+        Some(json!({
+            "id": format!("{}/activity", url),
+            "type": "Create",
+            "actor": self.id(),
+            "published": published,
+            "to": [PUBLIC],
+            "object": article,
+        }))
+    }
+}
+
+/// Write `actor.json` and `outbox.json` for `entries` (which should already
+/// be limited to public ones) into `outdir`, newest activity first. Images
+/// the entries link to are copied into `outdir` too.
+///
+/// Returns the path and object URL of each entry whose URL isn't in its
+/// `URL_FIELD` yet, to be written there.
+///
+pub fn export(
+    actor: &Actor,
+    entries: &[Entry],
+    jrnl_path: &Path,
+    outdir: &Path,
+) -> Result<Vec<(PathBuf, String)>> {
+    let transcluder = Transcluder::new(entries);
+    let placeholders = Placeholders::new(entries, datetime::now().naive_local().date());
+    let thumbnailer = Thumbnailer::new(jrnl_path, outdir, actor.base_url);
+    let mut items: Vec<Value> = Vec::new();
+    let mut new_urls = Vec::new();
+    for e in entries.iter().rev() {
+        let body = placeholders.render(e, &transcluder.expand(e)?);
+        let body = thumbnailer.rewrite(e, &body)?;
+        if let Some(item) = actor.create_activity(e, &body) {
+            let url = actor.object_url(e);
+            if e.frontmatter.get(URL_FIELD) != Some(serde_yaml::Value::String(url.clone())) {
+                new_urls.push((e.path.clone(), url));
+            }
+            items.push(item);
+        }
+    }
+
+    let outbox = json!({
+        "@context": CONTEXT,
+        "id": actor.outbox_id(),
+        "type": "OrderedCollection",
+        "totalItems": items.len(),
+        "orderedItems": items,
+    });
+
+    fs::create_dir_all(outdir)?;
+    fs::write(outdir.join("actor.json"), serde_json::to_string_pretty(&actor.to_json())?)?;
+    fs::write(outdir.join("outbox.json"), serde_json::to_string_pretty(&outbox)?)?;
+    Ok(new_urls)
+}
@@ -0,0 +1,165 @@
+//! Export entries as Markdown: either a file per entry, or with `single`,
+//! one document holding them all, with a table of contents, for feeding to
+//! other tools or printing. Transclusions and placeholders are expanded, as
+//! they are for the other exports.
+//!
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::datetime;
+use crate::error::Result;
+use crate::filedb::{self, Entry};
+use crate::placeholders::Placeholders;
+use crate::transclude::Transcluder;
+use super::slug;
+
+
+/// Write `entries` as one document to `w`: a table of contents linking to
+/// each, then each under its own heading, in the order given. Headings in
+/// entry bodies are pushed down two levels so they nest under it.
+///
+pub fn single<W: Write>(w: &mut W, title: &str, entries: &[Entry]) -> Result<()> {
+    let mut anchors = Anchors::default();
+    anchors.add("Contents");
+    let anchored: Vec<String> = entries.iter().map(|e| anchors.add(e.title())).collect();
+
+    writeln!(w, "# {}\n\n## Contents\n", title)?;
+    for (e, anchor) in entries.iter().zip(anchored.iter()) {
+        writeln!(w, "- [{}](#{}){}", e.title(), anchor, about(e, " - "))?;
+    }
+    let bodies = Bodies::new(entries);
+    for e in entries.iter() {
+        write!(w, "\n\n## {}\n\n", e.title())?;
+        let about = about(e, "");
+        if !about.is_empty() {
+            writeln!(w, "*{}*\n", about)?;
+        }
+        writeln!(w, "{}", demote_headings(without_title(e, &bodies.expand(e)?), 2).trim())?;
+    }
+    Ok(())
+}
+
+/// Write each of `entries` into `outdir` as `<slug>.md`, with its title as
+/// a heading and without its frontmatter. Where slugs are shared, later
+/// entries get `<slug>-2.md` and so on.
+///
+pub fn per_entry(outdir: &Path, entries: &[Entry]) -> Result<()> {
+    fs::create_dir_all(outdir)?;
+    let bodies = Bodies::new(entries);
+    let mut used = HashSet::new();
+    for e in entries.iter() {
+        let name = filedb::unique_id(&slug(e), &used);
+        let mut f = BufWriter::new(File::create(outdir.join(format!("{}.md", name)))?);
+        used.insert(name);
+        write!(f, "# {}\n\n", e.title())?;
+        let about = about(e, "");
+        if !about.is_empty() {
+            write!(f, "*{}*\n\n", about)?;
+        }
+        writeln!(f, "{}", without_title(e, &bodies.expand(e)?).trim())?;
+        f.flush()?;
+    }
+    Ok(())
+}
+
+/// Entry bodies as they're exported, with transclusions (from among the
+/// entries exported) and placeholders expanded.
+///
+struct Bodies<'a> {
+    transcluder: Transcluder<'a>,
+    placeholders: Placeholders<'a>,
+}
+
+impl<'a> Bodies<'a> {
+    fn new(entries: &'a [Entry]) -> Bodies<'a> {
+        Bodies {
+            transcluder: Transcluder::new(entries),
+            placeholders: Placeholders::new(entries, datetime::now().naive_local().date()),
+        }
+    }
+
+    fn expand(&self, e: &Entry) -> Result<String> {
+        Ok(self.placeholders.render(e, &self.transcluder.expand(e)?))
+    }
+}
+
+/// The entry's date and tags, after `prefix`, or nothing if it has neither.
+///
+fn about(e: &Entry, prefix: &str) -> String {
+    let mut parts = Vec::new();
+    if let Some(d) = e.date() {
+        parts.push(d.to_string());
+    }
+    let tags: Vec<String> = e.get_tags().unwrap_or_default().iter().map(|t| format!("#{}", t)).collect();
+    if !tags.is_empty() {
+        parts.push(tags.join(" "));
+    }
+    match parts.is_empty() {
+        true => String::new(),
+        false => format!("{}{}", prefix, parts.join(", ")),
+    }
+}
+
+/// `body`, the body of `e`, less a leading `# heading` if that's where the
+/// title came from, since the title's shown already.
+///
+fn without_title<'a>(e: &Entry, body: &'a str) -> &'a str {
+    let body = body.trim_start();
+    if e.frontmatter.title.is_none() {
+        let first = body.lines().next().unwrap_or("");
+        if first.starts_with("# ") && first[2..].trim().trim_end_matches('#').trim_end() == e.title() {
+            return &body[first.len()..];
+        }
+    }
+    body
+}
+
+/// Add `by` levels to each ATX heading outside code fences, up to the
+/// deepest Markdown has.
+///
+fn demote_headings(body: &str, by: usize) -> String {
+    let mut out = String::new();
+    let mut in_fence = false;
+    for line in body.lines() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+        let level = line.chars().take_while(|c| *c == '#').count();
+        let is_heading = !in_fence && level > 0 && level <= 6
+            && (line.len() == level || line[level..].starts_with(' '));
+        if is_heading {
+            out.push_str(&"#".repeat((level + by).min(6)));
+            out.push_str(&line[level..]);
+        } else {
+            out.push_str(line);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Anchors for headings, as GitHub, pandoc and most other renderers make
+/// them: lowercased, punctuation dropped, spaces as dashes, and numbered
+/// from `-1` when repeated.
+///
+#[derive(Default)]
+struct Anchors {
+    seen: HashMap<String, usize>,
+}
+
+impl Anchors {
+    fn add(&mut self, heading: &str) -> String {
+        let base: String = heading.trim().to_lowercase().chars()
+            .filter(|c| c.is_alphanumeric() || *c == ' ' || *c == '-' || *c == '_')
+            .map(|c| if c == ' ' { '-' } else { c })
+            .collect();
+        let n = self.seen.entry(base.clone()).or_insert(0);
+        *n += 1;
+        match *n {
+            1 => base,
+            n => format!("{}-{}", base, n - 1),
+        }
+    }
+}
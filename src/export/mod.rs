@@ -0,0 +1,42 @@
+//! Getting entries out of the journal and in front of other people.
+//!
+//! Every exporter (and `serve`) goes through `publishable`, so that the
+//! visibility rules in `scope` are enforced the same way everywhere.
+//!
+use crate::filedb::Entry;
+use crate::scope::{Scope, Visibility};
+
+pub mod activitypub;
+pub mod markdown;
+pub mod site;
+pub mod thumbnails;
+
+
+/// Those of `entries` that `scope` allows `audience` to see, oldest first.
+///
+pub fn publishable(entries: Vec<Entry>, scope: &Scope, audience: Visibility) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = entries.into_iter()
+        .filter(|e| scope.allows(e, audience))
+        .collect();
+    entries.sort_by(|a, b| a.pubdate().cmp(&b.pubdate()).then(a.path.cmp(&b.path)));
+    entries
+}
+
+/// Whether `e` has been through `jrni publish`, which stamps it with a
+/// `published:` date.
+///
+pub fn is_published(e: &Entry) -> bool {
+    e.frontmatter.get("published").is_some_and(|v| !v.is_null())
+}
+
+/// A URL-friendly name for an entry: its id, or failing that its filename
+/// without the extension.
+///
+pub fn slug(e: &Entry) -> String {
+    match e.get_id() {
+        Some(id) => id.to_owned(),
+        None => e.path.file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+    }
+}
@@ -0,0 +1,111 @@
+//! Export entries as a static HTML site, browsable without a server:
+//!
+//! - `index.html`, every entry newest first,
+//! - `tags/<tag>.html`, the entries with each tag, and
+//! - `e/<slug>.html`, an entry each.
+//!
+//! Every link is relative, so the output can be opened straight from disk or
+//! served from any path.
+//!
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::datetime;
+use crate::error::Result;
+use crate::filedb::Entry;
+use crate::html;
+use crate::placeholders::Placeholders;
+use crate::transclude::Transcluder;
+use super::slug;
+use super::thumbnails::Thumbnailer;
+
+
+/// Write the site for `entries` (which should already be limited to those
+/// its audience may see) into `outdir`, along with the images they link to.
+///
+pub fn export(entries: &[Entry], jrnl_path: &Path, outdir: &Path) -> Result<()> {
+    let transcluder = Transcluder::new(entries);
+    let placeholders = Placeholders::new(entries, datetime::now().naive_local().date());
+    // Entry pages are a directory down from the root of the site.
+    let thumbnailer = Thumbnailer::new(jrnl_path, outdir, "..");
+
+    fs::create_dir_all(outdir.join("e"))?;
+    fs::create_dir_all(outdir.join("tags"))?;
+
+    let mut by_tag: BTreeMap<&str, Vec<&Entry>> = BTreeMap::new();
+    for e in entries.iter() {
+        let body = placeholders.render(e, &transcluder.expand(e)?);
+        let body = thumbnailer.rewrite(e, &body)?;
+        let title = e.title();
+        let path = outdir.join("e").join(format!("{}.html", file_name(&slug(e))));
+        // Written as it's rendered, since some entries are huge.
+        let mut page = BufWriter::new(File::create(path)?);
+        write!(page, "{}<p><a href=\"../index.html\">&larr; all entries</a></p>\n\
+                      <h1>{}</h1>\n<p>{}</p>\n",
+            html::page_start(title, e.summary()), html::escape(title), meta(e))?;
+        html::write_markdown(&mut page, &body)?;
+        write!(page, "\n{}", html::PAGE_END)?;
+        page.flush()?;
+
+        for t in e.get_tags().unwrap_or_default() {
+            by_tag.entry(t).or_default().push(e);
+        }
+    }
+
+    let all: Vec<&Entry> = entries.iter().collect();
+    let tag_links: Vec<String> = by_tag.iter()
+        .map(|(t, tagged)| format!("<a href=\"tags/{}.html\">#{}</a> ({})",
+            file_name(t), html::escape(t), tagged.len()))
+        .collect();
+    fs::write(outdir.join("index.html"), html::page("journal", &format!(
+        "<h1>journal</h1>\n<p>{}</p>\n{}", tag_links.join(" "), listing(&all, ""))))?;
+
+    for (t, tagged) in by_tag.iter() {
+        let title = format!("#{}", t);
+        fs::write(outdir.join("tags").join(format!("{}.html", file_name(t))), html::page(
+            &title, &format!(
+                "<p><a href=\"../index.html\">&larr; all entries</a></p>\n<h1>{}</h1>\n{}",
+                html::escape(&title), listing(tagged, "../"))))?;
+    }
+    Ok(())
+}
+
+/// A list of links to `entries`, newest first, from a page `root` away from
+/// the root of the site.
+///
+fn listing(entries: &[&Entry], root: &str) -> String {
+    let items: Vec<String> = entries.iter().rev()
+        .map(|e| {
+            let date = e.pubdate().map(|d| d.format("%F ").to_string()).unwrap_or_default();
+            format!("<li>{}<a href=\"{}e/{}.html\">{}</a></li>",
+                date, root, file_name(&slug(e)), html::escape(e.title()))
+        })
+        .collect();
+    format!("<ul>\n{}\n</ul>", items.join("\n"))
+}
+
+/// Date and tag links for an entry page.
+///
+fn meta(e: &Entry) -> String {
+    let mut meta = Vec::new();
+    if let Some(d) = e.pubdate() {
+        meta.push(d.format("%F").to_string());
+    }
+    if let Some(a) = e.author() {
+        meta.push(html::escape(a));
+    }
+    for t in e.get_tags().unwrap_or_default() {
+        meta.push(format!("<a href=\"../tags/{}.html\">#{}</a>", file_name(t), html::escape(t)));
+    }
+    meta.join(" ")
+}
+
+/// `name` made safe to use as a file name (tags may contain `/` or `:`).
+///
+fn file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+        .collect()
+}
@@ -0,0 +1,139 @@
+//! Thumbnails for large attached images, so that exported pages stay light.
+//!
+//! Images linked from an entry body are copied into the export alongside it,
+//! named as the asset store would name them (see `assets`), so that their
+//! filenames don't give anything away. Images outside the journal are left
+//! alone. Large ones are shown as a thumbnail linking to the original.
+//! Thumbnails are made with ImageMagick's `convert` and cached under
+//! `.jrni/thumbs/`, keyed by the content hash of the original.
+//!
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use regex::{Captures, Regex};
+
+use crate::activity;
+use crate::assets;
+use crate::error::Result;
+use crate::filedb::{self, Entry};
+
+
+/// Thumbnails are scaled down to fit in this many pixels on each side.
+pub const MAX_SIZE: u32 = 800;
+
+/// Images smaller than this many bytes are used as they are.
+const MIN_BYTES: u64 = 256 * 1024;
+
+pub struct Thumbnailer<'a> {
+    jrnl_path: &'a Path,
+    outdir: &'a Path,
+    /// Prefix for links in the rewritten body, e.g. the export's base URL.
+    base_url: &'a str,
+    pattern: Regex,
+}
+
+impl<'a> Thumbnailer<'a> {
+    pub fn new(jrnl_path: &'a Path, outdir: &'a Path, base_url: &'a str) -> Thumbnailer<'a> {
+        Thumbnailer {
+            jrnl_path,
+            outdir,
+            base_url,
+            pattern: Regex::new(r"!\[([^\]]*)\]\(([^)\s]+)\)").unwrap(),
+        }
+    }
+
+    /// Copy the local images `body` (belonging to `e`) links to into the
+    /// export, thumbnailing large ones, and return the body with its image
+    /// links pointing at the exported copies.
+    ///
+    pub fn rewrite(&self, e: &Entry, body: &str) -> Result<String> {
+        let mut err = None;
+        let rewritten = self.pattern.replace_all(body, |caps: &Captures| {
+            match self.export_image(e, &caps[1], &caps[2]) {
+                Ok(Some(link)) => link,
+                Ok(None) => caps[0].to_owned(),
+                Err(image_err) => {
+                    err.get_or_insert(image_err);
+                    caps[0].to_owned()
+                },
+            }
+        }).into_owned();
+
+        match err {
+            Some(e) => Err(e),
+            None => Ok(rewritten),
+        }
+    }
+
+    /// The Markdown to replace an image link with, or None if it isn't a
+    /// local image.
+    ///
+    fn export_image(&self, e: &Entry, alt: &str, target: &str) -> Result<Option<String>> {
+        if target.contains("://") || target.starts_with('/') || !assets::is_image(target) {
+            return Ok(None);
+        }
+        let original = match e.resolve_link(self.jrnl_path, target) {
+            Some(p) => p,
+            None => return Ok(None),
+        };
+        // Resolved, `..` and symlinks and all, so that nothing outside the
+        // journal is exported, nor written outside `outdir`.
+        let original = original.canonicalize()?;
+        let rel = match original.strip_prefix(self.jrnl_path.canonicalize()?) {
+            Ok(rel) if !rel.components().all(|c| matches!(c, Component::Normal(_))) =>
+                return Ok(None),
+            Ok(rel) if assets::is_asset(rel) => rel.to_owned(),
+            Ok(rel) => assets::stored_path(&filedb::digest(&fs::read(&original)?), rel),
+            Err(_) => return Ok(None),
+        };
+
+        copy_into(&original, &self.outdir.join(&rel))?;
+        let original_url = format!("{}/{}", self.base_url, url_path(&rel));
+        if fs::metadata(&original)?.len() < MIN_BYTES {
+            return Ok(Some(format!("![{}]({})", alt, original_url)));
+        }
+
+        let thumb = self.thumbnail(&original)?;
+        let thumb_rel = Path::new("thumbs").join(thumb.file_name().unwrap());
+        copy_into(&thumb, &self.outdir.join(&thumb_rel))?;
+        Ok(Some(format!("[![{}]({}/{})]({})",
+            alt, self.base_url, url_path(&thumb_rel), original_url)))
+    }
+
+    /// The cached thumbnail for `original`, making it if need be.
+    ///
+    fn thumbnail(&self, original: &Path) -> Result<PathBuf> {
+        let hash = filedb::digest(&fs::read(original)?);
+        let ext = original.extension().and_then(|e| e.to_str()).unwrap_or("jpg");
+        let cached = activity::state_dir(self.jrnl_path).join("thumbs")
+            .join(format!("{}-{}.{}", hash, MAX_SIZE, ext.to_lowercase()));
+        if cached.exists() {
+            return Ok(cached);
+        }
+
+        fs::create_dir_all(cached.parent().unwrap())?;
+        let status = Command::new("convert")
+            .arg(original)
+            .arg("-thumbnail")
+            .arg(format!("{}x{}>", MAX_SIZE, MAX_SIZE))
+            .arg(&cached)
+            .stderr(Stdio::inherit())
+            .status()?;
+        if !status.success() {
+            let _ = fs::remove_file(&cached);
+            bail!("convert failed to thumbnail {}: {}", original.display(), status);
+        }
+        Ok(cached)
+    }
+}
+
+fn copy_into(from: &Path, to: &Path) -> Result<()> {
+    fs::create_dir_all(to.parent().unwrap())?;
+    fs::copy(from, to)?;
+    Ok(())
+}
+
+fn url_path(rel: &Path) -> String {
+    rel.to_string_lossy().replace('\\', "/")
+}
@@ -4,40 +4,217 @@
 /// the date of the post, associated tags, and a title.
 ///
 /// There is an overengineered parallelized mechanism for walking the journal
-/// contents, `walk_journal`.
+/// contents, `walk_journal`, and an index of parsed entries in `.jrni/index`
+/// so that unchanged files needn't be parsed again, `load_entries`.
 ///
-use std::sync::mpsc::channel;
+use std::sync::mpsc::{sync_channel, Receiver};
 use std::mem::drop;
 use std::path::{PathBuf, Path};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
 use std::fs;
-use std::collections::HashMap;
-use std::io::{self, BufRead};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use threadpool::ThreadPool;
 use walkdir::{WalkDir, DirEntry};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_yaml::Value as YValue;
+use chrono::{DateTime, FixedOffset, NaiveDate, TimeZone};
+use chrono::format::{Item, StrftimeItems};
+use pulldown_cmark::{Event, Parser, Tag};
+use regex::Regex;
+use sha2::{Digest, Sha256};
 
-use crate::error::Result;
+use crate::activity;
+use crate::crypt;
+use crate::datetime;
+use crate::error::{Error, ErrorKind, Result};
+use crate::tags;
 
 
+/// Reading speed assumed by `Entry::reading_time`.
+pub const WORDS_PER_MINUTE: usize = 200;
+
+/// The UTF-8 byte order mark some Windows editors start files with.
+const BOM: char = '\u{feff}';
+
+/// A Markdown task list item in an entry body, e.g. `- [x] call mom`.
+///
+#[derive(Debug)]
+pub struct Task<'a> {
+    pub done: bool,
+    pub text: &'a str,
+}
+
+/// A Markdown ATX heading in an entry body, e.g. `## Ideas`.
+///
+#[derive(Debug)]
+pub struct Heading<'a> {
+    pub level: usize,
+    pub title: &'a str,
+
+    /// Line number within the entry's file, starting at 1.
+    pub line: usize,
+}
+
+/// An entry's frontmatter. The fields jrni relies on are typed; anything
+/// else is kept in `extra`.
+///
+/// Deserializing is lenient: a field of the wrong shape (a tag that's a
+/// number, an unparseable pubdate) is coerced or dropped rather than making
+/// the whole frontmatter fail to load.
+///
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Frontmatter {
+    /// Written either as a list or as a comma-separated string.
+    #[serde(default, deserialize_with = "de_tags")]
+    pub tags: Vec<String>,
+    #[serde(default, deserialize_with = "de_string", skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, deserialize_with = "de_pubdate", serialize_with = "ser_pubdate",
+            skip_serializing_if = "Option::is_none")]
+    pub pubdate: Option<DateTime<FixedOffset>>,
+    #[serde(default, deserialize_with = "de_string", skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, YValue>,
+}
+
+impl Frontmatter {
+    /// Any field, typed or not, as a YAML value.
+    ///
+    pub fn get(&self, key: &str) -> Option<YValue> {
+        match key {
+            "tags" => Some(YValue::Sequence(
+                self.tags.iter().map(|t| YValue::String(t.clone())).collect())),
+            "id" => self.id.clone().map(YValue::String),
+            "pubdate" => self.pubdate.map(|d| YValue::String(datetime::to_str(d))),
+            "title" => self.title.clone().map(YValue::String),
+            _ => self.extra.get(key).cloned(),
+        }
+    }
+
+    /// Names of all fields present, sorted.
+    ///
+    pub fn keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self.extra.keys().cloned().collect();
+        keys.push("tags".to_owned());
+        if self.id.is_some() {
+            keys.push("id".to_owned());
+        }
+        if self.pubdate.is_some() {
+            keys.push("pubdate".to_owned());
+        }
+        if self.title.is_some() {
+            keys.push("title".to_owned());
+        }
+        keys.sort();
+        keys
+    }
+}
+
+fn scalar_string(v: &YValue) -> Option<String> {
+    match v {
+        YValue::String(s) => Some(s.clone()),
+        YValue::Number(n) => Some(n.to_string()),
+        YValue::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn de_tags<'de, D: Deserializer<'de>>(d: D) -> std::result::Result<Vec<String>, D::Error> {
+    Ok(match YValue::deserialize(d)? {
+        YValue::String(s) => tags::parse(&s),
+        YValue::Sequence(items) => tags::normalize_tags(items.iter().filter_map(scalar_string)),
+        // TODO log bad tags
+        _ => Vec::new(),
+    })
+}
+
+/// A non-empty scalar as a string; anything else is None.
+///
+fn de_string<'de, D: Deserializer<'de>>(d: D) -> std::result::Result<Option<String>, D::Error> {
+    Ok(scalar_string(&YValue::deserialize(d)?).filter(|s| !s.is_empty()))
+}
+
+fn de_pubdate<'de, D: Deserializer<'de>>(
+    d: D,
+) -> std::result::Result<Option<DateTime<FixedOffset>>, D::Error> {
+    Ok(match YValue::deserialize(d)? {
+        YValue::String(s) => datetime::from_str(s).ok(),
+        _ => None,
+    })
+}
+
+/// The fields of `raw` that were dropped in deserializing `fm` as unusable,
+/// as (field, reason).
+///
+fn unusable_fields(raw: &YValue, fm: &Frontmatter) -> Vec<(String, String)> {
+    let given = |key: &str| raw.get(key).filter(|v| !v.is_null());
+    let mut bad = Vec::new();
+    if let (Some(v), None) = (given("id"), &fm.id) {
+        bad.push(("id".to_owned(), format!("id '{}' isn't a usable id", inline_value(v))));
+    }
+    if let (Some(v), None) = (given("pubdate"), fm.pubdate) {
+        bad.push(("pubdate".to_owned(), format!(
+            "pubdate '{}' isn't a date like 2024-01-02 09:00:00.000 +0000", inline_value(v))));
+    }
+    bad
+}
+
+fn ser_pubdate<S: Serializer>(
+    pubdate: &Option<DateTime<FixedOffset>>,
+    s: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    match pubdate {
+        Some(d) => s.serialize_str(&datetime::to_str(*d)),
+        None => s.serialize_none(),
+    }
+}
+
 #[derive(Debug)]
 pub struct Entry {
     pub path: PathBuf,
-    pub file_metadata: fs::Metadata,
-    pub frontmatter: HashMap<String, YValue>,
+    /// None for entries that aren't on disk; see `storage`.
+    pub file_metadata: Option<fs::Metadata>,
+    pub frontmatter: Frontmatter,
 
     /// If an error was encountered while trying to decode frontmatter, 
     /// attach it here.
     pub frontmatter_err: Option<serde_yaml::Error>,
+    /// Fields jrni relies on that are there but can't be used, like a
+    /// pubdate that isn't a date, as (field, reason). The entry loads as if
+    /// they weren't there.
+    pub bad_fields: Vec<(String, String)>,
     pub body: String,
+
+    /// The line number in the file at which `body` starts, starting at 1.
+    pub body_line: usize,
+
+    /// The body is encrypted (see `crypt`), so `body` is left empty.
+    pub is_encrypted: bool,
 }
 
 impl Entry {
     pub fn from_path(p: &Path) -> Result<Entry> {
-        let pathstr = p.to_str().unwrap();
-        let mut fm = HashMap::new();
+        let mut entry = Entry::parse(p, &fs::read_to_string(p)?);
+        entry.file_metadata = Some(fs::metadata(p)?);
+        Ok(entry)
+    }
+
+    /// Parse the contents `raw` of the entry at `p`. This never fails (nor
+    /// panics, whatever `raw` holds): frontmatter that can't be parsed is
+    /// reported in `frontmatter_err`.
+    ///
+    pub fn parse(p: &Path, raw: &str) -> Entry {
+        let raw = raw.trim_start_matches(BOM);
+        let opening = has_opening_delimiter(raw);
+        let mut fm = Frontmatter::default();
         let mut fm_err = None;
+        let mut bad_fields = Vec::new();
         let mut all_lines: Vec<String> = Vec::new();
         let mut rawfrontmatter: Vec<String> = Vec::new();
         let mut body: Vec<String> = Vec::new();
@@ -45,13 +222,14 @@ impl Entry {
         let mut frontmatter_end_idx = -1;
         let mut idx = 0;
 
-        let f = io::BufReader::new(fs::File::open(&pathstr)?);
-
-        for line in f.lines() {
-            let line = line?;
+        for line in raw.lines() {
+            let line = line.to_owned();
             all_lines.push(line.clone());
             idx += 1;
 
+            if opening && idx == 1 {
+                continue;
+            }
             if frontmatter_end_idx == -1 && line.trim() == "---" {
                 into = &mut body;
                 frontmatter_end_idx = idx;
@@ -60,105 +238,771 @@ impl Entry {
             }
         }
 
-        if frontmatter_end_idx != -1 {
-            let yaml_result = serde_yaml::from_str(&rawfrontmatter.join("\n"));
+        // Empty frontmatter is no frontmatter, rather than bad YAML.
+        let empty = rawfrontmatter.iter().all(|l| l.trim().is_empty());
+        if frontmatter_end_idx != -1 && !empty {
+            let yaml_result = serde_yaml::from_str::<YValue>(&rawfrontmatter.join("\n"))
+                .and_then(|v| Ok((serde_yaml::from_value::<Frontmatter>(v.clone())?, v)));
             match yaml_result {
                 Err(yaml_err) => fm_err = Some(yaml_err),
-                Ok(res) => fm = res,
+                Ok((res, v)) => {
+                    bad_fields = unusable_fields(&v, &res);
+                    fm = res;
+                },
             }
         } 
             
-        if let Some(_) = fm_err {
+        if fm_err.is_some() {
             body = all_lines;
         }
 
         // In case no frontmatter is attached.
-        if body.len() == 0 {
+        if frontmatter_end_idx == -1 {
             body = rawfrontmatter;
         }
 
-        fm.insert("tags".to_owned(), normalize_tags(fm.get("tags")));
+        let body = body.join("\n");
+        let is_encrypted = fm_err.is_none() && crypt::is_encrypted(&body);
+        let body_line = match (frontmatter_end_idx, &fm_err) {
+            (-1, _) | (_, Some(_)) => 1,
+            (end, None) => end as usize + 1,
+        };
 
-        Ok(Entry {
+        Entry {
             path: p.to_owned(),
-            file_metadata: fs::metadata(pathstr)?,
+            file_metadata: None,
             frontmatter: fm,
             frontmatter_err: fm_err,
-            body: body.join("\n"),
-
-        })
+            bad_fields,
+            body: if is_encrypted { String::new() } else { body },
+            body_line,
+            is_encrypted,
+        }
     }
 
     pub fn get_tags(&self) -> Option<Vec<&str>> {
-        if !self.frontmatter.contains_key("tags") {
-            return None;
+        Some(self.frontmatter.tags.iter().map(|t| t.as_str()).collect())
+    }
+
+    pub fn get_id(&self) -> Option<&str> {
+        self.frontmatter.id.as_deref()
+    }
+
+    pub fn author(&self) -> Option<&str> {
+        let author = self.frontmatter.extra.get("author")?.as_str()?;
+        match author.len() { 0 => None, _ => Some(author) }
+    }
+
+    /// A short description of the entry, from its `summary` field.
+    ///
+    pub fn summary(&self) -> Option<&str> {
+        let summary = self.frontmatter.extra.get("summary")?.as_str()?.trim();
+        match summary.len() { 0 => None, _ => Some(summary) }
+    }
+
+    /// What to call the entry: its `title:`, or failing that the first
+    /// `# heading` in its body, or failing that its filename without the
+    /// extension.
+    ///
+    pub fn title(&self) -> &str {
+        if let Some(ref title) = self.frontmatter.title {
+            return title;
         }
+        match self.headings().into_iter().find(|h| h.level == 1 && !h.title.is_empty()) {
+            Some(h) => h.title,
+            None => self.path.file_stem().and_then(|s| s.to_str()).unwrap_or(""),
+        }
+    }
 
-        let mut tags = Vec::new();
+    pub fn pubdate(&self) -> Option<DateTime<FixedOffset>> {
+        self.frontmatter.pubdate
+    }
+
+    /// When the entry's file was last modified, if known.
+    ///
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.file_metadata.as_ref()?.modified().ok()
+    }
 
-        for t in self.frontmatter.get("tags").unwrap().as_sequence().unwrap() {
-            tags.push(t.as_str().unwrap());
+    /// When the entry's file was created, where the filesystem records it.
+    ///
+    pub fn created(&self) -> Option<SystemTime> {
+        self.file_metadata.as_ref()?.created().ok()
+    }
+
+    /// The day the entry is for: its pubdate, or failing that the date its
+    /// filename starts with, e.g. `2024-05-10-standup.md`.
+    ///
+    pub fn date(&self) -> Option<NaiveDate> {
+        self.pubdate().map(|d| d.naive_local().date()).or_else(|| self.filename_date())
+    }
+
+    /// The date the entry's filename starts with, if it does.
+    ///
+    pub fn filename_date(&self) -> Option<NaiveDate> {
+        let name = self.path.file_name()?.to_str()?;
+        NaiveDate::parse_from_str(name.get(..10)?, "%Y-%m-%d").ok()
+    }
+
+    /// When this entry's temporary tags lapse, per an `expires:` field or an
+    /// `until:YYYY-MM-DD` tag. If there are several, the earliest wins.
+    ///
+    pub fn expires(&self) -> Option<NaiveDate> {
+        let field = self.frontmatter.extra.get("expires").and_then(|v| v.as_str());
+        let tags = self.get_tags().unwrap_or_default();
+        let untils = tags.into_iter().filter_map(|t| t.strip_prefix("until:"));
+
+        field.into_iter()
+            .chain(untils)
+            .filter_map(|d| NaiveDate::parse_from_str(d.trim(), "%Y-%m-%d").ok())
+            .min()
+    }
+
+    /// The number of words in the body as it reads once rendered: Markdown
+    /// syntax, HTML and the contents of code blocks aren't counted.
+    ///
+    pub fn word_count(&self) -> usize {
+        let mut text = String::new();
+        let mut in_code_block = false;
+        for event in Parser::new(&self.body) {
+            match event {
+                Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+                Event::End(Tag::CodeBlock(_)) => in_code_block = false,
+                Event::Text(t) | Event::Code(t) if !in_code_block => text.push_str(&t),
+                Event::SoftBreak | Event::HardBreak => text.push(' '),
+                // Inline markup doesn't split words, e.g. "un*believ*able".
+                Event::End(Tag::Emphasis) | Event::End(Tag::Strong)
+                    | Event::End(Tag::Strikethrough) | Event::End(Tag::Link(..)) => (),
+                Event::End(_) => text.push(' '),
+                _ => (),
+            }
         }
+        text.split_whitespace().count()
+    }
 
-        Some(tags)
+    /// Minutes it takes to read the body, at `WORDS_PER_MINUTE`, rounded up.
+    ///
+    pub fn reading_time(&self) -> usize {
+        self.word_count().div_ceil(WORDS_PER_MINUTE)
     }
 
-    pub fn get_id(&self) -> Option<&str> {
-        if !self.frontmatter.contains_key("id") {
-            return None;
+    /// The day a `remind:` field asks for this entry to be brought back up,
+    /// unless it's since been dismissed with a `reminded:` date on or after
+    /// it.
+    ///
+    pub fn reminder(&self) -> Option<NaiveDate> {
+        let date = |key| self.frontmatter.extra.get(key)
+            .and_then(|v| v.as_str())
+            .and_then(|d| NaiveDate::parse_from_str(d.trim(), "%Y-%m-%d").ok());
+        let remind = date("remind")?;
+        match date("reminded") {
+            Some(reminded) if reminded >= remind => None,
+            _ => Some(remind),
+        }
+    }
+
+    /// Habits recorded in this entry, as (habit, done) pairs.
+    ///
+    /// Habits come from a `habits:` frontmatter map (`exercise: yes`), or
+    /// `habit: exercise=yes, meditate=no` lines in either the frontmatter or
+    /// the body.
+    ///
+    pub fn habits(&self) -> Vec<(String, bool)> {
+        let mut habits = Vec::new();
+
+        if let Some(YValue::Mapping(m)) = self.frontmatter.extra.get("habits") {
+            for (k, v) in m.iter() {
+                let done = match v {
+                    YValue::Bool(b) => *b,
+                    YValue::String(s) => is_truthy(s),
+                    YValue::Number(n) => n.as_i64().is_some_and(|n| n != 0),
+                    _ => false,
+                };
+                if let Some(k) = k.as_str() {
+                    habits.push((k.to_owned(), done));
+                }
+            }
         }
-        
-        let id = self.frontmatter.get("id")?.as_str()?;
-        match id.len() { 0 => None, _ => Some(id) }
+
+        let fm_line = self.frontmatter.extra.get("habit").and_then(|h| h.as_str());
+        let body_lines = self.body.lines()
+            .filter_map(|l| l.trim().strip_prefix("habit:"));
+        for line in fm_line.into_iter().chain(body_lines) {
+            for pair in line.split(',') {
+                let mut kv = pair.splitn(2, '=');
+                let name = kv.next().unwrap_or("").trim();
+                if !name.is_empty() {
+                    habits.push((name.to_owned(), kv.next().is_none_or(is_truthy)));
+                }
+            }
+        }
+        habits
+    }
+
+    /// Headings in the body, skipping anything inside fenced code blocks.
+    ///
+    pub fn headings(&self) -> Vec<Heading<'_>> {
+        let mut headings = Vec::new();
+        let mut in_fence = false;
+
+        for (i, line) in self.body.lines().enumerate() {
+            if line.trim_start().starts_with("```") {
+                in_fence = !in_fence;
+            }
+            if in_fence {
+                continue;
+            }
+            let level = line.chars().take_while(|c| *c == '#').count();
+            if level == 0 || level > 6 {
+                continue;
+            }
+            let rest = &line[level..];
+            if !rest.is_empty() && !rest.starts_with(' ') {
+                continue;
+            }
+            headings.push(Heading {
+                level,
+                title: rest.trim().trim_end_matches('#').trim_end(),
+                line: self.body_line + i,
+            });
+        }
+        headings
+    }
+
+    pub fn tasks(&self) -> Vec<Task<'_>> {
+        self.body.lines().filter_map(parse_task).collect()
+    }
+
+    /// How many of this entry's tasks are done, out of how many in total.
+    ///
+    pub fn task_progress(&self) -> (usize, usize) {
+        let tasks = self.tasks();
+        (tasks.iter().filter(|t| t.done).count(), tasks.len())
+    }
+
+    /// Ids this entry links to with wiki-style `[[id]]` (or `[[id|label]]`)
+    /// links, transclusions included, in order and without repeats.
+    ///
+    pub fn wiki_links(&self) -> Vec<&str> {
+        let pattern = Regex::new(r"\[\[([^\[\]|]+)(?:\|[^\[\]]*)?\]\]").unwrap();
+        let mut links: Vec<&str> = Vec::new();
+        for c in pattern.captures_iter(&self.body) {
+            let id = c.get(1).unwrap().as_str().trim();
+            if !id.is_empty() && !links.contains(&id) {
+                links.push(id);
+            }
+        }
+        links
+    }
+
+    /// Targets of the Markdown links and images in the body that aren't
+    /// URLs or anchors, in order.
+    ///
+    pub fn local_links(&self) -> Vec<&str> {
+        let pattern = Regex::new(r"\]\(([^)\s]+)\)").unwrap();
+        pattern.captures_iter(&self.body)
+            .map(|c| c.get(1).unwrap().as_str())
+            .filter(|t| !t.contains("://") && !t.starts_with('#') && !t.starts_with("mailto:"))
+            .collect()
+    }
+
+    /// The file a link in this entry points to, if it exists. Links are
+    /// relative to the entry; failing that, they're taken as relative to
+    /// the journal root, which is how attachments used to be linked.
+    ///
+    pub fn resolve_link(&self, jrnl_path: &Path, target: &str) -> Option<PathBuf> {
+        let target = target.split('#').next().unwrap_or(target);
+        self.path.parent().map(|dir| dir.join(target))
+            .into_iter()
+            .chain(Some(jrnl_path.join(target)))
+            .find(|p| p.is_file())
     }
 }
 
-fn normalize_tags(tags: Option<&YValue>) -> YValue {
-    match tags {
-        Some(val) => match val {
-            YValue::String(v) => {
-                let split: Vec<YValue> = v.split(",").map(
-                    |s| YValue::String(s.trim().to_owned())).collect();
-                YValue::Sequence(split)
-            },
-            YValue::Sequence(_) => val.to_owned(),
-            YValue::Null => YValue::Sequence(Vec::new()),
-            _ => {
-                YValue::Sequence(Vec::new())
-                // TODO log bad tags
-            },
+/// A link to `target` (a path relative to the journal root) from the body
+/// of the entry at `entry_path`.
+///
+pub fn link_from(jrnl_path: &Path, entry_path: &Path, target: &str) -> String {
+    let depth = entry_path.parent()
+        .and_then(|dir| dir.strip_prefix(jrnl_path).ok())
+        .map_or(0, |rel| rel.iter().count());
+    format!("{}{}", "../".repeat(depth), target)
+}
+
+/// A hash of file contents, for noticing changes. This is 64-bit FNV-1a:
+/// unlike std's hasher, it's stable across Rust releases. It's too short to
+/// tell files apart by; see `digest` for that.
+///
+pub fn content_hash(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in bytes {
+        hash ^= u64::from(*b);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+/// The SHA-256 of `bytes` in hex, for naming files by their contents, where
+/// two files getting the same name would lose one of them.
+///
+pub fn digest(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// A frontmatter value on one line, with sequences written like tags are.
+///
+pub fn inline_value(v: &YValue) -> String {
+    match v {
+        YValue::String(s) => s.clone(),
+        YValue::Sequence(items) =>
+            items.iter().map(inline_value).collect::<Vec<String>>().join(", "),
+        other => serde_yaml::to_string(other).unwrap_or_default()
+            .trim_start_matches("---").trim().replace('\n', " "),
+    }
+}
+
+/// Quote a string as a YAML double-quoted scalar, so it can be written into
+/// frontmatter verbatim.
+///
+pub fn yaml_quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Rewrite the tags of the entry at `path` in place, leaving everything else
+/// in the file as it was.
+///
+pub fn rewrite_tags(path: &Path, tags: &[String]) -> Result<()> {
+    let raw = fs::read_to_string(path)?;
+    write_atomic(path, &replace_field(&raw, "tags", &format!("tags: {}", tags.join(", "))))
+}
+
+/// Set a frontmatter field of the entry at `path` to a string, in place.
+/// Setting `tags` takes a comma-separated list.
+///
+pub fn set_field(path: &Path, key: &str, value: &str) -> Result<()> {
+    if key == "tags" {
+        return rewrite_tags(path, &tags::parse(value));
+    }
+    let raw = fs::read_to_string(path)?;
+    write_atomic(path, &replace_field(&raw, key, &format!("{}: {}", key, yaml_quote(value))))
+}
+
+/// Add `text` to the end of the body of the entry at `path`, as a paragraph
+/// of its own, in the file's line endings. The file is rewritten with
+/// `write_atomic`, so it's never left half-written.
+///
+pub fn append_body(path: &Path, text: &str) -> Result<()> {
+    let raw = fs::read_to_string(path)?;
+    let nl = line_ending(&raw);
+    let mut out = raw.trim_end().to_owned();
+    if !out.is_empty() {
+        out.push_str(nl);
+        out.push_str(nl);
+    }
+    for line in text.trim_end().lines() {
+        out.push_str(line);
+        out.push_str(nl);
+    }
+    write_atomic(path, &out)
+}
+
+/// Add `line` to the end of the entry at `path`, in the file's line endings.
+/// It's written in a single append, so it's never left half-written and
+/// lines added at the same time by another jrni aren't lost.
+///
+pub fn append_line(path: &Path, line: &str) -> Result<()> {
+    let raw = fs::read_to_string(path)?;
+    let nl = line_ending(&raw);
+    let mut out = String::new();
+    if !raw.is_empty() && !raw.ends_with('\n') {
+        out.push_str(nl);
+    }
+    out.push_str(line.trim_end());
+    out.push_str(nl);
+
+    let mut f = fs::OpenOptions::new().append(true).open(path)?;
+    f.write_all(out.as_bytes())?;
+    Ok(f.sync_all()?)
+}
+
+/// Set a frontmatter field of the entry at `path` to a number, in place.
+///
+pub fn set_number(path: &Path, key: &str, value: usize) -> Result<()> {
+    let raw = fs::read_to_string(path)?;
+    write_atomic(path, &replace_field(&raw, key, &format!("{}: {}", key, value)))
+}
+
+/// Replace the contents of `path` such that it's never seen half-written,
+/// even if jrni crashes or another jrni is reading it: write a hidden
+/// `.tmp` file alongside it, flush that to disk, then rename it over `path`.
+///
+/// If `path` is a symlink, the file it points to is replaced rather than
+/// the link, and the replacement keeps the original's permissions.
+///
+pub fn write_atomic(path: &Path, contents: &str) -> Result<()> {
+    let path = &fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+    let permissions = fs::metadata(path).ok().map(|m| m.permissions());
+    let tmp = temp_path(path);
+    let written = write_synced(&tmp, contents, permissions)
+        .and_then(|_| fs::rename(&tmp, path));
+    if written.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    written?;
+    sync_parent(path);
+    Ok(())
+}
+
+/// Like `write_atomic`, but for a new file: fails, leaving it alone, if
+/// something's already at `path`, even if another jrni put it there a
+/// moment ago.
+///
+pub fn create_atomic(path: &Path, contents: &str) -> Result<()> {
+    let tmp = temp_path(path);
+    if let Err(e) = write_synced(&tmp, contents, None) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e.into());
+    }
+    // Unlike a rename, a hard link never replaces what's there. Where hard
+    // links aren't supported, fall back to checking first.
+    let created = match fs::hard_link(&tmp, path) {
+        Err(ref e) if e.kind() != io::ErrorKind::AlreadyExists && !path.exists() =>
+            fs::rename(&tmp, path),
+        linked => linked,
+    };
+    let _ = fs::remove_file(&tmp);
+    match created {
+        Ok(()) => {
+            sync_parent(path);
+            Ok(())
         },
-        None => YValue::Sequence(Vec::<YValue>::new()),
+        Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists =>
+            bail!("{} exists already", path.display()),
+        Err(e) => Err(e.into()),
     }
 }
 
+/// Where to write `path`'s contents before they replace it: hidden, so
+/// it's never taken for an entry, and unique to this process.
+///
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()))
+}
+
+/// Write `contents` to a new file at `path` and flush it to disk, with
+/// `permissions` set before anything is written, if given.
+///
+fn write_synced(
+    path: &Path,
+    contents: &str,
+    permissions: Option<fs::Permissions>,
+) -> io::Result<()> {
+    let mut f = fs::File::create(path)?;
+    if let Some(permissions) = permissions {
+        f.set_permissions(permissions)?;
+    }
+    f.write_all(contents.as_bytes())?;
+    f.sync_all()
+}
+
+/// Flush the directory holding `path`, so a rename into it survives a
+/// crash. Not every platform can, so failing is fine.
+///
+fn sync_parent(path: &Path) {
+    #[cfg(unix)]
+    {
+        if let Some(dir) = path.parent() {
+            if let Ok(d) = fs::File::open(dir) {
+                let _ = d.sync_all();
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+/// Replace the `key:` field (including any block-style value under it) in
+/// the frontmatter of `raw` with `field_line`, adding the field if it's
+/// missing and frontmatter if the entry doesn't have any.
+///
+/// Line endings and any byte order mark are kept as they were.
+///
+fn replace_field(raw: &str, key: &str, field_line: &str) -> String {
+    let bom = if raw.starts_with(BOM) { "\u{feff}" } else { "" };
+    let raw = raw.trim_start_matches(BOM);
+    let nl = line_ending(raw);
+    if !raw.lines().any(|l| l.trim() == "---") {
+        return format!("{}{}{}---{}{}{}", bom, field_line, nl, nl, nl, raw);
+    }
+
+    let prefix = format!("{}:", key);
+    let opening = has_opening_delimiter(raw);
+    let mut out: Vec<&str> = Vec::new();
+    let mut in_frontmatter = true;
+    let mut in_field = false;
+    let mut replaced = false;
+
+    for (i, line) in raw.lines().enumerate() {
+        if opening && i == 0 {
+            out.push(line);
+            continue;
+        }
+        if in_frontmatter && in_field {
+            // Only indented lines and `- ` items belong to the value; the
+            // closing `---` never does.
+            let continues = line.starts_with(' ') || line.starts_with('\t')
+                || line.starts_with("- ") || line == "-";
+            if continues && line.trim() != "---" {
+                continue;
+            }
+            in_field = false;
+        }
+        if in_frontmatter && line.trim() == "---" {
+            if !replaced {
+                out.push(field_line);
+            }
+            in_frontmatter = false;
+        } else if in_frontmatter && line.starts_with(&prefix) {
+            out.push(field_line);
+            in_field = true;
+            replaced = true;
+            continue;
+        }
+        out.push(line);
+    }
+
+    let mut rewritten = format!("{}{}", bom, out.join(nl));
+    if raw.ends_with('\n') {
+        rewritten.push_str(nl);
+    }
+    rewritten
+}
+
+/// The line ending used in `raw`: CRLF if it has any (it was likely edited
+/// on Windows), otherwise LF.
+///
+pub fn line_ending(raw: &str) -> &'static str {
+    match raw.contains("\r\n") {
+        true => "\r\n",
+        false => "\n",
+    }
+}
+
+/// Whether frontmatter is opened with a `---` line as well as closed with
+/// one, Jekyll-style, rather than only closed.
+///
+pub(crate) fn has_opening_delimiter(raw: &str) -> bool {
+    let mut delimiters = raw.lines().map(|l| l.trim() == "---");
+    delimiters.next() == Some(true) && delimiters.any(|d| d)
+}
+
+fn is_truthy(s: &str) -> bool {
+    matches!(s.trim().to_lowercase().as_str(), "yes" | "y" | "true" | "1" | "done" | "x")
+}
+
+/// Recognize `- [ ] ...`, `* [x] ...`, `1. [ ] ...` and friends.
+///
+fn parse_task(line: &str) -> Option<Task<'_>> {
+    let line = line.trim_start();
+    let item = if line.starts_with("- ") || line.starts_with("* ")
+            || line.starts_with("+ ") {
+        &line[2..]
+    } else {
+        let digits = line.find(|c: char| !c.is_ascii_digit())?;
+        let after = &line[digits..];
+        if digits == 0 || !(after.starts_with(". ") || after.starts_with(") ")) {
+            return None;
+        }
+        &after[2..]
+    };
+
+    let done = match item.get(..3) {
+        Some("[ ]") => false,
+        Some("[x]") | Some("[X]") => true,
+        _ => return None,
+    };
+    Some(Task { done, text: item[3..].trim() })
+}
+
 /// Ignore paths that don't end in extensions we can make sense of.
 ///
 fn is_jrnl_path(p: &Path) -> bool {
     if p.is_dir() {
         return false;
     }
-    match p.extension() {
-        Some(osstr) =>
-            match osstr.to_str().unwrap() {
-                "md" | "txt" => return true,
-                _ => false
-            }
-        _ => false,
-    }
+    matches!(p.extension().and_then(|e| e.to_str()), Some("md") | Some("txt"))
+}
+
+/// Hidden files and directories (`.templates/`, `.git/`, ...) are never part
+/// of the journal proper.
+///
+fn is_hidden(e: &DirEntry) -> bool {
+    e.depth() > 0 && e.file_name().to_str().is_some_and(|n| n.starts_with('.'))
+}
+
+/// If `p` is a copy left behind by a sync tool after a conflict, the path of
+/// the file it conflicts with.
+///
+/// Recognizes Dropbox's `name (conflicted copy 2024-01-01).md` (and the
+/// `(Jo's conflicted copy ...)` variant) and Syncthing's
+/// `name.sync-conflict-20240101-120000-ABCDEFG.md`.
+///
+pub fn conflict_original(p: &Path) -> Option<PathBuf> {
+    let stem = p.file_stem()?.to_str()?;
+    let original = if let Some(idx) = stem.find(".sync-conflict-") {
+        &stem[..idx]
+    } else {
+        let idx = stem.rfind(" (")?;
+        if !stem.ends_with(')') || !stem[idx..].contains("conflicted copy") {
+            return None;
+        }
+        &stem[..idx]
+    };
+
+    let name = match p.extension().and_then(|e| e.to_str()) {
+        Some(ext) => format!("{}.{}", original, ext),
+        None => original.to_owned(),
+    };
+    Some(p.with_file_name(name))
+}
+
+/// Every sync conflict copy in the journal, paired with the entry it
+/// conflicts with.
+///
+/// Parts of the journal that can't be walked are skipped here; loading
+/// entries reports them (see `load_entries_checked`).
+///
+pub fn sync_conflicts(jrnl_path: &str) -> Vec<(PathBuf, PathBuf)> {
+    let mut conflicts: Vec<(PathBuf, PathBuf)> = get_all_jrnl_walker(jrnl_path)
+        .filter_map(|e| e.ok())
+        .filter_map(|e| conflict_original(e.path()).map(|o| (e.path().to_owned(), o)))
+        .collect();
+    conflicts.sort();
+    conflicts
 }
 
-fn get_jrnl_walker(jrnl_path: &str) -> Box<Iterator<Item = DirEntry>> {
+/// Like `get_jrnl_walker`, but including sync conflict copies.
+///
+fn get_all_jrnl_walker(jrnl_path: &str) -> Box<dyn Iterator<Item = Result<DirEntry>> + Send> {
     Box::new(WalkDir::new(jrnl_path)
         .follow_links(true)
+        // In the same order every time, so that the first match is too.
+        .sort_by(|a, b| a.file_name().cmp(b.file_name()))
         .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| is_jrnl_path(e.path())))
+        .filter_entry(|e| !is_hidden(e))
+        .map(|e| e.map_err(walk_error))
+        .filter(|e| e.as_ref().map_or(true, |e| is_jrnl_path(e.path()))))
+}
+
+/// Every entry file in the journal, and an error for each file or
+/// directory that couldn't be walked.
+///
+fn get_jrnl_walker(jrnl_path: &str) -> Box<dyn Iterator<Item = Result<DirEntry>> + Send> {
+    Box::new(get_all_jrnl_walker(jrnl_path)
+        .filter(|e| e.as_ref().map_or(true, |e| conflict_original(e.path()).is_none())))
+}
+
+fn walk_error(e: walkdir::Error) -> Error {
+    let path = e.path().map(Path::to_owned).unwrap_or_default();
+    let reason = match e.io_error() {
+        Some(io) => io.to_string(),
+        None => e.to_string(),
+    };
+    ErrorKind::EntryUnreadable(path, reason).into()
+}
+
+/// The paths of `walker`'s entries, for `map_parallel_iter`.
+///
+fn walked_paths<I>(walker: I) -> impl Iterator<Item = Result<PathBuf>> + Send
+    where I: Iterator<Item = Result<DirEntry>> + Send
+{
+    walker.map(|e| e.map(|e| e.path().to_owned()))
+}
+
+/// How new entries are named when the config doesn't say.
+pub const DEFAULT_FILENAME: &str = "{date:%F}-{slug}.md";
+
+/// Where, relative to the journal, a new entry named `slug` and written at
+/// `when` goes, per `format`: a path in which `{slug}` is replaced with
+/// `slug`, `{date}` with the day (e.g. 2024-05-10), and `{date:FMT}` with
+/// `when` in a strftime format, e.g. `{date:%Y/%m}/{slug}.md`.
+///
+pub fn entry_filename<Tz: TimeZone>(format: &str, slug: &str, when: &DateTime<Tz>) -> Result<PathBuf>
+    where Tz::Offset: std::fmt::Display
+{
+    if !format.contains("{slug}") {
+        bail!("filename format '{}' needs a {{slug}}, or entries would share names", format);
+    }
+    let mut out = String::new();
+    let mut rest = format;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => bail!("unclosed {{ in filename format '{}'", format),
+        };
+        let placeholder = &rest[start + 1..end];
+        match placeholder.splitn(2, ':').collect::<Vec<_>>().as_slice() {
+            ["slug"] => out.push_str(slug),
+            ["date"] => out.push_str(&when.format("%F").to_string()),
+            ["date", fmt] => {
+                if StrftimeItems::new(fmt).any(|i| i == Item::Error) {
+                    bail!("bad date format '{}' in filename format '{}'", fmt, format);
+                }
+                out.push_str(&when.format(fmt).to_string());
+            },
+            _ => bail!("unknown {{{}}} in filename format '{}'; use {{slug}}, {{date}} or {{date:FMT}}",
+                       placeholder, format),
+        }
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+
+    let path = PathBuf::from(out);
+    let inside = path.components().all(|c| matches!(c, std::path::Component::Normal(_)));
+    if !inside {
+        bail!("filename format '{}' has to give a path inside the journal", format);
+    }
+    Ok(path)
 }
 
+/// An id based on `base` that isn't among the `taken` ones: `base` itself
+/// if it's free, otherwise `base-2`, `base-3`, ... See `ids` for the ids in
+/// a journal, or `Journal::unique_id`.
+///
+pub fn unique_id(base: &str, taken: &HashSet<String>) -> String {
+    if !taken.contains(base) {
+        return base.to_owned();
+    }
+    (2..).map(|n| format!("{}-{}", base, n))
+        .find(|id| !taken.contains(id))
+        .unwrap()
+}
+
+/// The ids of every entry in the journal. Fails if any file can't be read,
+/// since its id might be taken.
+///
+pub fn ids(jrnl_path: &Path) -> Result<HashSet<String>> {
+    Ok(load_all_entries(jrnl_path)?.iter()
+        .filter_map(|e| e.get_id().map(String::from))
+        .collect())
+}
+
+/// Paths of every entry in the journal, and an error for each file or
+/// directory that couldn't be read.
+///
+pub fn entry_paths(jrnl_path: &Path) -> (Vec<PathBuf>, Vec<Error>) {
+    let mut errors = Vec::new();
+    let paths = walked_paths(get_jrnl_walker(jrnl_path.to_str().unwrap()))
+        .filter_map(|p| p.map_err(|e| errors.push(e)).ok())
+        .collect();
+    (paths, errors)
+}
 
 /// For each entry in the journal, perform some action per `path_fn` and
-/// return a vector of the results. 
+/// return a vector of the results, with an error among them for any part
+/// of the journal that couldn't be walked.
 ///
 /// This happens in parallel using a threadpool.
 ///
@@ -166,20 +1010,397 @@ pub fn walk_journal<T, F>(jrnl_path: &str, path_fn: F) -> Vec<Result<T>>
     where F : Fn(PathBuf) -> Result<T> + Send + Sync + 'static, 
         T : Send + 'static
 {
-    let pool = ThreadPool::new(num_cpus::get());
-    let (tx, rx) = channel();
+    map_parallel_iter(walked_paths(get_jrnl_walker(jrnl_path)), path_fn).collect()
+}
+
+/// Like `walk_journal`, but results are yielded as the walk goes, rather
+/// than once the whole journal has been walked.
+///
+/// Dropping the iterator stops the walk, so a search can stop at the first
+/// match without reading the rest of the journal.
+///
+pub fn walk_journal_iter<T, F>(jrnl_path: &str, path_fn: F) -> impl Iterator<Item = Result<T>>
+    where F : Fn(PathBuf) -> Result<T> + Send + Sync + 'static,
+        T : Send + 'static
+{
+    map_parallel_iter(walked_paths(get_jrnl_walker(jrnl_path)), path_fn)
+}
+
+/// Apply `path_fn` to `paths` on a threadpool, collecting the results in
+/// the order of `paths`.
+///
+pub fn map_parallel<T, F, I>(paths: I, path_fn: F) -> Vec<Result<T>>
+    where F : Fn(PathBuf) -> Result<T> + Send + Sync + 'static,
+        T : Send + 'static,
+        I : IntoIterator<Item = PathBuf>,
+        I::IntoIter : Send + 'static
+{
+    map_parallel_iter(paths.into_iter().map(Ok), path_fn).collect()
+}
+
+/// Apply `path_fn` to `paths` on a threadpool, yielding the results in the
+/// order of `paths`, errors among them passed through as they are.
+/// `paths` is consumed from another thread, only so far ahead of the
+/// results being taken; once the iterator is dropped, paths not yet
+/// started are skipped and no more are taken.
+///
+fn map_parallel_iter<T, F, I>(paths: I, path_fn: F) -> impl Iterator<Item = Result<T>>
+    where F : Fn(PathBuf) -> Result<T> + Send + Sync + 'static,
+        T : Send + 'static,
+        I : IntoIterator<Item = Result<PathBuf>>,
+        I::IntoIter : Send + 'static
+{
+    let workers = num_cpus::get();
+    // A receiver for each path's result, in order.
+    let (queue, pending) = sync_channel::<Receiver<Result<T>>>(workers * 4);
     let fn_ref = Arc::new(path_fn);
+    let abandoned = Arc::new(AtomicBool::new(false));
+    let paths = paths.into_iter();
+
+    thread::spawn(move || {
+        let pool = ThreadPool::new(workers);
+        for path in paths {
+            let (tx, rx) = sync_channel(1);
+            if queue.send(rx).is_err() {
+                abandoned.store(true, Ordering::Relaxed);
+                break;
+            }
+            let path = match path {
+                Ok(path) => path,
+                Err(e) => {
+                    let _ = tx.send(Err(e));
+                    continue;
+                },
+            };
+            let path_fn = fn_ref.clone();
+            let abandoned = abandoned.clone();
+
+            pool.execute(move || {
+                if abandoned.load(Ordering::Relaxed) {
+                    return;
+                }
+                let _ = tx.send(path_fn(path));
+            });
+        }
+    });
+
+    // A job that panicked leaves nothing to receive, and is skipped.
+    pending.into_iter().filter_map(|rx| rx.recv().ok())
+}
+
+
+/// Name of the index under the journal's state directory.
+pub const INDEX_FILE: &str = "index";
+
+/// An entry as cached in the index, one JSON object per line.
+///
+#[derive(Serialize, Deserialize)]
+struct IndexRecord {
+    path: PathBuf,
+    /// The file's mtime and size when it was parsed; if either changes, it's
+    /// parsed again.
+    stamp: (u64, u32, u64),
+    frontmatter: Frontmatter,
+    body: String,
+    body_line: usize,
+    #[serde(default)]
+    is_encrypted: bool,
+}
+
+fn stamp(m: &fs::Metadata) -> Option<(u64, u32, u64)> {
+    let mtime = m.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((mtime.as_secs(), mtime.subsec_nanos(), m.len()))
+}
+
+/// Every entry in the journal, taken from the index where the file hasn't
+/// changed since it was last parsed. Everything else is parsed in parallel
+/// and the index brought up to date.
+///
+/// Entries that fail to parse are skipped, and entries whose frontmatter is
+/// malformed aren't indexed, so that the error is seen each time.
+///
+pub fn load_entries(jrnl_path: &Path) -> Vec<Entry> {
+    load_entries_checked(jrnl_path).0
+}
 
-    for entry in get_jrnl_walker(jrnl_path) {
-        let path = entry.path().to_owned();
-        let tx = tx.clone();
-        let path_fn = fn_ref.clone();
+/// Like `load_entries`, but also returns an error for each file (or
+/// directory) that couldn't be read, and each entry whose frontmatter couldn't be parsed
+/// (which is still loaded, as it would be otherwise).
+///
+pub fn load_entries_checked(jrnl_path: &Path) -> (Vec<Entry>, Vec<Error>) {
+    let index_path = activity::state_dir(jrnl_path).join(INDEX_FILE);
+    let mut cached = read_index(&index_path);
+    let mut entries = Vec::new();
+    let mut errors = Vec::new();
+    let mut stale = Vec::new();
 
-        pool.execute(move || {
-            tx.send(path_fn(path)).expect("Couldn't send data!");
-        });
+    for d in get_jrnl_walker(jrnl_path.to_str().unwrap()) {
+        let path = match d {
+            Ok(d) => d.path().to_owned(),
+            Err(e) => {
+                errors.push(e);
+                continue;
+            },
+        };
+        let metadata = match fs::metadata(&path) {
+            Ok(m) => m,
+            Err(e) => {
+                errors.push(ErrorKind::EntryUnreadable(path, e.to_string()).into());
+                continue;
+            },
+        };
+        match cached.remove(&path) {
+            Some(r) if stamp(&metadata) == Some(r.stamp) => entries.push(Entry {
+                path: r.path,
+                file_metadata: Some(metadata),
+                frontmatter: r.frontmatter,
+                frontmatter_err: None,
+                bad_fields: Vec::new(),
+                body: r.body,
+                body_line: r.body_line,
+                is_encrypted: r.is_encrypted,
+            }),
+            _ => stale.push(path),
+        }
     }
 
-    drop(tx);
-    rx.iter().collect()
-}      
+    let parsed: Vec<Entry> = map_parallel(stale, |p| {
+            Entry::from_path(&p).map_err(|e| ErrorKind::EntryUnreadable(p, e.to_string()).into())
+        })
+        .into_iter()
+        .filter_map(|e| e.map_err(|e| errors.push(e)).ok())
+        .collect();
+    errors.extend(parsed.iter().flat_map(frontmatter_errors));
+    // Anything left in `cached` has been deleted.
+    let changed = !cached.is_empty() || parsed.iter().any(is_indexable);
+    entries.extend(parsed);
+
+    if changed {
+        // The index is only an optimization, so a journal that can't be
+        // written to still works, just more slowly.
+        let _ = write_index(&index_path, &entries);
+    }
+    (entries, errors)
+}
+
+/// Every entry in the journal, as `load_entries_checked` loads them, or the
+/// first error for a file that couldn't be read at all. Entries with bad
+/// frontmatter are kept.
+///
+pub fn load_all_entries(jrnl_path: &Path) -> Result<Vec<Entry>> {
+    let (entries, errors) = load_entries_checked(jrnl_path);
+    match errors.into_iter().find(|e| matches!(e.kind(), ErrorKind::EntryUnreadable(..))) {
+        Some(e) => Err(e),
+        None => Ok(entries),
+    }
+}
+
+/// Errors for `e`'s frontmatter, if it couldn't be parsed or has fields that
+/// can't be used, with the line in the file that each problem's on.
+///
+fn frontmatter_errors(e: &Entry) -> Vec<Error> {
+    if e.frontmatter_err.is_none() && e.bad_fields.is_empty() {
+        return Vec::new();
+    }
+    frontmatter_errors_in(e, &fs::read_to_string(&e.path).unwrap_or_default())
+}
+
+/// Like `frontmatter_errors`, given the text `e` was parsed from.
+///
+pub fn frontmatter_errors_in(e: &Entry, raw: &str) -> Vec<Error> {
+    let raw = raw.trim_start_matches(BOM);
+    if let Some(ref err) = e.frontmatter_err {
+        // YAML lines are counted from the start of the frontmatter, which is
+        // a line down if it opens with a delimiter.
+        let opening = has_opening_delimiter(raw);
+        let line = err.location().map(|l| l.line() + opening as usize);
+        return vec![ErrorKind::BadFrontmatter(e.path.clone(), line, err.to_string()).into()];
+    }
+    e.bad_fields.iter().map(|(key, reason)| {
+        let line = raw.lines().take(e.body_line)
+            .position(|l| l.starts_with(&format!("{}:", key)))
+            .map(|i| i + 1);
+        ErrorKind::BadFrontmatter(e.path.clone(), line, reason.clone()).into()
+    }).collect()
+}
+
+/// Whether `e` can be kept in the index. Entries with problems are left
+/// out, so they're parsed, and their problems reported, every time.
+///
+fn is_indexable(e: &Entry) -> bool {
+    e.frontmatter_err.is_none() && e.bad_fields.is_empty()
+}
+
+fn read_index(index_path: &Path) -> HashMap<PathBuf, IndexRecord> {
+    let raw = match fs::read_to_string(index_path) {
+        Ok(raw) => raw,
+        Err(_) => return HashMap::new(),
+    };
+    raw.lines()
+        .filter_map(|l| serde_json::from_str::<IndexRecord>(l).ok())
+        .map(|r| (r.path.clone(), r))
+        .collect()
+}
+
+/// Replace the index with `entries`, via a rename so that concurrent
+/// readers never see it half-written.
+///
+fn write_index(index_path: &Path, entries: &[Entry]) -> Result<()> {
+    fs::create_dir_all(index_path.parent().unwrap())?;
+    let tmp = index_path.with_extension(format!("tmp{}", std::process::id()));
+    let mut f = io::BufWriter::new(fs::File::create(&tmp)?);
+
+    for e in entries.iter().filter(|e| is_indexable(e)) {
+        let stamp = match e.file_metadata.as_ref().and_then(stamp) {
+            Some(s) => s,
+            None => continue,
+        };
+        let record = IndexRecord {
+            path: e.path.clone(),
+            stamp,
+            frontmatter: e.frontmatter.clone(),
+            body: e.body.clone(),
+            body_line: e.body_line,
+            is_encrypted: e.is_encrypted,
+        };
+        // Frontmatter JSON can't represent (non-string keys) is left out.
+        if let Ok(line) = serde_json::to_string(&record) {
+            writeln!(f, "{}", line)?;
+        }
+    }
+    f.flush()?;
+    drop(f);
+    fs::rename(&tmp, index_path)?;
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn replace_field_keeps_closing_delimiter_after_last_key() {
+        let raw = "tags: a\nreading_time: 1\n---\n\nbody\n";
+        assert_eq!(replace_field(raw, "reading_time", "reading_time: 3"),
+                   "tags: a\nreading_time: 3\n---\n\nbody\n");
+    }
+
+    #[test]
+    fn replace_field_replaces_block_values() {
+        let raw = "---\ntags:\n- a\n- b\nid: x\n---\nbody\n";
+        assert_eq!(replace_field(raw, "tags", "tags: c"),
+                   "---\ntags: c\nid: x\n---\nbody\n");
+        let raw = "tags:\n  - a\n---\nbody";
+        assert_eq!(replace_field(raw, "tags", "tags: c"), "tags: c\n---\nbody");
+    }
+
+    #[test]
+    fn map_parallel_keeps_order() {
+        let paths: Vec<PathBuf> = (0..200).map(|i| PathBuf::from(i.to_string())).collect();
+        let mapped: Vec<PathBuf> = map_parallel(paths.clone(), Ok).into_iter()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(mapped, paths);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_atomic_keeps_permissions_and_symlinks() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let dir = std::env::temp_dir().join(format!("jrni-atomic-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let target = dir.join("entry.md");
+        let link = dir.join("link.md");
+        fs::write(&target, "old").unwrap();
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o600)).unwrap();
+        symlink(&target, &link).unwrap();
+
+        write_atomic(&link, "new").unwrap();
+        let is_link = fs::symlink_metadata(&link).unwrap().file_type().is_symlink();
+        let mode = fs::metadata(&target).unwrap().permissions().mode();
+        let contents = fs::read_to_string(&target).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(is_link);
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(contents, "new");
+    }
+
+    #[test]
+    fn digest_is_sha256() {
+        assert_eq!(digest(b"abc"),
+                   "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    }
+
+
+    /// Frontmatter of distinct fields (none of them the standard ones) and
+    /// a body, as raw text, with or without an opening delimiter, CRLF
+    /// line endings and a byte order mark.
+    fn entry_text() -> impl Strategy<Value = (Vec<(String, String)>, String, String)> {
+        let fields = prop::collection::btree_map("k[a-z]{0,6}", "v[a-z0-9]{0,10}", 0..6)
+            .prop_map(|m| m.into_iter().collect::<Vec<_>>());
+        let body = prop::collection::vec("[^\r\n]{0,20}", 0..5);
+        (fields, body, any::<bool>(), any::<bool>(), any::<bool>())
+            .prop_map(|(fields, body, opening, crlf, bom)| {
+                let nl = if crlf { "\r\n" } else { "\n" };
+                let mut raw = String::new();
+                if bom {
+                    raw.push(BOM);
+                }
+                // A lone `---` would open frontmatter, not close it.
+                if opening || fields.is_empty() {
+                    raw.push_str("---");
+                    raw.push_str(nl);
+                }
+                for (k, v) in fields.iter() {
+                    raw.push_str(&format!("{}: {}{}", k, v, nl));
+                }
+                raw.push_str("---");
+                raw.push_str(nl);
+                let body = body.iter().map(|l| format!("{}{}", l, nl)).collect::<String>();
+                raw.push_str(&body);
+                (fields, body, raw)
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn replace_field_round_trips(
+            (fields, _, raw) in entry_text(),
+            key in "k[a-z]{0,6}",
+            value in "v[a-z0-9]{0,10}",
+        ) {
+            let rewritten = replace_field(&raw, &key, &format!("{}: {}", key, value));
+            let before = Entry::parse(Path::new("a.md"), &raw);
+            let after = Entry::parse(Path::new("a.md"), &rewritten);
+
+            prop_assert!(after.frontmatter_err.is_none());
+            prop_assert_eq!(after.frontmatter.get(&key), Some(YValue::String(value.clone())));
+            for (k, _) in fields.iter().filter(|(k, _)| *k != key) {
+                prop_assert_eq!(after.frontmatter.get(k), before.frontmatter.get(k));
+            }
+            prop_assert_eq!(&after.body, &before.body);
+            prop_assert_eq!(rewritten.starts_with(BOM), raw.starts_with(BOM));
+            prop_assert_eq!(rewritten.contains('\r'), raw.contains('\r'));
+            // Doing it again changes nothing more.
+            prop_assert_eq!(replace_field(&rewritten, &key, &format!("{}: {}", key, value)), rewritten);
+        }
+
+        #[test]
+        fn parse_never_panics(raw in "(---|\r?\n|\u{feff}|tags:|- |[^\r\n]{0,30})*") {
+            let e = Entry::parse(Path::new("a.md"), &raw);
+            prop_assert!(e.body_line >= 1);
+        }
+    }
+
+    #[test]
+    fn replace_field_adds_missing_fields_and_keeps_crlf() {
+        assert_eq!(replace_field("id: x\r\n---\r\nbody\r\n", "tags", "tags: a"),
+                   "id: x\r\ntags: a\r\n---\r\nbody\r\n");
+        assert_eq!(replace_field("just a body\n", "tags", "tags: a"),
+                   "tags: a\n---\n\njust a body\n");
+    }
+}
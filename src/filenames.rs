@@ -0,0 +1,105 @@
+//! Keeping filenames portable, so that a journal synced to Windows, or
+//! through Dropbox, doesn't trip over files it can't represent. Configured
+//! with, e.g.:
+//!
+//! ```toml
+//! [filenames]
+//! forbidden = ":?*<>|\"\\"
+//! max_length = 255
+//! normalize = true
+//! ```
+//!
+//! New and imported entries are named within these rules; `jrni doctor
+//! filenames` finds existing entries that aren't.
+//!
+use std::path::{Component, Path, PathBuf};
+
+use serde::Deserialize;
+use unicode_normalization::UnicodeNormalization;
+
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Rules {
+    /// Characters that can't appear in a filename, replaced with `-`.
+    /// Control characters never can.
+    pub forbidden: String,
+    /// Longest a filename (each part of a path, not the whole) may be, in
+    /// bytes.
+    pub max_length: usize,
+    /// Normalize to Unicode NFC, since macOS and Linux may otherwise write
+    /// the same name with different bytes.
+    pub normalize: bool,
+}
+
+impl Default for Rules {
+    fn default() -> Rules {
+        Rules { forbidden: r#":?*<>|"\"#.to_owned(), max_length: 255, normalize: true }
+    }
+}
+
+impl Rules {
+    /// `name`, a single filename, changed as little as possible to keep to
+    /// the rules: forbidden characters become `-`, trailing dots and
+    /// spaces (which Windows drops) go, and a name that's too long is
+    /// shortened before its extension.
+    ///
+    pub fn sanitize(&self, name: &str) -> String {
+        let name: String = match self.normalize {
+            true => name.nfc().collect(),
+            false => name.to_owned(),
+        };
+        let name: String = name.chars()
+            .map(|c| if c.is_control() || self.forbidden.contains(c) { '-' } else { c })
+            .collect();
+        let name = name.trim_end_matches(['.', ' ']);
+        if name.len() <= self.max_length {
+            return name.to_owned();
+        }
+
+        let (stem, ext) = match name.rfind('.') {
+            Some(i) if i > 0 && name.len() - i < self.max_length => name.split_at(i),
+            _ => (name, ""),
+        };
+        let mut end = self.max_length - ext.len();
+        while !stem.is_char_boundary(end) {
+            end -= 1;
+        }
+        format!("{}{}", stem[..end].trim_end_matches(['.', ' ']), ext)
+    }
+
+    /// `path`, relative to the journal, with each part sanitized.
+    ///
+    pub fn sanitize_path(&self, path: &Path) -> PathBuf {
+        path.components().map(|c| match c {
+            Component::Normal(name) => PathBuf::from(self.sanitize(&name.to_string_lossy())),
+            other => PathBuf::from(other.as_os_str()),
+        }).collect()
+    }
+
+    /// Whether every part of `path` already keeps to the rules.
+    ///
+    pub fn allows(&self, path: &Path) -> bool {
+        self.sanitize_path(path) == path
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn sanitize_keeps_to_the_rules(name in "\\PC{0,80}", max_length in 8usize..64) {
+            let rules = Rules { max_length, ..Rules::default() };
+            let sane = rules.sanitize(&name);
+            prop_assert!(sane.len() <= max_length);
+            prop_assert!(!sane.chars().any(|c| c.is_control() || rules.forbidden.contains(c)));
+            prop_assert!(!sane.ends_with('.') && !sane.ends_with(' '));
+            prop_assert_eq!(rules.sanitize(&sane), sane);
+        }
+    }
+}
@@ -0,0 +1,114 @@
+//! Rewriting frontmatter in one canonical style, for `jrni fmt`: fields in
+//! a set order (those named in `[fmt] key_order`, then the rest
+//! alphabetically), tags as a YAML sequence, and dates quoted. Bodies are
+//! left exactly as they were, as are line endings and delimiters.
+//!
+use chrono::{DateTime, NaiveDate};
+use serde_yaml::{Mapping, Value as YValue};
+
+use crate::datetime;
+use crate::error::Result;
+use crate::filedb::{self, Frontmatter};
+
+
+/// Fields put first when no `key_order` is configured, in the order new
+/// entries get them.
+pub const DEFAULT_KEY_ORDER: &[&str] = &["tags", "id", "pubdate", "title"];
+
+/// `raw`, the contents of an entry, with its frontmatter in canonical
+/// style, or None if it has no frontmatter. Fails on frontmatter that
+/// doesn't parse, or that has comments, which would be lost.
+///
+pub fn format(raw: &str, key_order: &[String]) -> Result<Option<String>> {
+    let bom = if raw.starts_with('\u{feff}') { "\u{feff}" } else { "" };
+    let raw = raw.trim_start_matches('\u{feff}');
+    let nl = filedb::line_ending(raw);
+    let opening = filedb::has_opening_delimiter(raw);
+
+    let mut lines = raw.split_inclusive('\n');
+    let mut offset = 0;
+    if opening {
+        offset += lines.next().map_or(0, |l| l.len());
+    }
+    let start = offset;
+    let mut closing = None;
+    for line in lines {
+        if line.trim() == "---" {
+            closing = Some(offset);
+            break;
+        }
+        offset += line.len();
+    }
+    let end = match closing {
+        Some(end) => end,
+        None => return Ok(None),
+    };
+
+    let yaml = &raw[start..end];
+    if yaml.lines().any(|l| l.trim_start().starts_with('#')) {
+        bail!("frontmatter has comments, which formatting would lose");
+    }
+    let (mapping, typed): (Mapping, Frontmatter) = match yaml.trim().is_empty() {
+        true => (Mapping::new(), Frontmatter::default()),
+        false => (serde_yaml::from_str(yaml)?, serde_yaml::from_str(yaml)?),
+    };
+
+    let mut fields: Vec<(&str, &YValue)> = Vec::new();
+    for (k, v) in mapping.iter() {
+        match k.as_str() {
+            Some(k) => fields.push((k, v)),
+            None => bail!("frontmatter has a key that isn't a string"),
+        }
+    }
+    let rank = |k: &str| key_order.iter().position(|o| o == k).unwrap_or(key_order.len());
+    fields.sort_by(|a, b| rank(a.0).cmp(&rank(b.0)).then(a.0.cmp(b.0)));
+
+    let mut out = String::from(bom);
+    if opening {
+        out.push_str("---");
+        out.push_str(nl);
+    }
+    for (k, v) in fields {
+        let line = match k {
+            "tags" => format!("tags: [{}]", typed.tags.iter()
+                .map(|t| flow_item(t)).collect::<Vec<_>>().join(", ")),
+            _ => field(k, v)?,
+        };
+        for l in line.lines() {
+            out.push_str(l);
+            out.push_str(nl);
+        }
+    }
+    out.push_str(&raw[end..]);
+    Ok(Some(out))
+}
+
+/// `key: value` as YAML, quoting dates.
+///
+fn field(key: &str, value: &YValue) -> Result<String> {
+    match value {
+        YValue::String(s) if is_date(s) => Ok(format!("{}: {}", key, filedb::yaml_quote(s))),
+        _ => {
+            let mut m = Mapping::new();
+            m.insert(YValue::String(key.to_owned()), value.clone());
+            Ok(serde_yaml::to_string(&m)?.trim_start_matches("---").trim().to_owned())
+        },
+    }
+}
+
+fn is_date(s: &str) -> bool {
+    datetime::from_str(s.to_owned()).is_ok()
+        || DateTime::parse_from_rfc3339(s).is_ok()
+        || NaiveDate::parse_from_str(s, "%Y-%m-%d").is_ok()
+}
+
+/// A tag as an item of a flow sequence, quoted unless it's plainly safe.
+///
+fn flow_item(tag: &str) -> String {
+    let plain = tag.chars().all(|c| c.is_alphanumeric() || "-_/.:".contains(c))
+        && !tag.starts_with('-') && !tag.ends_with(':');
+    match plain {
+        true => tag.to_owned(),
+        false => filedb::yaml_quote(tag),
+    }
+}
@@ -0,0 +1,156 @@
+//! Markdown footnotes (`text[^1]` ... `[^1]: note`): finding the ones that
+//! have rotted, and renumbering them in reading order.
+//!
+use std::collections::HashMap;
+use std::fmt;
+
+use regex::{Captures, Regex};
+
+
+
+#[derive(Debug, PartialEq)]
+pub enum Problem {
+    /// Referenced, but never defined.
+    Undefined(String),
+    /// Defined, but never referenced.
+    Unused(String),
+    /// Defined more than once.
+    Duplicate(String),
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::Undefined(l) => write!(f, "[^{}] is never defined", l),
+            Problem::Unused(l) => write!(f, "[^{}] is defined but never used", l),
+            Problem::Duplicate(l) => write!(f, "[^{}] is defined more than once", l),
+        }
+    }
+}
+
+fn label_re() -> Regex {
+    Regex::new(r"\[\^([^\]\s]+)\]").unwrap()
+}
+
+fn definition_re() -> Regex {
+    Regex::new(r"^ {0,3}\[\^([^\]\s]+)\]:").unwrap()
+}
+
+/// Lines of `text` outside of fenced code blocks.
+///
+fn prose_lines(text: &str) -> impl Iterator<Item = &str> {
+    let mut in_fence = false;
+    text.lines().filter(move |line| {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+            return false;
+        }
+        !in_fence
+    })
+}
+
+/// Footnote labels in `text`, as (references in order of first use,
+/// definitions in order).
+///
+fn labels(text: &str) -> (Vec<String>, Vec<String>) {
+    let label = label_re();
+    let definition = definition_re();
+    let mut refs: Vec<String> = Vec::new();
+    let mut defs: Vec<String> = Vec::new();
+
+    for line in prose_lines(text) {
+        let mut rest = line;
+        if let Some(caps) = definition.captures(line) {
+            defs.push(caps[1].to_owned());
+            rest = &line[caps.get(0).unwrap().end()..];
+        }
+        for caps in label.captures_iter(rest) {
+            if !refs.iter().any(|r| r == &caps[1]) {
+                refs.push(caps[1].to_owned());
+            }
+        }
+    }
+    (refs, defs)
+}
+
+pub fn check(text: &str) -> Vec<Problem> {
+    let (refs, defs) = labels(text);
+    let mut problems = Vec::new();
+
+    for r in refs.iter().filter(|r| !defs.contains(r)) {
+        problems.push(Problem::Undefined(r.clone()));
+    }
+    let mut seen: Vec<&String> = Vec::new();
+    for d in defs.iter() {
+        if seen.contains(&d) {
+            if !problems.contains(&Problem::Duplicate(d.clone())) {
+                problems.push(Problem::Duplicate(d.clone()));
+            }
+        } else if !refs.contains(d) {
+            problems.push(Problem::Unused(d.clone()));
+        }
+        seen.push(d);
+    }
+    problems
+}
+
+/// Relabel footnotes as 1, 2, 3... in the order they're first referenced,
+/// followed by any that are defined but never referenced.
+///
+pub fn renumber(text: &str) -> String {
+    let (refs, defs) = labels(text);
+    let mut numbers: HashMap<String, String> = HashMap::new();
+    for l in refs.into_iter().chain(defs) {
+        let n = (numbers.len() + 1).to_string();
+        numbers.entry(l).or_insert(n);
+    }
+
+    let label = label_re();
+    let mut out = String::with_capacity(text.len());
+    let mut in_fence = false;
+    // Each line keeps its own ending, so nothing but the labels changes.
+    for line in text.split_inclusive('\n') {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+        if in_fence || line.trim_start().starts_with("```") {
+            out.push_str(line);
+            continue;
+        }
+        out.push_str(&label.replace_all(line, |caps: &Captures| {
+            format!("[^{}]", numbers.get(&caps[1]).map_or(&caps[1], |n| n.as_str()))
+        }));
+    }
+    out
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_finds_undefined_unused_and_duplicate_footnotes() {
+        let text = "One[^a], two[^b], one again[^a].\n\n\
+                    [^a]: first\n[^c]: never used\n[^a]: again\n";
+        assert_eq!(check(text), vec![
+            Problem::Undefined("b".to_owned()),
+            Problem::Unused("c".to_owned()),
+            Problem::Duplicate("a".to_owned()),
+        ]);
+        assert_eq!(check("Fine[^1].\n\n[^1]: note[^2]\n[^2]: a note's note\n"), vec![]);
+    }
+
+    #[test]
+    fn code_blocks_are_left_alone() {
+        let text = "Real[^x].\n\n```\narr[^y]\n[^z]: not a note\n```\n\n[^x]: note\n";
+        assert_eq!(check(text), vec![]);
+        assert_eq!(renumber(text), "Real[^1].\n\n```\narr[^y]\n[^z]: not a note\n```\n\n[^1]: note\n");
+    }
+
+    #[test]
+    fn renumber_follows_reading_order() {
+        let text = "B[^b] then A[^a] and B[^b].\r\n\r\n[^a]: a\r\n[^b]: b\r\n[^old]: unused";
+        assert_eq!(renumber(text), "B[^1] then A[^2] and B[^1].\r\n\r\n[^2]: a\r\n[^1]: b\r\n[^3]: unused");
+    }
+}
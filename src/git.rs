@@ -0,0 +1,223 @@
+//! Keeping a journal that's a git repository committed and in sync.
+//!
+//! In git mode, whatever a command creates or changes in the journal is
+//! committed once it's done, with a message saying which entries changed.
+//! Only what the command wrote is committed, so edits made outside jrni
+//! are left for you to commit.
+//! jrni's own state (`.jrni/`) is never committed. `sync` then pulls and
+//! pushes, so the journal can be kept on several machines.
+//!
+//! Separately, a pre-commit hook can be installed that runs `jrni verify`
+//! on the entries being committed, whoever commits them.
+//!
+//! This goes through the `git` CLI, so it needs to be installed.
+//!
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::activity::STATE_DIR;
+use crate::crypt::run;
+use crate::error::Result;
+use crate::filedb::content_hash;
+
+
+/// Whether the journal at `jrnl_path` is in a git repository.
+///
+pub fn is_repo(jrnl_path: &Path) -> bool {
+    git(jrnl_path)
+        .args(["rev-parse", "--is-inside-work-tree"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
+/// Make the journal at `jrnl_path` a git repository of its own, ignoring
+/// jrni's state.
+///
+pub fn init(jrnl_path: &Path) -> Result<()> {
+    run(git(jrnl_path).args(["init", "-q"]), None)?;
+    let ignore = jrnl_path.join(".gitignore");
+    if !ignore.exists() {
+        fs::write(&ignore, format!("/{}/\n", STATE_DIR))?;
+    }
+    Ok(())
+}
+
+/// Stage everything changed in the journal and commit it. Returns the
+/// commit message, or None if there was nothing to commit.
+///
+pub fn commit_changes(jrnl_path: &Path) -> Result<Option<String>> {
+    commit(jrnl_path, &[".".to_owned(), format!(":(exclude){}", STATE_DIR)])
+}
+
+/// Files in the journal that differ from what's committed, staged or not,
+/// with a hash of each one's contents (None for one that's been deleted).
+/// jrni's own state is left out.
+///
+pub fn dirty_files(jrnl_path: &Path) -> Result<HashMap<PathBuf, Option<u64>>> {
+    if !is_repo(jrnl_path) {
+        bail!("{} isn't in a git repository; run `git init` there to use git mode",
+              jrnl_path.display());
+    }
+    let exclude = format!(":(exclude){}", STATE_DIR);
+    let unstaged = run(git(jrnl_path)
+        .args(["ls-files", "-z", "--modified", "--others", "--deleted", "--exclude-standard", "--", "."])
+        .arg(&exclude), None)?;
+    let staged = run(git(jrnl_path)
+        .args(["diff", "--cached", "--name-only", "-z", "--relative", "--", "."])
+        .arg(&exclude), None)?;
+
+    let mut dirty = HashMap::new();
+    for out in [unstaged, staged].iter() {
+        for rel in String::from_utf8_lossy(out).split('\0').filter(|p| !p.is_empty()) {
+            let hash = fs::read(jrnl_path.join(rel)).ok().map(|c| content_hash(&c));
+            dirty.insert(PathBuf::from(rel), hash);
+        }
+    }
+    Ok(dirty)
+}
+
+/// Commit the files in the journal that have changed since `before` was
+/// taken with `dirty_files`, leaving alone any that were already changed and
+/// still are as they were. Returns the commit message, or None if nothing
+/// changed.
+///
+pub fn commit_written(jrnl_path: &Path, before: &HashMap<PathBuf, Option<u64>>) -> Result<Option<String>> {
+    let written: Vec<String> = dirty_files(jrnl_path)?.into_iter()
+        .filter(|(path, hash)| before.get(path) != Some(hash))
+        .map(|(path, _)| format!(":(literal){}", path.display()))
+        .collect();
+    if written.is_empty() {
+        return Ok(None);
+    }
+    commit(jrnl_path, &written)
+}
+
+/// Stage and commit what `pathspecs` match in the journal, and nothing
+/// else.
+///
+fn commit(jrnl_path: &Path, pathspecs: &[String]) -> Result<Option<String>> {
+    if !is_repo(jrnl_path) {
+        bail!("{} isn't in a git repository; run `git init` there to use git mode",
+              jrnl_path.display());
+    }
+    run(git(jrnl_path).args(["add", "-A", "--"]).args(pathspecs), None)?;
+
+    let staged = run(git(jrnl_path)
+        .args(["diff", "--cached", "--name-status", "--relative", "--"])
+        .args(pathspecs), None)?;
+    let staged = String::from_utf8_lossy(&staged);
+    let changes: Vec<(&str, &str)> = staged.lines()
+        .filter_map(|l| {
+            let mut fields = l.split('\t');
+            Some((fields.next()?, fields.next_back()?))
+        })
+        .collect();
+    if changes.is_empty() {
+        return Ok(None);
+    }
+
+    let message = message(&changes);
+    run(git(jrnl_path).args(["commit", "-q", "-m", &message, "--"]).args(pathspecs), None)?;
+    Ok(Some(message))
+}
+
+/// Entry files staged for commit in the journal, with their contents as
+/// staged, which may not be what's on disk.
+///
+pub fn staged_entries(jrnl_path: &Path) -> Result<Vec<(PathBuf, String)>> {
+    let exclude = format!(":(exclude){}", STATE_DIR);
+    let staged = run(git(jrnl_path)
+        .args(["diff", "--cached", "--name-only", "--diff-filter=ACMR", "-z", "--relative", "--", "."])
+        .arg(&exclude), None)?;
+
+    let mut entries = Vec::new();
+    for rel in String::from_utf8_lossy(&staged).split('\0').map(Path::new) {
+        let is_entry = rel.extension().is_some_and(|e| e == "md" || e == "txt");
+        let hidden = rel.components().any(|c| c.as_os_str().to_string_lossy().starts_with('.'));
+        if !is_entry || hidden {
+            continue;
+        }
+        let contents = run(git(jrnl_path).arg("show").arg(format!(":./{}", rel.display())), None)?;
+        entries.push((jrnl_path.join(rel), String::from_utf8_lossy(&contents).into_owned()));
+    }
+    Ok(entries)
+}
+
+/// First line of the hook after the shebang, marking it as jrni's.
+const HOOK_MARKER: &str = "# installed by `jrni hooks install-git`";
+
+/// Install a pre-commit hook in the journal's repository that runs
+/// `jrni verify --staged` with `jrni` (the path to the binary), so broken
+/// entries can't be committed. A hook that jrni didn't install is only
+/// replaced with `force`. Returns the hook's path.
+///
+pub fn install_hook(jrnl_path: &Path, jrni: &Path, force: bool) -> Result<PathBuf> {
+    if !is_repo(jrnl_path) {
+        bail!("{} isn't in a git repository", jrnl_path.display());
+    }
+    // Respects core.hooksPath, and worktrees.
+    let hooks = run(git(jrnl_path).args(["rev-parse", "--git-path", "hooks"]), None)?;
+    let hooks = jrnl_path.join(String::from_utf8_lossy(&hooks).trim());
+    let hook = hooks.join("pre-commit");
+
+    if let Ok(existing) = fs::read_to_string(&hook) {
+        if !existing.contains(HOOK_MARKER) && !force {
+            bail!("{} already exists; pass --force to replace it", hook.display());
+        }
+    }
+    let script = format!("#!/bin/sh\n{}\nexec {} --path {} verify --staged\n",
+                         HOOK_MARKER, shell_quote(&jrni.to_string_lossy()),
+                         shell_quote(&fs::canonicalize(jrnl_path)?.to_string_lossy()));
+    fs::create_dir_all(&hooks)?;
+    fs::write(&hook, script)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&hook, fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(hook)
+}
+
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// Pull (rebasing any local commits) and then push the journal's repository.
+///
+pub fn sync(jrnl_path: &Path) -> Result<()> {
+    commit_changes(jrnl_path)?;
+    run(git(jrnl_path).args(["pull", "--rebase", "-q"]), None)?;
+    run(git(jrnl_path).args(["push", "-q"]), None)?;
+    Ok(())
+}
+
+/// A commit message for `changes`, pairs of git's status letter and path.
+///
+fn message(changes: &[(&str, &str)]) -> String {
+    let verb = |status: &str| match status.chars().next() {
+        Some('A') => "add",
+        Some('D') => "remove",
+        Some('R') => "rename",
+        _ => "update",
+    };
+    match changes {
+        [(status, path)] => format!("jrni: {} {}", verb(status), path),
+        _ => {
+            let mut message = format!("jrni: change {} files\n", changes.len());
+            for (status, path) in changes.iter() {
+                message.push_str(&format!("\n{} {}", verb(status), path));
+            }
+            message
+        },
+    }
+}
+
+fn git(jrnl_path: &Path) -> Command {
+    let mut cmd = Command::new("git");
+    cmd.arg("-C").arg(jrnl_path);
+    cmd
+}
@@ -0,0 +1,116 @@
+//! Helpers for rendering entries as HTML.
+//!
+use std::io::{self, Write};
+
+use pulldown_cmark::{html as cmark_html, CowStr, Event, Options, Parser, Tag};
+
+
+pub fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Render a Markdown entry body to HTML.
+///
+pub fn markdown(body: &str) -> String {
+    let mut out = String::new();
+    cmark_html::push_html(&mut out, Parser::new_ext(body, options()));
+    out
+}
+
+/// Render a Markdown entry body to HTML as it's parsed, rather than
+/// building it up in memory first.
+///
+pub fn write_markdown<W: Write>(w: W, body: &str) -> io::Result<()> {
+    cmark_html::write_html(w, Parser::new_ext(body, options()))
+}
+
+/// Render a Markdown body that may have been written by someone other than
+/// the reader: raw HTML is shown as text, and links and images that aren't
+/// relative, `http`, `https` or `mailto` lose their target.
+///
+pub fn safe_markdown(body: &str) -> String {
+    let events = Parser::new_ext(body, options()).map(|event| match event {
+        Event::Html(raw) => Event::Text(raw),
+        Event::Start(tag) => Event::Start(safe_tag(tag)),
+        Event::End(tag) => Event::End(safe_tag(tag)),
+        e => e,
+    });
+    let mut out = String::new();
+    cmark_html::push_html(&mut out, events);
+    out
+}
+
+fn safe_tag(tag: Tag) -> Tag {
+    match tag {
+        Tag::Link(kind, url, title) if !is_safe_url(&url) =>
+            Tag::Link(kind, CowStr::Borrowed(""), title),
+        Tag::Image(kind, url, title) if !is_safe_url(&url) =>
+            Tag::Image(kind, CowStr::Borrowed(""), title),
+        t => t,
+    }
+}
+
+/// Whether `url` is relative or has a scheme that can't run script.
+///
+fn is_safe_url(url: &str) -> bool {
+    let url: String = url.chars().filter(|c| !c.is_whitespace() && !c.is_control()).collect();
+    match url.find([':', '/', '?', '#']) {
+        Some(i) if url[i..].starts_with(':') => {
+            let scheme = url[..i].to_ascii_lowercase();
+            scheme == "http" || scheme == "https" || scheme == "mailto"
+        },
+        _ => true,
+    }
+}
+
+pub(crate) fn options() -> Options {
+    let mut opts = Options::empty();
+    opts.insert(Options::ENABLE_TABLES);
+    opts.insert(Options::ENABLE_FOOTNOTES);
+    opts.insert(Options::ENABLE_STRIKETHROUGH);
+    opts.insert(Options::ENABLE_TASKLISTS);
+    opts
+}
+
+/// Wrap some HTML in a minimal standalone page.
+///
+pub fn page(title: &str, content: &str) -> String {
+    described_page(title, None, content)
+}
+
+/// A page with a description, for search engines and link previews.
+///
+pub fn described_page(title: &str, description: Option<&str>, content: &str) -> String {
+    format!("{}{}\n{}", page_start(title, description), content, PAGE_END)
+}
+
+/// A page up to the start of its body, for writing the rest piecemeal.
+/// Finish it with `PAGE_END`.
+///
+pub fn page_start(title: &str, description: Option<&str>) -> String {
+    let meta = match description {
+        Some(d) => format!(
+            "<meta name=\"description\" content=\"{0}\">\n\
+             <meta property=\"og:title\" content=\"{1}\">\n\
+             <meta property=\"og:description\" content=\"{0}\">\n",
+            escape(d), escape(title)),
+        None => String::new(),
+    };
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{}</title>\n{}</head>\n<body>\n",
+        escape(title), meta)
+}
+
+pub const PAGE_END: &str = "</body>\n</html>\n";
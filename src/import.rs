@@ -0,0 +1,296 @@
+//! Reading entries out of other journaling tools, to be written into the
+//! journal as ordinary entries.
+//!
+//! - `jrnl`: the plain text that `jrnl --export txt` writes, or a jrnl
+//!   journal file itself. Each entry starts with a line like
+//!   `[2024-05-10 09:30] Title` (the brackets are optional); `@tags` in the
+//!   text become tags.
+//! - `dayone`: the JSON file in a Day One export.
+//! - `markdown`: a directory of loose Markdown or text files. Frontmatter
+//!   is kept, id and other fields included. The date is its pubdate, or
+//!   failing that the date the filename starts with, or when the file was
+//!   last modified.
+//!
+use std::fs;
+use std::path::Path;
+
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use regex::Regex;
+use serde::Deserialize;
+use walkdir::WalkDir;
+
+use crate::error::Result;
+use crate::filedb::{self, Entry};
+
+
+pub const FORMATS: &[&str] = &["jrnl", "dayone", "markdown"];
+
+/// An entry read from another tool.
+///
+#[derive(Debug)]
+pub struct Imported {
+    pub when: DateTime<Local>,
+    pub title: Option<String>,
+    /// The id it had, for tools that give entries one.
+    pub id: Option<String>,
+    /// What it was called, less any date, for tools that name entries.
+    pub name: Option<String>,
+    pub tags: Vec<String>,
+    /// Further frontmatter fields it had.
+    pub fields: Vec<(String, String)>,
+    pub body: String,
+}
+
+/// Read every entry at `path`, in `format` (one of `FORMATS`).
+///
+pub fn read(format: &str, path: &Path) -> Result<Vec<Imported>> {
+    match format {
+        "jrnl" => Ok(jrnl(&fs::read_to_string(path)?)),
+        "dayone" => dayone(&fs::read_to_string(path)?),
+        "markdown" => markdown(path),
+        _ => bail!("can't import '{}'; formats are {}", format, FORMATS.join(", ")),
+    }
+}
+
+const JRNL_DATE_FORMATS: &[&str] = &[
+    "%Y-%m-%d %I:%M:%S %p",
+    "%Y-%m-%d %I:%M %p",
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%d %H:%M",
+];
+
+fn jrnl(raw: &str) -> Vec<Imported> {
+    let start = Regex::new(
+        r"^\[?(\d{4}-\d{2}-\d{2} \d{1,2}:\d{2}(?::\d{2})?(?: ?[AaPp][Mm])?)\]? (.*)$").unwrap();
+    let mut entries = Vec::new();
+    let mut current: Option<Imported> = None;
+
+    for line in raw.lines() {
+        let started = start.captures(line).and_then(|c| {
+            let when = JRNL_DATE_FORMATS.iter()
+                .find_map(|f| NaiveDateTime::parse_from_str(&c[1], f).ok())?;
+            Some((local(when)?, c[2].trim().to_owned()))
+        });
+        match (started, current.as_mut()) {
+            (Some((when, title)), _) => {
+                entries.extend(current.take());
+                current = Some(Imported {
+                    when,
+                    title: Some(title.trim_end_matches('*').trim().to_owned()),
+                    id: None,
+                    name: None,
+                    tags: Vec::new(),
+                    fields: Vec::new(),
+                    body: String::new(),
+                });
+            },
+            (None, Some(e)) => {
+                e.body.push_str(line);
+                e.body.push('\n');
+            },
+            // Anything before the first entry isn't part of one.
+            (None, None) => (),
+        }
+    }
+    entries.extend(current);
+
+    let tag = Regex::new(r"(?:^|\s)@([\w/-]+)").unwrap();
+    for e in entries.iter_mut() {
+        e.body = e.body.trim().to_owned();
+        let text = format!("{} {}", e.title.as_deref().unwrap_or(""), e.body);
+        for c in tag.captures_iter(&text) {
+            if !e.tags.contains(&c[1].to_owned()) {
+                e.tags.push(c[1].to_owned());
+            }
+        }
+    }
+    entries
+}
+
+#[derive(Deserialize)]
+struct DayOneExport {
+    entries: Vec<DayOneEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DayOneEntry {
+    creation_date: String,
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+fn dayone(raw: &str) -> Result<Vec<Imported>> {
+    let export: DayOneExport = serde_json::from_str(raw)?;
+    let mut entries = Vec::new();
+    for e in export.entries {
+        let when = match DateTime::parse_from_rfc3339(&e.creation_date) {
+            Ok(when) => when.with_timezone(&Local),
+            Err(_) => bail!("Day One entry has a bad creationDate '{}'", e.creation_date),
+        };
+        // Day One keeps the title as the first line, usually a heading.
+        let text = e.text.trim();
+        let (first, rest) = text.split_at(text.find('\n').unwrap_or(text.len()));
+        let title = first.trim_start_matches('#').trim();
+        entries.push(Imported {
+            when,
+            title: if title.is_empty() { None } else { Some(title.to_owned()) },
+            id: None,
+            name: None,
+            tags: e.tags,
+            fields: Vec::new(),
+            body: unescape_dayone(rest.trim()),
+        });
+    }
+    Ok(entries)
+}
+
+/// Day One escapes Markdown punctuation it didn't mean as Markdown.
+///
+fn unescape_dayone(body: &str) -> String {
+    Regex::new(r"\\([\\.\-!()\[\]#*_+])").unwrap().replace_all(body, "$1").into_owned()
+}
+
+fn markdown(dir: &Path) -> Result<Vec<Imported>> {
+    let mut entries = Vec::new();
+    let walker = WalkDir::new(dir)
+        .sort_by(|a, b| a.path().cmp(b.path()))
+        .into_iter()
+        // Not the directory itself, which may well be `.`.
+        .filter_entry(|d| d.depth() == 0 || !d.file_name().to_string_lossy().starts_with('.'));
+    for dent in walker {
+        let dent = dent?;
+        let is_text = dent.path().extension()
+            .is_some_and(|e| e == "md" || e == "markdown" || e == "txt");
+        if !dent.file_type().is_file() || !is_text {
+            continue;
+        }
+        let raw = fs::read_to_string(dent.path())?;
+        let parsed = Entry::parse(dent.path(), &raw);
+        // Frontmatter that doesn't parse may just be text above a rule.
+        let has_frontmatter = parsed.frontmatter_err.is_none() && parsed.body_line > 1;
+
+        let when = match parsed.pubdate() {
+            Some(p) if has_frontmatter => p.with_timezone(&Local),
+            _ => match parsed.filename_date().and_then(|d| local(d.and_hms_opt(9, 0, 0)?)) {
+                Some(when) => when,
+                None => DateTime::from(fs::metadata(dent.path())?.modified()?),
+            },
+        };
+        let stem = dent.path().file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        let name = match parsed.filename_date() {
+            Some(_) => stem.get(10..).unwrap_or("").trim_start_matches(['-', '_', ' ']),
+            None => stem.as_str(),
+        };
+        // Only a title of its own; one from the filename is the name.
+        let title = Some(parsed.title()).filter(|t| *t != stem).map(String::from);
+
+        entries.push(match has_frontmatter {
+            true => {
+                let mut fields: Vec<(String, String)> = parsed.frontmatter.extra.iter()
+                    .map(|(k, v)| (k.clone(), filedb::inline_value(v)))
+                    .collect();
+                fields.sort();
+                Imported {
+                    when,
+                    title,
+                    id: parsed.get_id().map(String::from),
+                    name: Some(name.to_owned()).filter(|n| !n.is_empty()),
+                    tags: parsed.frontmatter.tags.clone(),
+                    fields,
+                    body: parsed.body.trim().to_owned(),
+                }
+            },
+            false => Imported {
+                when,
+                title,
+                id: None,
+                name: Some(name.to_owned()).filter(|n| !n.is_empty()),
+                tags: Vec::new(),
+                fields: Vec::new(),
+                body: raw.trim_start_matches('\u{feff}').trim().to_owned(),
+            },
+        });
+    }
+    Ok(entries)
+}
+
+fn local(when: NaiveDateTime) -> Option<DateTime<Local>> {
+    Local.from_local_datetime(&when).earliest()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> DateTime<Local> {
+        local(NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()).unwrap()
+    }
+
+    #[test]
+    fn jrnl_entries_start_at_timestamps() {
+        let raw = "ignored preamble\n\
+                   [2024-05-10 09:30] Standup *\n\
+                   Shipped it @work/code.\n\
+                   \n\
+                   2024-05-10 09:45 PM Dinner with @family @work/code\n\
+                   [not a date] still dinner\n";
+        let entries = jrnl(raw);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].when, at("2024-05-10 09:30"));
+        assert_eq!(entries[0].title.as_deref(), Some("Standup"));
+        assert_eq!(entries[0].body, "Shipped it @work/code.");
+        assert_eq!(entries[0].tags, vec!["work/code"]);
+        assert_eq!(entries[1].when, at("2024-05-10 21:45"));
+        assert_eq!(entries[1].body, "[not a date] still dinner");
+        assert_eq!(entries[1].tags, vec!["family", "work/code"]);
+    }
+
+    #[test]
+    fn dayone_titles_come_from_the_first_line() {
+        let raw = r##"{"entries": [
+            {"creationDate": "2024-05-10T09:30:00Z", "text": "# Standup\n\nShipped v1\\.2\\!", "tags": ["work"]},
+            {"creationDate": "2024-05-11T09:30:00Z"}
+        ]}"##;
+        let entries = dayone(raw).unwrap();
+        assert_eq!(entries[0].when, DateTime::parse_from_rfc3339("2024-05-10T09:30:00Z").unwrap());
+        assert_eq!(entries[0].title.as_deref(), Some("Standup"));
+        assert_eq!(entries[0].body, "Shipped v1.2!");
+        assert_eq!(entries[0].tags, vec!["work"]);
+        assert_eq!(entries[1].title, None);
+        assert!(dayone(r#"{"entries": [{"creationDate": "yesterday"}]}"#).is_err());
+    }
+
+    #[test]
+    fn markdown_keeps_frontmatter_and_names_from_filenames() {
+        let dir = std::env::temp_dir().join(format!("jrni-import-{}", std::process::id()));
+        fs::create_dir_all(dir.join(".hidden")).unwrap();
+        fs::write(dir.join("2024-05-10-standup.md"),
+                  "tags: work\nid: su\nmood: good\npubdate: 2024-05-10 09:30:00.000 +0000\n---\n\nShipped.\n").unwrap();
+        fs::write(dir.join("2024-05-11_ideas.txt"), "Some ideas.\n").unwrap();
+        fs::write(dir.join(".hidden").join("secret.md"), "no\n").unwrap();
+        fs::write(dir.join("photo.png"), b"").unwrap();
+
+        let entries = read("markdown", &dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].when, DateTime::parse_from_rfc3339("2024-05-10T09:30:00Z").unwrap());
+        assert_eq!(entries[0].id.as_deref(), Some("su"));
+        assert_eq!(entries[0].name.as_deref(), Some("standup"));
+        assert_eq!(entries[0].tags, vec!["work"]);
+        assert_eq!(entries[0].fields, vec![("mood".to_owned(), "good".to_owned())]);
+        assert_eq!(entries[0].body, "Shipped.");
+        assert_eq!(entries[1].when, at("2024-05-11 09:00"));
+        assert_eq!(entries[1].name.as_deref(), Some("ideas"));
+        assert_eq!(entries[1].title, None);
+        assert_eq!(entries[1].body, "Some ideas.");
+    }
+
+    #[test]
+    fn unknown_formats() {
+        assert!(read("evernote", Path::new(".")).is_err());
+    }
+}
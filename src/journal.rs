@@ -0,0 +1,378 @@
+//! A journal as a whole, for using jrni as a library: finding entries by id
+//! or tag, searching them, and creating new ones, with the journal's
+//! config taken into account the way the `jrni` command does.
+//!
+//! ```no_run
+//! use jrni::Journal;
+//!
+//! let journal = Journal::open("/home/me/journal")?;
+//! for e in journal.by_tag("work")? {
+//!     println!("{}", e.title());
+//! }
+//! # Ok::<(), jrni::error::Error>(())
+//! ```
+//!
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use chrono::{DateTime, Local};
+use regex::Regex;
+
+use crate::archive;
+use crate::autotag::AutoTagger;
+use crate::config::Config;
+use crate::datetime;
+use crate::entry_types::{self, EntryType};
+use crate::error::{Error, Result};
+use crate::filedb::{self, Entry};
+use crate::search::Block;
+use crate::storage::{FileStorage, Storage};
+use crate::tags;
+
+
+pub struct Journal {
+    pub path: PathBuf,
+    pub config: Config,
+    /// Where its entries are kept: the directory at `path`, unless given
+    /// otherwise with `with_storage`.
+    storage: Box<dyn Storage>,
+}
+
+/// What to make a new entry from.
+///
+#[derive(Debug)]
+pub struct NewEntry<'a> {
+    /// The entry's name, which its filename and id are made from.
+    pub name: &'a str,
+    /// The entry's id, if it isn't to be made from its name. It's still
+    /// suffixed if another entry has it.
+    pub id: Option<&'a str>,
+    pub kind: Option<&'a EntryType>,
+    /// None for the configured default tags.
+    pub tags: Option<Vec<String>>,
+    pub when: DateTime<Local>,
+    pub body: &'a str,
+    /// Further frontmatter fields, after the standard ones.
+    pub fields: &'a [(String, String)],
+    /// The ids entries in the journal have, if the caller has them already,
+    /// as when making many entries; otherwise they're read from the journal.
+    pub taken_ids: Option<&'a HashSet<String>>,
+}
+
+/// A new entry as it would be written.
+///
+#[derive(Debug)]
+pub struct Draft {
+    pub path: PathBuf,
+    /// The entry's id: its name, suffixed if another entry has that.
+    pub id: String,
+    /// The tags given, or the defaults, with any from autotag rules.
+    pub tags: Vec<String>,
+    pub contents: String,
+}
+
+impl Journal {
+    /// The journal at `path`, with its config (the global one, with the
+    /// journal's own `.jrni.toml` over it).
+    ///
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Journal> {
+        let path = path.as_ref().to_owned();
+        let config = Config::load_for(&path)?;
+        Ok(Journal::with_config(path, config))
+    }
+
+    pub fn with_config<P: AsRef<Path>>(path: P, config: Config) -> Journal {
+        let storage = Box::new(FileStorage::new(path.as_ref()));
+        Journal { path: path.as_ref().to_owned(), config, storage }
+    }
+
+    /// A journal whose entries are kept in `storage`, e.g. a
+    /// `MemoryStorage`, with paths under `path`.
+    ///
+    pub fn with_storage<P, S>(path: P, config: Config, storage: S) -> Journal
+        where P: AsRef<Path>, S: Storage + 'static
+    {
+        Journal { path: path.as_ref().to_owned(), config, storage: Box::new(storage) }
+    }
+
+    /// Every entry, less archived ones. Fails if any file in the journal
+    /// can't be read; see `all_entries_checked` to carry on regardless.
+    ///
+    pub fn entries(&self) -> Result<Vec<Entry>> {
+        Ok(self.storage.entries()?.into_iter()
+            .filter(|e| !archive::is_archived(&self.path, e))
+            .collect())
+    }
+
+    /// Every entry, archived or not, and the errors from those that can't
+    /// be read.
+    ///
+    pub fn all_entries_checked(&self) -> (Vec<Entry>, Vec<Error>) {
+        self.storage.entries_checked()
+    }
+
+    /// The entry with id `id`, archived or not, if there is one; the first
+    /// in the journal if several have it. Fails if it isn't found and some
+    /// file couldn't be read, since that might have been it.
+    ///
+    pub fn by_id(&self, id: &str) -> Result<Option<Entry>> {
+        self.storage.find(&|e| e.get_id() == Some(id))
+    }
+
+    /// Entries tagged `tag` or anything beneath it, sorted by path.
+    ///
+    pub fn by_tag(&self, tag: &str) -> Result<Vec<Entry>> {
+        let tag = tags::normalize(tag);
+        let mut found: Vec<Entry> = self.entries()?.into_iter()
+            .filter(|e| e.frontmatter.tags.iter().any(|t| tags::is_within(t, &tag)))
+            .collect();
+        found.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(found)
+    }
+
+    /// How many entries have each tag, counting those beneath it.
+    ///
+    pub fn tag_counts(&self) -> Result<BTreeMap<String, usize>> {
+        let entries = self.entries()?;
+        Ok(tags::rolled_up_counts(entries.iter().map(|e| e.get_tags().unwrap_or_default())))
+    }
+
+    /// Entries with lines matching `pattern`, sorted by path, with
+    /// `context` lines around each match.
+    ///
+    pub fn search(&self, pattern: &Regex, context: usize) -> Result<Vec<(Entry, Vec<Block>)>> {
+        let entries = self.entries()?;
+        let found: Vec<(PathBuf, Vec<Block>)> = self.storage.search(&entries, pattern, context)
+            .into_iter()
+            .map(|(e, blocks)| (e.path.clone(), blocks))
+            .collect();
+        let mut by_path: HashMap<PathBuf, Entry> = entries.into_iter()
+            .map(|e| (e.path.clone(), e))
+            .collect();
+        Ok(found.into_iter()
+            .filter_map(|(path, blocks)| Some((by_path.remove(&path)?, blocks)))
+            .collect())
+    }
+
+    /// An id based on `base` that no entry has yet; see `filedb::unique_id`.
+    ///
+    pub fn unique_id(&self, base: &str) -> Result<String> {
+        Ok(filedb::unique_id(base, &self.storage.ids()?))
+    }
+
+    /// Where a new entry `name` written at `when` goes; see `entry_path`.
+    ///
+    pub fn entry_path(&self, name: &str, when: DateTime<Local>, kind: Option<&EntryType>) -> Result<PathBuf> {
+        entry_path(&self.path, &self.config, name, when, kind)
+    }
+
+    /// A new entry as it would be written, without writing it.
+    ///
+    pub fn draft(&self, new: &NewEntry) -> Result<Draft> {
+        let taken = match new.taken_ids {
+            Some(_) => None,
+            None => Some(self.storage.ids()?),
+        };
+        let new = NewEntry {
+            tags: new.tags.clone(),
+            taken_ids: new.taken_ids.or(taken.as_ref()),
+            ..*new
+        };
+        draft(&self.path, &self.config, &new)
+    }
+
+    /// Write a new entry into the journal, failing if one's already at its
+    /// path, and return the path.
+    ///
+    pub fn create(&self, new: &NewEntry) -> Result<PathBuf> {
+        let draft = self.draft(new)?;
+        let rel = draft.path.strip_prefix(&self.path).unwrap_or(&draft.path);
+        self.storage.create(rel, &draft.contents)?;
+        Ok(draft.path)
+    }
+}
+
+/// Where a new entry `name` written at `when` goes in the journal at
+/// `jrnl_path`, per the configured `filename` format, with the extension of
+/// its type if it has one.
+///
+pub fn entry_path(
+    jrnl_path: &Path,
+    config: &Config,
+    name: &str,
+    when: DateTime<Local>,
+    kind: Option<&EntryType>,
+) -> Result<PathBuf> {
+    let format = config.filename.as_deref().unwrap_or(filedb::DEFAULT_FILENAME);
+    let mut rel = filedb::entry_filename(format, name, &when)?;
+    if let Some(kind) = kind {
+        rel = entry_types::with_extension(&rel, kind)?;
+    }
+    Ok(jrnl_path.join(config.filenames.sanitize_path(&rel)))
+}
+
+/// A new entry for the journal at `jrnl_path`, as it would be written.
+///
+pub fn draft(jrnl_path: &Path, config: &Config, new: &NewEntry) -> Result<Draft> {
+    let path = entry_path(jrnl_path, config, new.name, new.when, new.kind)?;
+    let base = new.id.unwrap_or(new.name);
+    let id = match new.taken_ids {
+        Some(taken) => filedb::unique_id(base, taken),
+        None => filedb::unique_id(base, &filedb::ids(jrnl_path)?),
+    };
+
+    let mut all_tags = match new.tags {
+        Some(ref tags) => tags::normalize_tags(tags),
+        None => tags::normalize_tags(config.default_tags.iter()),
+    };
+    let tagger = AutoTagger::new(&config.autotag)?;
+    for t in tagger.tags_for(jrnl_path, &path, new.body) {
+        if !all_tags.contains(&t) {
+            all_tags.push(t);
+        }
+    }
+
+    let mut fields = new.fields.to_vec();
+    if let Some(author) = config.default_author() {
+        fields.insert(0, ("author".to_owned(), author));
+    }
+    fields.extend(captured_environment(config));
+
+    let extra: String = fields.iter()
+        .map(|(k, v)| format!("{}: {}\n", k, filedb::yaml_quote(v)))
+        .collect();
+    let contents = format!(
+        "tags: {}\nid: {}\npubdate: {}\n{}---\n\n{}\n",
+        all_tags.join(","), id, datetime::to_str(new.when), extra, new.body);
+    Ok(Draft { path, id, tags: all_tags, contents })
+}
+
+/// Write `draft`, failing if an entry's already at its path, and return the
+/// path.
+///
+pub fn write(draft: &Draft) -> Result<PathBuf> {
+    if let Some(dir) = draft.path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    filedb::create_atomic(&draft.path, &draft.contents)?;
+    Ok(draft.path.clone())
+}
+
+/// Frontmatter fields describing where the entry was written, per the
+/// `[capture]` config. Anything that can't be determined is left out.
+///
+fn captured_environment(config: &Config) -> Vec<(String, String)> {
+    let capture = &config.capture;
+    let mut fields = Vec::new();
+
+    if capture.hostname {
+        if let Some(host) = command_output("hostname", &[]) {
+            fields.push(("hostname".to_owned(), host));
+        }
+    }
+    if capture.git_branch {
+        if let Some(branch) = command_output("git", &["rev-parse", "--abbrev-ref", "HEAD"]) {
+            fields.push(("git_branch".to_owned(), branch));
+        }
+    }
+    if capture.cwd {
+        if let Ok(cwd) = env::current_dir() {
+            fields.push(("cwd".to_owned(), cwd.display().to_string()));
+        }
+    }
+    fields
+}
+
+/// The trimmed stdout of a command, or None if it fails or prints nothing.
+///
+fn command_output(prog: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(prog).args(args).stderr(Stdio::null()).output().ok()?;
+    let out = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+    match output.status.success() && !out.is_empty() {
+        true => Some(out),
+        false => None,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    fn journal() -> Journal {
+        let storage = MemoryStorage::new()
+            .with_entry("standup", &["work/standup"], "2024-05-10 09:00:00.000 +0000", "Shipped it.")
+            .with_entry("review", &["work"], "2024-05-11 09:00:00.000 +0000", "Reviewed the launch.")
+            .with_entry("hike", &["outdoors"], "2024-05-12 09:00:00.000 +0000", "Up the hill.");
+        Journal::with_storage("journal", Config::default(), storage)
+    }
+
+    fn ids(entries: &[Entry]) -> Vec<&str> {
+        entries.iter().filter_map(|e| e.get_id()).collect()
+    }
+
+    #[test]
+    fn by_tag_includes_tags_beneath_it() {
+        let journal = journal();
+        assert_eq!(ids(&journal.by_tag("work").unwrap()), vec!["review", "standup"]);
+        assert_eq!(ids(&journal.by_tag("work/standup").unwrap()), vec!["standup"]);
+        assert!(journal.by_tag("nothing").unwrap().is_empty());
+    }
+
+    #[test]
+    fn tag_counts_roll_up() {
+        let counts = journal().tag_counts().unwrap();
+        assert_eq!(counts.get("work"), Some(&2));
+        assert_eq!(counts.get("work/standup"), Some(&1));
+        assert_eq!(counts.get("outdoors"), Some(&1));
+    }
+
+    #[test]
+    fn by_id_and_search() {
+        let journal = journal();
+        assert_eq!(journal.by_id("hike").unwrap().map(|e| e.title().to_owned()),
+                   Some("hike".to_owned()));
+        assert!(journal.by_id("missing").unwrap().is_none());
+
+        let found = journal.search(&Regex::new("(?i)launch").unwrap(), 0).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0.get_id(), Some("review"));
+    }
+
+    #[test]
+    fn create_takes_a_free_id_without_touching_the_disk() {
+        let journal = journal();
+        let when = Local.with_ymd_and_hms(2024, 5, 13, 9, 0, 0).unwrap();
+        let path = journal.create(&NewEntry {
+            name: "hike",
+            id: None,
+            kind: None,
+            tags: Some(vec!["outdoors".to_owned()]),
+            when,
+            body: "Again.",
+            fields: &[],
+            taken_ids: None,
+        }).unwrap();
+
+        assert!(!path.exists());
+        assert_eq!(journal.by_id("hike-2").unwrap().map(|e| e.body.trim().to_owned()),
+                   Some("Again.".to_owned()));
+        assert_eq!(journal.by_tag("outdoors").unwrap().len(), 2);
+        assert!(journal.create(&NewEntry {
+            name: "hike",
+            id: None,
+            kind: None,
+            tags: None,
+            when,
+            body: "",
+            fields: &[],
+            taken_ids: None,
+        }).is_err());
+    }
+}
@@ -1,8 +1,53 @@
 #[macro_use]
 pub extern crate error_chain;
  
+pub mod activity;
+pub mod anonymize;
+pub mod archive;
+pub mod assets;
+pub mod autotag;
+#[cfg(feature = "backup")]
+pub mod backup;
+pub mod board;
+pub mod config;
+pub mod crypt;
 pub mod datetime;
+pub mod diff;
+pub mod editor;
+pub mod entry_types;
 pub mod filedb;
+pub mod filenames;
+pub mod fmt;
+pub mod footnotes;
+pub mod git;
+pub mod html;
+pub mod import;
+pub mod journal;
+pub mod line_format;
+pub mod lint;
 pub mod error;
+pub mod exif;
+pub mod export;
+pub mod locale;
+pub mod matcher;
+pub mod migrate;
+#[cfg(feature = "tui")]
+pub mod picker;
+pub mod placeholders;
+pub mod prompt;
+pub mod retention;
+pub mod schedule;
+pub mod scope;
+pub mod search;
+pub mod serve;
+pub mod setup;
+pub mod similar;
+pub mod stats;
+pub mod storage;
+pub mod tags;
+pub mod templates;
+pub mod term;
+pub mod transclude;
 
-pub use crate::filedb::{Entry, walk_journal};
+pub use crate::filedb::{Entry, walk_journal, walk_journal_iter};
+pub use crate::journal::Journal;
@@ -0,0 +1,201 @@
+//! A small formatting language for listing lines, as in `jrni ls
+//! --template '{pubdate}  {id:<20} {tags}'`.
+//!
+//! `{field}` is replaced with the field's value. After a colon, a field can
+//! be aligned in a given width (`<` left, `>` right, `^` centered) and cut
+//! to a greatest length with `.N`, so `{title:<30.30}` always takes exactly
+//! 30 columns. `{{` and `}}` are literal braces.
+//!
+use crate::error::Result;
+
+
+#[derive(Debug)]
+enum Piece {
+    Literal(String),
+    Field(Field),
+}
+
+#[derive(Debug)]
+struct Field {
+    name: String,
+    align: Align,
+    width: usize,
+    max: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+/// A parsed line format.
+///
+#[derive(Debug)]
+pub struct LineFormat {
+    pieces: Vec<Piece>,
+}
+
+impl LineFormat {
+    pub fn parse(format: &str) -> Result<LineFormat> {
+        let mut pieces = Vec::new();
+        let mut literal = String::new();
+        let mut chars = format.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                },
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                },
+                '{' => {
+                    let mut spec = String::new();
+                    let mut closed = false;
+                    for c in chars.by_ref() {
+                        if c == '}' {
+                            closed = true;
+                            break;
+                        }
+                        spec.push(c);
+                    }
+                    if !closed {
+                        bail!("unclosed '{{' in '{}'", format);
+                    }
+                    if !literal.is_empty() {
+                        pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+                    }
+                    pieces.push(Piece::Field(Field::parse(&spec)?));
+                },
+                '}' => bail!("unmatched '}}' in '{}'; write '}}}}' for a literal one", format),
+                _ => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            pieces.push(Piece::Literal(literal));
+        }
+        Ok(LineFormat { pieces })
+    }
+
+    /// Names of the fields used, in order.
+    ///
+    pub fn fields(&self) -> Vec<&str> {
+        self.pieces.iter()
+            .filter_map(|p| match p {
+                Piece::Field(f) => Some(f.name.as_str()),
+                Piece::Literal(_) => None,
+            })
+            .collect()
+    }
+
+    /// The line, with each field's value from `value`. Values are put on
+    /// one line, so a line is always one line.
+    ///
+    pub fn render<F: Fn(&str) -> String>(&self, value: F) -> String {
+        let mut out = String::new();
+        for p in self.pieces.iter() {
+            match p {
+                Piece::Literal(s) => out.push_str(s),
+                Piece::Field(f) => out.push_str(&f.render(&value(&f.name))),
+            }
+        }
+        out
+    }
+}
+
+impl Field {
+    /// `name`, or `name:[align][width][.max]`.
+    ///
+    fn parse(spec: &str) -> Result<Field> {
+        let (name, options) = match spec.find(':') {
+            Some(i) => (&spec[..i], &spec[i + 1..]),
+            None => (spec, ""),
+        };
+        let name = name.trim();
+        if name.is_empty() {
+            bail!("'{{{}}}' doesn't name a field", spec);
+        }
+
+        let (align, options) = match options.chars().next() {
+            Some('<') => (Align::Left, &options[1..]),
+            Some('>') => (Align::Right, &options[1..]),
+            Some('^') => (Align::Center, &options[1..]),
+            _ => (Align::Left, options),
+        };
+        let (width, max) = match options.find('.') {
+            Some(i) => (&options[..i], Some(&options[i + 1..])),
+            None => (options, None),
+        };
+        let number = |s: &str| s.parse::<usize>()
+            .map_err(|_| format!("bad width '{}' in '{{{}}}'", s, spec));
+        Ok(Field {
+            name: name.to_owned(),
+            align,
+            width: if width.is_empty() { 0 } else { number(width)? },
+            max: match max {
+                Some(m) => Some(number(m)?),
+                None => None,
+            },
+        })
+    }
+
+    fn render(&self, value: &str) -> String {
+        let mut value: String = value.replace(['\n', '\t'], " ");
+        if let Some(max) = self.max {
+            if value.chars().count() > max {
+                value = value.chars().take(max.saturating_sub(1)).chain(Some('…')).collect();
+            }
+        }
+        let pad = self.width.saturating_sub(value.chars().count());
+        match self.align {
+            Align::Left => format!("{}{}", value, " ".repeat(pad)),
+            Align::Right => format!("{}{}", " ".repeat(pad), value),
+            Align::Center => format!("{}{}{}", " ".repeat(pad / 2), value, " ".repeat(pad - pad / 2)),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(format: &str) -> String {
+        LineFormat::parse(format).unwrap().render(|f| match f {
+            "id" => "standup".to_owned(),
+            "title" => "Weekly\nstandup".to_owned(),
+            _ => String::new(),
+        })
+    }
+
+    #[test]
+    fn fields_are_aligned_and_cut() {
+        assert_eq!(render("{id}: {title}"), "standup: Weekly standup");
+        assert_eq!(render("[{id:<9}]"), "[standup  ]");
+        assert_eq!(render("[{id:>9}]"), "[  standup]");
+        assert_eq!(render("[{id:^10}]"), "[ standup  ]");
+        assert_eq!(render("[{title:<8.8}]"), "[Weekly …]");
+        assert_eq!(render("[{id:.3}]"), "[st…]");
+        assert_eq!(render("[{missing:3}]"), "[   ]");
+    }
+
+    #[test]
+    fn doubled_braces_are_literal() {
+        assert_eq!(render("{{{id}}}"), "{standup}");
+        assert_eq!(LineFormat::parse("{{x}} {id} {title:>5}").unwrap().fields(), vec!["id", "title"]);
+    }
+
+    #[test]
+    fn bad_formats() {
+        assert!(LineFormat::parse("{id").is_err());
+        assert!(LineFormat::parse("id}").is_err());
+        assert!(LineFormat::parse("{}").is_err());
+        assert!(LineFormat::parse("{ :<3}").is_err());
+        assert!(LineFormat::parse("{id:<x}").is_err());
+        assert!(LineFormat::parse("{id:3.y}").is_err());
+    }
+}
@@ -0,0 +1,216 @@
+//! Checks an entry has to pass before it's published: fields it must have,
+//! links that must go somewhere, leftover TODOs, and optionally spelling.
+//! And checks on the journal as a whole, for `jrni check`.
+//!
+//! What's checked is set in the `[publish]` config section:
+//!
+//! ```toml
+//! [publish]
+//! required = ["title", "summary"]
+//! max_todos = 0
+//! check_links = true
+//! spellcheck = "aspell list"
+//! ```
+//!
+//! `spellcheck` is a shell command that reads the body on stdin and prints
+//! the words it doesn't know, a line each, as `aspell list` and
+//! `hunspell -l` do.
+//!
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use std::process::Command;
+
+use crate::config::PublishConfig;
+use crate::crypt::run;
+use crate::error::Result;
+use crate::filedb::Entry;
+
+
+/// Fields required when `required` isn't configured.
+const DEFAULT_REQUIRED: &[&str] = &["title", "summary"];
+
+/// A check an entry failed.
+///
+#[derive(Debug)]
+pub struct Problem {
+    /// Which check, e.g. "links".
+    pub check: &'static str,
+    pub message: String,
+}
+
+/// Everything wrong with `e`, among `entries` (for resolving `[[id]]`
+/// links) in the journal at `jrnl_path`. Empty if it's fit to publish.
+///
+pub fn check(
+    config: &PublishConfig,
+    jrnl_path: &Path,
+    e: &Entry,
+    entries: &[Entry],
+) -> Result<Vec<Problem>> {
+    let mut problems = Vec::new();
+    let mut problem = |check, message| problems.push(Problem { check, message });
+
+    let required: Vec<&str> = match config.required {
+        Some(ref r) => r.iter().map(|f| f.as_str()).collect(),
+        None => DEFAULT_REQUIRED.to_vec(),
+    };
+    for field in required {
+        let present = match e.frontmatter.get(field) {
+            Some(serde_yaml::Value::Null) | None => false,
+            Some(serde_yaml::Value::String(s)) => !s.trim().is_empty(),
+            Some(serde_yaml::Value::Sequence(s)) => !s.is_empty(),
+            Some(_) => true,
+        };
+        if !present {
+            problem("fields", format!("missing '{}'", field));
+        }
+    }
+
+    if config.check_links.unwrap_or(true) {
+        for target in e.local_links() {
+            if e.resolve_link(jrnl_path, target).is_none() {
+                problem("links", format!("broken link to {}", target));
+            }
+        }
+        let ids: HashSet<&str> = entries.iter().filter_map(|e| e.get_id()).collect();
+        for id in e.wiki_links() {
+            if !ids.contains(id) {
+                problem("links", format!("no entry '{}' for [[{}]]", id, id));
+            }
+        }
+    }
+
+    let todos = e.body.lines().filter(|l| l.contains("TODO")).count();
+    let max_todos = config.max_todos.unwrap_or(0);
+    if todos > max_todos {
+        problem("todos", format!("{} lines with TODO, at most {} allowed", todos, max_todos));
+    }
+
+    // An encrypted body isn't there to check, and mustn't be sent off.
+    if let (Some(ref cmd), false) = (&config.spellcheck, e.is_encrypted) {
+        let unknown = spellcheck(cmd, &e.body)?;
+        if !unknown.is_empty() {
+            problem("spelling", format!("unknown words: {}", unknown.join(", ")));
+        }
+    }
+    Ok(problems)
+}
+
+/// Problems with the frontmatter of `entries`, in the journal at
+/// `jrnl_path`: frontmatter that doesn't parse, missing pubdates, ids used
+/// more than once, tags spelled with different casing, and filenames whose
+/// date isn't the pubdate's.
+///
+pub fn check_journal(jrnl_path: &Path, entries: &[Entry]) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let rel = |e: &Entry| e.path.strip_prefix(jrnl_path).unwrap_or(&e.path).display().to_string();
+
+    let mut ids: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    let mut spellings: BTreeMap<String, HashMap<&str, usize>> = BTreeMap::new();
+    for e in entries.iter() {
+        problems.extend(frontmatter_problems(&rel(e), e));
+        if e.frontmatter_err.is_some() {
+            continue;
+        }
+        if let Some(id) = e.get_id() {
+            ids.entry(id).or_default().push(rel(e));
+        }
+        for t in e.get_tags().unwrap_or_default() {
+            *spellings.entry(t.to_lowercase()).or_default()
+                .entry(t).or_insert(0) += 1;
+        }
+    }
+
+    for (id, paths) in ids.iter().filter(|(_, paths)| paths.len() > 1) {
+        problems.push(Problem {
+            check: "ids",
+            message: format!("id '{}' is used by {}", id, paths.join(", ")),
+        });
+    }
+    for spelled in spellings.values().filter(|s| s.len() > 1) {
+        let mut spelled: Vec<String> = spelled.iter()
+            .map(|(t, n)| format!("{} ({})", t, n))
+            .collect();
+        spelled.sort();
+        problems.push(Problem {
+            check: "tags",
+            message: format!("tag spelled differently: {}", spelled.join(", ")),
+        });
+    }
+    problems
+}
+
+/// Problems with just `checked`, such as entries about to be committed:
+/// frontmatter that doesn't parse, a missing or mismatched pubdate, an id
+/// another entry (in `checked` or `others`) already has, and tags that
+/// `known_tag` doesn't know.
+///
+pub fn verify(
+    jrnl_path: &Path,
+    checked: &[Entry],
+    others: &[Entry],
+    known_tag: impl Fn(&str) -> bool,
+) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let rel = |e: &Entry| e.path.strip_prefix(jrnl_path).unwrap_or(&e.path).display().to_string();
+
+    for (i, e) in checked.iter().enumerate() {
+        problems.extend(frontmatter_problems(&rel(e), e));
+        if e.frontmatter_err.is_some() {
+            continue;
+        }
+        if let Some(id) = e.get_id() {
+            let clash = checked[..i].iter().chain(others.iter())
+                .find(|o| o.path != e.path && o.get_id() == Some(id));
+            if let Some(o) = clash {
+                problems.push(Problem {
+                    check: "ids",
+                    message: format!("{}: id '{}' is already used by {}", rel(e), id, rel(o)),
+                });
+            }
+        }
+        for t in e.get_tags().unwrap_or_default().into_iter().filter(|t| !known_tag(t)) {
+            problems.push(Problem {
+                check: "tags",
+                message: format!("{}: tag '{}' isn't in the configured vocabulary", rel(e), t),
+            });
+        }
+    }
+    problems
+}
+
+/// Problems with an entry's frontmatter on its own, naming it `rel`.
+///
+fn frontmatter_problems(rel: &str, e: &Entry) -> Vec<Problem> {
+    if let Some(ref err) = e.frontmatter_err {
+        // Nothing else about its frontmatter can be trusted.
+        return vec![Problem {
+            check: "frontmatter",
+            message: format!("{}: frontmatter doesn't parse: {}", rel, err),
+        }];
+    }
+    match (e.pubdate(), e.filename_date()) {
+        (None, _) => vec![Problem {
+            check: "pubdate",
+            message: format!("{}: no pubdate", rel),
+        }],
+        (Some(p), Some(d)) if p.naive_local().date() != d => vec![Problem {
+            check: "filename",
+            message: format!("{}: filename says {} but pubdate is {}",
+                             rel, d, p.naive_local().date()),
+        }],
+        _ => Vec::new(),
+    }
+}
+
+/// Words in `body` the spellchecker `cmd` doesn't know, each once, in the
+/// order it reports them.
+///
+fn spellcheck(cmd: &str, body: &str) -> Result<Vec<String>> {
+    let out = run(Command::new("sh").arg("-c").arg(cmd), Some(body.as_bytes()))?;
+    let mut seen = HashSet::new();
+    Ok(String::from_utf8_lossy(&out).lines()
+        .map(|w| w.trim().to_owned())
+        .filter(|w| !w.is_empty() && seen.insert(w.clone()))
+        .collect())
+}
@@ -0,0 +1,201 @@
+//! Locale-aware date formatting and a minimal catalog of user-facing
+//! messages.
+//!
+//! Only a handful of languages are known; anything else falls back to
+//! English.
+//!
+use std::env;
+
+use chrono::prelude::*;
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Lang {
+    En,
+    De,
+    Fr,
+    Es,
+}
+
+/// Messages shown to the user. Any `{}` is filled in by the caller via
+/// `Locale::fmt`.
+///
+#[derive(Debug, Clone, Copy)]
+pub enum Msg {
+    EntryNotFound,
+    FileExists,
+    YesNo,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Locale {
+    pub lang: Lang,
+}
+
+impl Default for Locale {
+    fn default() -> Locale {
+        Locale { lang: Lang::En }
+    }
+}
+
+impl Locale {
+    /// Parse a POSIX-ish locale tag like "de_DE.UTF-8" or "fr".
+    ///
+    pub fn from_tag(tag: &str) -> Locale {
+        let prefix = tag.get(..2).unwrap_or("").to_lowercase();
+        let lang = match prefix.as_str() {
+            "de" => Lang::De,
+            "fr" => Lang::Fr,
+            "es" => Lang::Es,
+            _ => Lang::En,
+        };
+        Locale { lang }
+    }
+
+    /// Use the configured locale if there is one, otherwise the usual
+    /// environment variables.
+    ///
+    pub fn detect(configured: Option<&str>) -> Locale {
+        if let Some(tag) = configured {
+            return Locale::from_tag(tag);
+        }
+        for var in &["LC_ALL", "LC_TIME", "LANG"] {
+            match env::var(var) {
+                Ok(ref v) if !v.is_empty() => return Locale::from_tag(v),
+                _ => (),
+            }
+        }
+        Locale::default()
+    }
+
+    /// Format a date with `strftime`-style `fmt`, spelling weekday (`%A`,
+    /// `%a`) and month (`%B`, `%b`) names in this locale.
+    ///
+    pub fn format_date<Tz: TimeZone>(&self, dt: &DateTime<Tz>, fmt: &str) -> String
+        where Tz::Offset: std::fmt::Display
+    {
+        dt.format(&self.localize(dt.weekday(), dt.month0(), fmt)).to_string()
+    }
+
+    /// Like `format_date`, for a day without a time.
+    ///
+    pub fn format_day(&self, day: NaiveDate, fmt: &str) -> String {
+        day.format(&self.localize(day.weekday(), day.month0(), fmt)).to_string()
+    }
+
+    /// `fmt` with its weekday and month names spelled out in this locale.
+    ///
+    fn localize(&self, weekday: Weekday, month0: u32, fmt: &str) -> String {
+        let weekday = weekday.num_days_from_monday() as usize;
+        let month = month0 as usize;
+        let mut localized = String::new();
+        let mut chars = fmt.chars();
+
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                localized.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('A') => localized.push_str(self.weekdays().0[weekday]),
+                Some('a') => localized.push_str(self.weekdays().1[weekday]),
+                Some('B') => localized.push_str(self.months().0[month]),
+                Some('b') => localized.push_str(self.months().1[month]),
+                Some(other) => {
+                    localized.push('%');
+                    localized.push(other);
+                },
+                None => localized.push('%'),
+            }
+        }
+        localized
+    }
+
+    pub fn msg(&self, msg: Msg) -> &'static str {
+        match (msg, self.lang) {
+            (Msg::EntryNotFound, Lang::En) => "Couldn't find entry by id '{}'",
+            (Msg::EntryNotFound, Lang::De) => "Kein Eintrag mit der ID '{}' gefunden",
+            (Msg::EntryNotFound, Lang::Fr) => "Aucune entrée avec l'id '{}'",
+            (Msg::EntryNotFound, Lang::Es) => "No hay ninguna entrada con el id '{}'",
+
+            (Msg::FileExists, Lang::En) => "file with path {} already exists",
+            (Msg::FileExists, Lang::De) => "die Datei {} existiert bereits",
+            (Msg::FileExists, Lang::Fr) => "le fichier {} existe déjà",
+            (Msg::FileExists, Lang::Es) => "el archivo {} ya existe",
+
+            (Msg::YesNo, Lang::En) => "[y/N]",
+            (Msg::YesNo, Lang::De) => "[j/N]",
+            (Msg::YesNo, Lang::Fr) => "[o/N]",
+            (Msg::YesNo, Lang::Es) => "[s/N]",
+        }
+    }
+
+    /// Look up a message and substitute `arg` for its placeholder.
+    ///
+    pub fn fmt(&self, msg: Msg, arg: &str) -> String {
+        self.msg(msg).replacen("{}", arg, 1)
+    }
+
+    /// Whether `answer` means "yes" to a `Msg::YesNo` question. English
+    /// answers are always accepted.
+    ///
+    pub fn is_yes(&self, answer: &str) -> bool {
+        let answer = answer.trim().to_lowercase();
+        let localized: &[&str] = match self.lang {
+            Lang::En => &[],
+            Lang::De => &["j", "ja"],
+            Lang::Fr => &["o", "oui"],
+            Lang::Es => &["s", "si", "sí"],
+        };
+        answer == "y" || answer == "yes" || localized.contains(&answer.as_str())
+    }
+
+    fn weekdays(&self) -> ([&'static str; 7], [&'static str; 7]) {
+        match self.lang {
+            Lang::En => (
+                ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday",
+                 "Saturday", "Sunday"],
+                ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"]),
+            Lang::De => (
+                ["Montag", "Dienstag", "Mittwoch", "Donnerstag", "Freitag",
+                 "Samstag", "Sonntag"],
+                ["Mo", "Di", "Mi", "Do", "Fr", "Sa", "So"]),
+            Lang::Fr => (
+                ["lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi",
+                 "dimanche"],
+                ["lun.", "mar.", "mer.", "jeu.", "ven.", "sam.", "dim."]),
+            Lang::Es => (
+                ["lunes", "martes", "miércoles", "jueves", "viernes",
+                 "sábado", "domingo"],
+                ["lun", "mar", "mié", "jue", "vie", "sáb", "dom"]),
+        }
+    }
+
+    fn months(&self) -> ([&'static str; 12], [&'static str; 12]) {
+        match self.lang {
+            Lang::En => (
+                ["January", "February", "March", "April", "May", "June",
+                 "July", "August", "September", "October", "November",
+                 "December"],
+                ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug",
+                 "Sep", "Oct", "Nov", "Dec"]),
+            Lang::De => (
+                ["Januar", "Februar", "März", "April", "Mai", "Juni", "Juli",
+                 "August", "September", "Oktober", "November", "Dezember"],
+                ["Jan", "Feb", "Mär", "Apr", "Mai", "Jun", "Jul", "Aug",
+                 "Sep", "Okt", "Nov", "Dez"]),
+            Lang::Fr => (
+                ["janvier", "février", "mars", "avril", "mai", "juin",
+                 "juillet", "août", "septembre", "octobre", "novembre",
+                 "décembre"],
+                ["janv.", "févr.", "mars", "avr.", "mai", "juin", "juil.",
+                 "août", "sept.", "oct.", "nov.", "déc."]),
+            Lang::Es => (
+                ["enero", "febrero", "marzo", "abril", "mayo", "junio",
+                 "julio", "agosto", "septiembre", "octubre", "noviembre",
+                 "diciembre"],
+                ["ene", "feb", "mar", "abr", "may", "jun", "jul", "ago",
+                 "sep", "oct", "nov", "dic"]),
+        }
+    }
+}
@@ -0,0 +1,57 @@
+//! Fuzzy matching of short patterns against names, the way editors' file
+//! pickers do it: `stdp` matches `2024-05-10-standup`.
+//!
+use std::cmp::max;
+
+
+/// Characters after which a match counts as the start of a word.
+const SEPARATORS: &[char] = &['-', '_', ' ', '.', '/'];
+
+/// Score `candidate` against `pattern`, a case-insensitive subsequence of
+/// it, or None if it isn't one. Higher is better: matches at the start of a
+/// word or right after the previous match score more, and shorter
+/// candidates are preferred.
+///
+pub fn score(pattern: &str, candidate: &str) -> Option<i64> {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+    if pattern.is_empty() {
+        return Some(0);
+    }
+
+    let mut score = 0;
+    let mut p = 0;
+    let mut last_match: Option<usize> = None;
+    for (i, c) in candidate.iter().enumerate() {
+        if p == pattern.len() {
+            break;
+        }
+        if *c != pattern[p] {
+            continue;
+        }
+        score += 1;
+        if i == 0 || SEPARATORS.contains(&candidate[i - 1]) {
+            score += 8;
+        }
+        if last_match == Some(i.wrapping_sub(1)) {
+            score += 10;
+        }
+        last_match = Some(i);
+        p += 1;
+    }
+
+    match p == pattern.len() {
+        true => Some(score * 100 - candidate.len() as i64),
+        false => None,
+    }
+}
+
+/// The best score of `pattern` against any of `names`.
+///
+pub fn best_score<'a, I>(pattern: &str, names: I) -> Option<i64>
+    where I: IntoIterator<Item = &'a str>
+{
+    names.into_iter()
+        .filter_map(|n| score(pattern, n))
+        .fold(None, |best, s| Some(best.map_or(s, |b| max(b, s))))
+}
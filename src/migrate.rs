@@ -0,0 +1,154 @@
+//! Upgrades between versions of the journal's on-disk format.
+//!
+//! A journal records the format version it's at in `.jrni/format_version`;
+//! one without it is at version 0. Each migration takes a journal from the
+//! version before it to its `to` version, and can say what it would do
+//! before doing anything. Migrations are idempotent, so a journal whose
+//! version file was lost can safely be migrated again.
+//!
+//! Before migrating, the journal is copied to `.jrni/backups/<timestamp>/`.
+//!
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use walkdir::WalkDir;
+
+use crate::activity::{self, STATE_DIR};
+use crate::assets;
+use crate::datetime;
+use crate::error::Result;
+use crate::filedb;
+
+
+/// The format this version of jrni reads and writes.
+pub const FORMAT_VERSION: u32 = 2;
+
+const VERSION_FILE: &str = "format_version";
+
+pub struct Migration {
+    /// The version a journal is at after this migration.
+    pub to: u32,
+    pub summary: &'static str,
+    plan: fn(&Path) -> Result<Vec<String>>,
+    apply: fn(&Path) -> Result<()>,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        to: 1,
+        summary: "move attachments into the content-addressed asset store",
+        plan: plan_asset_store,
+        apply: apply_asset_store,
+    },
+    Migration {
+        to: 2,
+        summary: "rebuild the index, which now records encrypted entries",
+        plan: plan_reindex,
+        apply: apply_reindex,
+    },
+];
+
+impl Migration {
+    /// What applying the migration would change, a line per change.
+    ///
+    pub fn plan(&self, jrnl_path: &Path) -> Result<Vec<String>> {
+        (self.plan)(jrnl_path)
+    }
+}
+
+/// The format version the journal at `jrnl_path` is at.
+///
+pub fn version(jrnl_path: &Path) -> Result<u32> {
+    let path = activity::state_dir(jrnl_path).join(VERSION_FILE);
+    if !path.exists() {
+        return Ok(0);
+    }
+    let raw = fs::read_to_string(&path)?;
+    match raw.trim().parse() {
+        Ok(v) => Ok(v),
+        Err(_) => bail!("{} should hold a version number, not '{}'", path.display(), raw.trim()),
+    }
+}
+
+/// Migrations the journal needs to reach `FORMAT_VERSION`, in order.
+///
+pub fn pending(jrnl_path: &Path) -> Result<Vec<&'static Migration>> {
+    let current = version(jrnl_path)?;
+    if current > FORMAT_VERSION {
+        bail!("the journal is at format version {}, but this jrni only knows up to {}; \
+               upgrade jrni", current, FORMAT_VERSION);
+    }
+    Ok(MIGRATIONS.iter().filter(|m| m.to > current).collect())
+}
+
+/// Back up the journal, then apply `migrations` in order, recording the
+/// version reached after each. Returns where the backup is.
+///
+pub fn run(jrnl_path: &Path, migrations: &[&Migration]) -> Result<PathBuf> {
+    let backup = backup(jrnl_path)?;
+    for m in migrations.iter() {
+        (m.apply)(jrnl_path)?;
+        set_version(jrnl_path, m.to)?;
+    }
+    Ok(backup)
+}
+
+/// Mark a journal as being at `version`, e.g. a new one at `FORMAT_VERSION`.
+///
+pub fn set_version(jrnl_path: &Path, version: u32) -> Result<()> {
+    fs::create_dir_all(activity::state_dir(jrnl_path))?;
+    fs::write(activity::state_dir(jrnl_path).join(VERSION_FILE), format!("{}\n", version))?;
+    Ok(())
+}
+
+/// Copy everything in the journal but jrni's own state into a new directory
+/// under `.jrni/backups/`.
+///
+fn backup(jrnl_path: &Path) -> Result<PathBuf> {
+    let dest = activity::state_dir(jrnl_path).join("backups")
+        .join(datetime::now().format("%Y%m%dT%H%M%S").to_string());
+    let walker = WalkDir::new(jrnl_path)
+        .into_iter()
+        .filter_entry(|d| d.file_name() != STATE_DIR);
+    for dent in walker {
+        let dent = dent?;
+        let rel = dent.path().strip_prefix(jrnl_path).unwrap();
+        if dent.file_type().is_dir() {
+            fs::create_dir_all(dest.join(rel))?;
+        } else if dent.file_type().is_file() {
+            fs::copy(dent.path(), dest.join(rel))?;
+        }
+    }
+    Ok(dest)
+}
+
+fn plan_asset_store(jrnl_path: &Path) -> Result<Vec<String>> {
+    Ok(assets::unstored(jrnl_path)?.into_iter()
+        .map(|m| format!("move {} to {}", m.from, m.to))
+        .collect())
+}
+
+fn apply_asset_store(jrnl_path: &Path) -> Result<()> {
+    let moves = assets::unstored(jrnl_path)?;
+    if !moves.is_empty() {
+        assets::migrate(jrnl_path, &moves, &filedb::load_all_entries(jrnl_path)?)?;
+    }
+    Ok(())
+}
+
+fn plan_reindex(jrnl_path: &Path) -> Result<Vec<String>> {
+    let index = activity::state_dir(jrnl_path).join(filedb::INDEX_FILE);
+    Ok(match index.exists() {
+        true => vec![format!("rebuild {}", index.display())],
+        false => Vec::new(),
+    })
+}
+
+fn apply_reindex(jrnl_path: &Path) -> Result<()> {
+    let index = activity::state_dir(jrnl_path).join(filedb::INDEX_FILE);
+    if index.exists() {
+        fs::remove_file(&index)?;
+    }
+    filedb::load_entries(jrnl_path);
+    Ok(())
+}
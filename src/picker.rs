@@ -0,0 +1,121 @@
+//! A minimal full-screen picker for choosing an entry in the terminal.
+//!
+//! Entries are listed newest first. Typing filters them: every word typed
+//! has to appear (ignoring case) in an entry's date, id, title or tags.
+//! Up/Down (or Ctrl-P/Ctrl-N) move the selection, Enter chooses, and Esc or
+//! Ctrl-C gives up.
+//!
+//! The picker draws on stderr, so stdout stays free for printing the choice.
+//!
+use std::io::{self, Stderr, Write};
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+
+use crate::error::Result;
+use crate::filedb::Entry;
+
+
+/// Let the user choose one of `entries`, which should already be in the
+/// order to list them. None if they gave up.
+///
+pub fn pick<'a>(entries: &[&'a Entry]) -> Result<Option<&'a Entry>> {
+    let lines: Vec<String> = entries.iter().map(|e| line(e)).collect();
+    let haystacks: Vec<String> = entries.iter().zip(lines.iter())
+        .map(|(e, l)| format!("{} {}", l, e.get_tags().unwrap_or_default().join(" ")).to_lowercase())
+        .collect();
+
+    let mut screen = Screen::enter()?;
+    let mut query = String::new();
+    let mut selected = 0;
+
+    loop {
+        let terms: Vec<String> = query.to_lowercase().split_whitespace().map(String::from).collect();
+        let shown: Vec<usize> = (0..entries.len())
+            .filter(|i| terms.iter().all(|t| haystacks[*i].contains(t.as_str())))
+            .collect();
+        selected = selected.min(shown.len().saturating_sub(1));
+        screen.draw(&query, &shown.iter().map(|i| lines[*i].as_str()).collect::<Vec<_>>(), selected)?;
+
+        let key = match event::read()? {
+            Event::Key(k) if k.kind == KeyEventKind::Press => k,
+            _ => continue,
+        };
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return Ok(None),
+            KeyCode::Char('c') if ctrl => return Ok(None),
+            KeyCode::Enter => return Ok(shown.get(selected).map(|i| entries[*i])),
+            KeyCode::Up => selected = selected.saturating_sub(1),
+            KeyCode::Char('p') if ctrl => selected = selected.saturating_sub(1),
+            KeyCode::Down => selected += 1,
+            KeyCode::Char('n') if ctrl => selected += 1,
+            KeyCode::Backspace => { query.pop(); },
+            KeyCode::Char(c) if !ctrl => {
+                query.push(c);
+                selected = 0;
+            },
+            _ => (),
+        }
+    }
+}
+
+/// How an entry is listed: its date, id and title.
+///
+fn line(e: &Entry) -> String {
+    let date = e.date().map(|d| d.to_string()).unwrap_or_default();
+    match e.get_id() {
+        Some(id) => format!("{:10}  {}  {}", date, id, e.title()),
+        None => format!("{:10}  {}", date, e.title()),
+    }
+}
+
+/// The terminal, in raw mode on the alternate screen until dropped.
+///
+struct Screen {
+    out: Stderr,
+}
+
+impl Screen {
+    fn enter() -> Result<Screen> {
+        let mut out = io::stderr();
+        terminal::enable_raw_mode()?;
+        execute!(out, EnterAlternateScreen, Hide)?;
+        Ok(Screen { out })
+    }
+
+    /// Draw the query on the first line and as many of `lines` as fit below
+    /// it, scrolled so that `selected` is visible.
+    ///
+    fn draw(&mut self, query: &str, lines: &[&str], selected: usize) -> Result<()> {
+        let (width, height) = terminal::size()?;
+        let rows = (height as usize).saturating_sub(1);
+        let first = (selected + 1).saturating_sub(rows);
+
+        queue!(self.out, Clear(ClearType::All), MoveTo(0, 0),
+               Print(format!("> {}", query)))?;
+        for (row, (i, text)) in lines.iter().enumerate().skip(first).take(rows).enumerate() {
+            let text: String = text.chars().take(width as usize).collect();
+            queue!(self.out, MoveTo(0, row as u16 + 1))?;
+            if i == selected {
+                queue!(self.out, SetAttribute(Attribute::Reverse), Print(text),
+                       SetAttribute(Attribute::Reset))?;
+            } else {
+                queue!(self.out, Print(text))?;
+            }
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        // Nothing more can be done if this fails; the shell's `reset` will.
+        let _ = execute!(self.out, Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
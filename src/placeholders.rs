@@ -0,0 +1,66 @@
+//! Placeholders in entry bodies that are computed when an entry is rendered
+//! rather than stored, e.g. `{{days_since "2020-03-01"}}`.
+//!
+//! The supported placeholders are
+//!
+//!   - `{{age_of_entry}}`: days since the entry's pubdate,
+//!   - `{{backlinks_count}}`: how many other entries mention it as `[[id]]`,
+//!   - `{{days_since "YYYY-MM-DD"}}`: days since a date.
+//!
+//! Anything else, or a placeholder that can't be computed, is left as is.
+//!
+use chrono::NaiveDate;
+use regex::{Captures, Regex};
+
+use crate::filedb::Entry;
+
+
+pub struct Placeholders<'a> {
+    /// Entries to count backlinks from.
+    entries: &'a [Entry],
+    today: NaiveDate,
+    pattern: Regex,
+}
+
+impl<'a> Placeholders<'a> {
+    pub fn new(entries: &'a [Entry], today: NaiveDate) -> Placeholders<'a> {
+        Placeholders {
+            entries,
+            today,
+            pattern: Regex::new(r#"\{\{\s*(\w+)(?:\s+"([^"]*)")?\s*\}\}"#).unwrap(),
+        }
+    }
+
+    /// `body` (belonging to `e`) with its placeholders filled in.
+    ///
+    pub fn render(&self, e: &Entry, body: &str) -> String {
+        self.pattern.replace_all(body, |caps: &Captures| {
+            let arg = caps.get(2).map(|a| a.as_str());
+            match self.compute(e, &caps[1], arg) {
+                Some(value) => value,
+                None => caps[0].to_owned(),
+            }
+        }).into_owned()
+    }
+
+    fn compute(&self, e: &Entry, name: &str, arg: Option<&str>) -> Option<String> {
+        match (name, arg) {
+            ("age_of_entry", None) => {
+                let published = e.pubdate()?.naive_local().date();
+                Some((self.today - published).num_days().to_string())
+            },
+            ("backlinks_count", None) => {
+                let id = e.get_id()?;
+                let count = self.entries.iter()
+                    .filter(|other| other.path != e.path && other.wiki_links().contains(&id))
+                    .count();
+                Some(count.to_string())
+            },
+            ("days_since", Some(date)) => {
+                let since = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+                Some((self.today - since).num_days().to_string())
+            },
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,62 @@
+//! Interactive prompts shared by subcommands.
+//!
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::config::Config;
+use crate::error::Result;
+use crate::locale::{Locale, Msg};
+
+
+/// Decides whether a destructive or bulk operation may proceed.
+///
+/// Every such command should go through here rather than prompting on its
+/// own, so that `--yes` and the `[confirm]` config section behave the same
+/// everywhere.
+///
+#[derive(Debug)]
+pub struct Confirm {
+    assume_yes: bool,
+    defaults: HashMap<String, bool>,
+    locale: Locale,
+}
+
+impl Confirm {
+    pub fn new(assume_yes: bool, config: &Config, locale: Locale) -> Confirm {
+        Confirm { assume_yes, defaults: config.confirm.clone(), locale }
+    }
+
+    /// Ask whether `command` may go ahead with `action`, unless the user has
+    /// opted out of confirmation for it.
+    ///
+    pub fn ask(&self, command: &str, action: &str) -> Result<bool> {
+        let wants_prompt = self.defaults.get(command).cloned().unwrap_or(true);
+        if self.assume_yes || !wants_prompt {
+            return Ok(true);
+        }
+        ask_yes_no(action, &self.locale)
+    }
+}
+
+/// Print a yes/no question to stderr and read the answer from stdin.
+/// Anything other than a "yes" in `locale` (including EOF) means no.
+///
+pub fn ask_yes_no(question: &str, locale: &Locale) -> Result<bool> {
+    eprint!("{} {} ", question, locale.msg(Msg::YesNo));
+    io::stderr().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(locale.is_yes(&answer))
+}
+
+/// Print a question to stderr and read a one-line answer from stdin.
+///
+pub fn ask(question: &str) -> Result<String> {
+    eprint!("{} ", question);
+    io::stderr().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(answer.trim().to_owned())
+}
@@ -0,0 +1,172 @@
+//! Retention policies: entries with some tag deleted or archived once
+//! they're old enough, e.g. scratch notes after a month.
+//!
+//! ```toml
+//! [[retention]]
+//! tag = "scratch"
+//! after = "30d"
+//! action = "delete"
+//!
+//! [[retention]]
+//! tag = "clip"
+//! after = "90d"
+//! action = "archive"
+//! ```
+//!
+//! A rule for a tag covers the tags beneath it too, so `scratch` covers
+//! `scratch/ideas`.
+//!
+//! An entry's age is counted from its date (see `Entry::date`); entries
+//! without one are never touched. Archived entries are moved under
+//! `archive/`; see `archive`.
+//!
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+use crate::archive;
+use crate::datetime;
+use crate::error::Result;
+use crate::filedb::Entry;
+use crate::tags;
+
+
+#[derive(Debug, Deserialize)]
+pub struct Rule {
+    pub tag: String,
+    /// How old entries must be, e.g. "30d"; see `datetime::parse_relative`.
+    pub after: String,
+    pub action: Action,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Delete,
+    Archive,
+}
+
+impl Action {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Action::Delete => "delete",
+            Action::Archive => "archive",
+        }
+    }
+}
+
+/// What `rules` would do to `entries` in the journal at `jrnl_path` as of
+/// `today`. Where several rules apply to an entry, deleting wins.
+///
+pub fn plan<'a>(
+    rules: &[Rule],
+    jrnl_path: &Path,
+    entries: &'a [Entry],
+    today: NaiveDate,
+) -> Result<Vec<(Action, &'a Entry)>> {
+    let mut cutoffs = Vec::new();
+    for r in rules.iter() {
+        match today.checked_sub_signed(datetime::parse_relative(&r.after)?) {
+            Some(cutoff) => cutoffs.push((r, cutoff)),
+            // Nothing can be that old.
+            None => continue,
+        }
+    }
+
+    let mut planned = Vec::new();
+    for e in entries.iter() {
+        let date = match e.date() {
+            Some(d) => d,
+            None => continue,
+        };
+        let tags = e.get_tags().unwrap_or_default();
+        let archived = archive::is_archived(jrnl_path, e);
+        let action = cutoffs.iter()
+            .filter(|(r, cutoff)| date < *cutoff && tags.iter().any(|t| tags::is_within(t, &r.tag)))
+            .map(|(r, _)| r.action)
+            .filter(|a| !(archived && *a == Action::Archive))
+            .min_by_key(|a| match a { Action::Delete => 0, Action::Archive => 1 });
+        if let Some(action) = action {
+            planned.push((action, e));
+        }
+    }
+    Ok(planned)
+}
+
+/// Carry out `planned`, returning where each archived entry went.
+///
+pub fn apply(jrnl_path: &Path, planned: &[(Action, &Entry)]) -> Result<Vec<PathBuf>> {
+    let mut moved = Vec::new();
+    for (action, e) in planned.iter() {
+        match action {
+            Action::Delete => fs::remove_file(&e.path)?,
+            Action::Archive => moved.push(archive::archive(jrnl_path, &e.path)?),
+        }
+    }
+    Ok(moved)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str, tags: &str) -> Entry {
+        Entry::parse(Path::new(path), &format!("tags: {}\n---\n\nbody\n", tags))
+    }
+
+    fn rule(tag: &str, after: &str, action: Action) -> Rule {
+        Rule { tag: tag.to_owned(), after: after.to_owned(), action }
+    }
+
+    fn planned(rules: &[Rule], entries: &[Entry]) -> Vec<(Action, String)> {
+        let today = NaiveDate::from_ymd_opt(2024, 5, 10).unwrap();
+        plan(rules, Path::new("/j"), entries, today).unwrap().into_iter()
+            .map(|(a, e)| (a, e.path.display().to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn rules_cover_old_entries_with_the_tag_or_one_beneath_it() {
+        let rules = [rule("scratch", "30d", Action::Delete)];
+        let entries = [
+            entry("/j/2024-01-01-old.md", "scratch/ideas"),
+            entry("/j/2024-05-01-new.md", "scratch"),
+            entry("/j/2024-01-01-kept.md", "scratchpad"),
+            entry("/j/undated.md", "scratch"),
+        ];
+        assert_eq!(planned(&rules, &entries),
+                   vec![(Action::Delete, "/j/2024-01-01-old.md".to_owned())]);
+    }
+
+    #[test]
+    fn deleting_wins_and_archived_entries_stay_put() {
+        let rules = [
+            rule("clip", "30d", Action::Archive),
+            rule("scratch", "30d", Action::Delete),
+        ];
+        let entries = [
+            entry("/j/2024-01-01-both.md", "clip,scratch"),
+            entry("/j/2024-01-01-clip.md", "clip"),
+            entry("/j/archive/2024-01-01-clip.md", "clip"),
+            entry("/j/archive/2024-01-02-both.md", "clip,scratch"),
+        ];
+        assert_eq!(planned(&rules, &entries), vec![
+            (Action::Delete, "/j/2024-01-01-both.md".to_owned()),
+            (Action::Archive, "/j/2024-01-01-clip.md".to_owned()),
+            (Action::Delete, "/j/archive/2024-01-02-both.md".to_owned()),
+        ]);
+    }
+
+    #[test]
+    fn bad_or_endless_ages() {
+        let entries = [entry("/j/2024-01-01-old.md", "scratch")];
+        let today = NaiveDate::from_ymd_opt(2024, 5, 10).unwrap();
+        let bad = [rule("scratch", "30 days", Action::Delete)];
+        assert!(plan(&bad, Path::new("/j"), &entries, today).is_err());
+        // Nothing is that old, so there's nothing to do.
+        assert!(planned(&[rule("scratch", "100000000y", Action::Delete)], &entries).is_empty());
+    }
+}
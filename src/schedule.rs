@@ -0,0 +1,143 @@
+//! Recurring jrni actions, declared in the config and rendered as crontab
+//! lines or systemd timer units for the user to install:
+//!
+//! ```toml
+//! [[schedule]]
+//! name = "nightly-backup"
+//! when = "daily 02:30"
+//! args = ["backup"]
+//! ```
+//!
+//! `when` is one of `hourly`, `daily HH:MM`, `weekly <weekday> HH:MM`, or
+//! `monthly <day> HH:MM`.
+//!
+use chrono::Weekday;
+
+use crate::config::ScheduledAction;
+use crate::error::Result;
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum When {
+    Hourly,
+    Daily { hour: u32, minute: u32 },
+    Weekly { day: Weekday, hour: u32, minute: u32 },
+    Monthly { day: u32, hour: u32, minute: u32 },
+}
+
+impl When {
+    pub fn parse(s: &str) -> Result<When> {
+        let lowered = s.trim().to_lowercase();
+        let words: Vec<&str> = lowered.split_whitespace().collect();
+        let when = match words.as_slice() {
+            ["hourly"] => Some(When::Hourly),
+            ["daily", time] => parse_time(time)
+                .map(|(hour, minute)| When::Daily { hour, minute }),
+            ["weekly", day, time] => match (day.parse::<Weekday>().ok(), parse_time(time)) {
+                (Some(day), Some((hour, minute))) => Some(When::Weekly { day, hour, minute }),
+                _ => None,
+            },
+            ["monthly", day, time] => match (day.parse::<u32>().ok(), parse_time(time)) {
+                (Some(day), Some((hour, minute))) if (1..=28).contains(&day) =>
+                    Some(When::Monthly { day, hour, minute }),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        match when {
+            Some(w) => Ok(w),
+            None => bail!("can't understand schedule '{}'; try e.g. \"daily 20:00\", \
+                           \"weekly sun 18:00\", or \"monthly 1 09:00\" (days 1-28)", s),
+        }
+    }
+
+    /// The first five fields of a crontab line.
+    ///
+    pub fn cron(&self) -> String {
+        match *self {
+            When::Hourly => "0 * * * *".to_owned(),
+            When::Daily { hour, minute } => format!("{} {} * * *", minute, hour),
+            When::Weekly { day, hour, minute } =>
+                format!("{} {} * * {}", minute, hour, day.num_days_from_sunday()),
+            When::Monthly { day, hour, minute } =>
+                format!("{} {} {} * *", minute, hour, day),
+        }
+    }
+
+    /// A systemd `OnCalendar=` expression.
+    ///
+    pub fn on_calendar(&self) -> String {
+        match *self {
+            When::Hourly => "hourly".to_owned(),
+            When::Daily { hour, minute } => format!("*-*-* {:02}:{:02}:00", hour, minute),
+            When::Weekly { day, hour, minute } =>
+                format!("{:?} *-*-* {:02}:{:02}:00", day, hour, minute),
+            When::Monthly { day, hour, minute } =>
+                format!("*-*-{:02} {:02}:{:02}:00", day, hour, minute),
+        }
+    }
+}
+
+fn parse_time(s: &str) -> Option<(u32, u32)> {
+    let mut parts = s.splitn(2, ':');
+    let hour: u32 = parts.next()?.parse().ok()?;
+    let minute: u32 = parts.next()?.parse().ok()?;
+    if hour < 24 && minute < 60 { Some((hour, minute)) } else { None }
+}
+
+/// Shell-quote an argument if it needs it.
+///
+fn quote(arg: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c);
+    if !arg.is_empty() && arg.chars().all(safe) {
+        arg.to_owned()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+fn command_line(jrni: &str, jrnl_path: &str, action: &ScheduledAction) -> String {
+    let mut words = vec![quote(jrni), "-p".to_owned(), quote(jrnl_path)];
+    words.extend(action.args.iter().map(|a| quote(a)));
+    words.join(" ")
+}
+
+/// A crontab snippet covering every action.
+///
+pub fn crontab(jrni: &str, jrnl_path: &str, actions: &[ScheduledAction]) -> Result<String> {
+    let mut out = String::from("# jrni scheduled actions\n");
+    for action in actions {
+        let when = When::parse(&action.when)?;
+        // cron reads a bare `%` as a newline.
+        out.push_str(&format!("# {}\n{} {}\n",
+            action.name, when.cron(), command_line(jrni, jrnl_path, action).replace('%', "\\%")));
+    }
+    Ok(out)
+}
+
+/// A `.service` and `.timer` unit per action, each preceded by a comment
+/// naming the file it belongs in (under `~/.config/systemd/user/`).
+///
+pub fn systemd(jrni: &str, jrnl_path: &str, actions: &[ScheduledAction]) -> Result<String> {
+    let mut out = String::new();
+    for action in actions {
+        let when = When::parse(&action.when)?;
+        let unit = format!("jrni-{}", action.name);
+        out.push_str(&format!(
+            "# {unit}.service\n\
+             [Unit]\nDescription=jrni: {name}\n\n\
+             [Service]\nType=oneshot\nExecStart={cmd}\n\n\
+             # {unit}.timer\n\
+             [Unit]\nDescription=jrni: {name} ({when})\n\n\
+             [Timer]\nOnCalendar={calendar}\nPersistent=true\n\n\
+             [Install]\nWantedBy=timers.target\n\n",
+            unit = unit,
+            name = action.name,
+            when = action.when,
+            // systemd reads `%` as the start of a specifier.
+            cmd = command_line(jrni, jrnl_path, action).replace('%', "%%"),
+            calendar = when.on_calendar()));
+    }
+    Ok(out)
+}
@@ -0,0 +1,76 @@
+//! Who gets to see which entries, based on tag namespaces.
+//!
+//! The `[visibility]` config section maps tag namespaces to the widest
+//! audience entries tagged with them may reach:
+//!
+//! ```toml
+//! [visibility]
+//! private = "local"
+//! team = "team"
+//! public = "public"
+//! ```
+//!
+//! An entry is only as visible as its most restrictive tag allows, and one
+//! no rule covers stays local unless `default_visibility` says otherwise, so
+//! nothing is published that wasn't meant to be. Anything
+//! that publishes entries (serving, exports, feeds) must filter through a
+//! `Scope` for its audience.
+//!
+use serde::Deserialize;
+
+use crate::config::Config;
+use crate::filedb::Entry;
+
+
+/// Audiences, from narrowest to widest.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    /// Never leaves this machine.
+    Local,
+    Team,
+    Public,
+}
+
+#[derive(Debug)]
+pub struct Scope {
+    /// (namespace, visibility), longest namespace first.
+    rules: Vec<(String, Visibility)>,
+    default: Visibility,
+}
+
+impl Scope {
+    pub fn from_config(config: &Config) -> Scope {
+        let mut rules: Vec<(String, Visibility)> = config.visibility.iter()
+            .map(|(ns, v)| (ns.trim_end_matches('/').to_owned(), *v))
+            .collect();
+        rules.sort_by(|a, b| b.0.len().cmp(&a.0.len()).then(a.0.cmp(&b.0)));
+
+        Scope {
+            rules,
+            default: config.default_visibility.unwrap_or(Visibility::Local),
+        }
+    }
+
+    /// The widest audience an entry may be shown to.
+    ///
+    pub fn visibility(&self, e: &Entry) -> Visibility {
+        e.get_tags().unwrap_or_default().into_iter()
+            .filter_map(|t| self.tag_visibility(t))
+            .min()
+            .unwrap_or(self.default)
+    }
+
+    pub fn allows(&self, e: &Entry, audience: Visibility) -> bool {
+        self.visibility(e) >= audience
+    }
+
+    /// The rule for the most specific namespace containing `tag`, if any.
+    ///
+    fn tag_visibility(&self, tag: &str) -> Option<Visibility> {
+        self.rules.iter()
+            .find(|(ns, _)| tag == ns.as_str() || tag.starts_with(&format!("{}/", ns)))
+            .map(|(_, v)| *v)
+    }
+}
@@ -0,0 +1,124 @@
+//! Full-text search over entries, frontmatter included.
+//!
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use regex::Regex;
+
+use crate::error::Result;
+use crate::filedb::{self, Entry};
+
+
+/// A run of lines from an entry's file around one or more matches.
+///
+#[derive(Debug)]
+pub struct Block {
+    /// (line number starting at 1, whether it matched, text)
+    pub lines: Vec<(usize, bool, String)>,
+}
+
+/// Those of `entries` with lines matching `pattern`, sorted by path, with
+/// `context` lines around each match.
+///
+/// Each file is searched as it is on disk, frontmatter and all, in parallel
+/// via `filedb::map_parallel`. Only the frontmatter of encrypted entries is
+/// searched, since their bodies are ciphertext.
+///
+pub fn search<'a>(entries: &'a [Entry], pattern: &Regex, context: usize) -> Vec<(&'a Entry, Vec<Block>)> {
+    // How many lines of each file to search.
+    let limits: Arc<HashMap<PathBuf, usize>> = Arc::new(entries.iter()
+        .map(|e| (e.path.clone(), searched_lines(e)))
+        .collect());
+    let by_path: HashMap<&Path, &Entry> = entries.iter().map(|e| (e.path.as_path(), e)).collect();
+    let pattern = pattern.clone();
+
+    let mut results: Vec<(&Entry, Vec<Block>)> = filedb::map_parallel(
+            entries.iter().map(|e| e.path.clone()).collect::<Vec<_>>(),
+            move |p| {
+                let text = BufReader::new(File::open(&p)?);
+                let found = blocks(text, &pattern, context, limits[&p])?;
+                Ok((p, found))
+            })
+        .into_iter()
+        .filter_map(|r| r.ok())
+        .filter(|(_, found)| !found.is_empty())
+        .filter_map(|(p, found)| Some((*by_path.get(p.as_path())?, found)))
+        .collect();
+
+    results.sort_by(|a, b| a.0.path.cmp(&b.0.path));
+    results
+}
+
+/// Like `search`, but with each entry's text got from `read`, one entry at
+/// a time, for entries that aren't files; see `Storage::search`.
+///
+pub fn search_with<'a>(
+    entries: &'a [Entry],
+    pattern: &Regex,
+    context: usize,
+    read: &dyn Fn(&Path) -> Result<String>,
+) -> Vec<(&'a Entry, Vec<Block>)> {
+    let mut results: Vec<(&Entry, Vec<Block>)> = entries.iter()
+        .filter_map(|e| {
+            let text = read(&e.path).ok()?;
+            let found = blocks(text.as_bytes(), pattern, context, searched_lines(e)).ok()?;
+            Some((e, found))
+        })
+        .filter(|(_, found)| !found.is_empty())
+        .collect();
+    results.sort_by(|a, b| a.0.path.cmp(&b.0.path));
+    results
+}
+
+/// How many lines of `e`'s text to search: just the frontmatter if its body
+/// is encrypted.
+///
+fn searched_lines(e: &Entry) -> usize {
+    match e.is_encrypted {
+        true => e.body_line - 1,
+        false => usize::MAX,
+    }
+}
+
+/// Group matching lines among the first `limit` read from `text` into
+/// blocks, merging any whose context would overlap. Only the last `context`
+/// lines are held on to while looking for the next match.
+///
+fn blocks<R: BufRead>(text: R, pattern: &Regex, context: usize, limit: usize) -> io::Result<Vec<Block>> {
+    let mut blocks = Vec::new();
+    let mut current: Option<Block> = None;
+    // Lines since the current block's trailing context ended.
+    let mut before: VecDeque<(usize, String)> = VecDeque::with_capacity(context);
+    // Lines of trailing context still to add to the current block.
+    let mut after = 0;
+
+    for (i, line) in text.lines().take(limit).enumerate() {
+        let line = line?;
+        if pattern.is_match(&line) {
+            let block = current.get_or_insert_with(|| Block { lines: Vec::new() });
+            block.lines.extend(before.drain(..).map(|(n, l)| (n, false, l)));
+            block.lines.push((i + 1, true, line));
+            after = context;
+        } else if after > 0 {
+            if let Some(block) = current.as_mut() {
+                block.lines.push((i + 1, false, line));
+            }
+            after -= 1;
+        } else {
+            // A line falling out of reach of the next match's context
+            // separates it from the current block.
+            if before.len() == context {
+                before.pop_front();
+                blocks.extend(current.take());
+            }
+            if context > 0 {
+                before.push_back((i + 1, line));
+            }
+        }
+    }
+    blocks.extend(current);
+    Ok(blocks)
+}
@@ -0,0 +1,332 @@
+//! A small, read-only web view of the journal, so that teammates can browse
+//! a shared journal without any write access.
+//!
+//! Entries are re-read on every request, so the view is always current.
+//!
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::error::Result;
+use crate::export;
+use crate::filedb::Entry;
+use crate::datetime;
+use crate::html;
+use crate::placeholders::Placeholders;
+use crate::scope::{Scope, Visibility};
+use crate::storage::{FileStorage, Storage};
+use crate::tags;
+use crate::transclude::Transcluder;
+
+
+/// How long a client may take to send its request, or to take the response,
+/// before it's dropped so the next can be served.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The most of a request that's read: its request line and headers.
+const MAX_REQUEST: u64 = 64 * 1024;
+
+pub struct Server {
+    jrnl_path: PathBuf,
+    storage: FileStorage,
+    /// The `Authorization` header clients must send, if any.
+    auth_header: Option<String>,
+    scope: Scope,
+    audience: Visibility,
+}
+
+struct Response {
+    status: &'static str,
+    headers: Vec<(&'static str, String)>,
+    body: String,
+}
+
+impl Response {
+    fn ok(body: String) -> Response {
+        Response { status: "200 OK", headers: Vec::new(), body }
+    }
+
+    fn error(status: &'static str) -> Response {
+        let body = html::page(status, &format!("<h1>{}</h1>", status));
+        Response { status, headers: Vec::new(), body }
+    }
+
+    fn write_to(&self, stream: &mut TcpStream, head_only: bool) -> Result<()> {
+        write!(stream, "HTTP/1.1 {}\r\n", self.status)?;
+        write!(stream, "Content-Type: text/html; charset=utf-8\r\n")?;
+        write!(stream, "Content-Length: {}\r\n", self.body.len())?;
+        write!(stream, "Connection: close\r\n")?;
+        for (name, value) in self.headers.iter() {
+            write!(stream, "{}: {}\r\n", name, value)?;
+        }
+        write!(stream, "\r\n")?;
+        if !head_only {
+            stream.write_all(self.body.as_bytes())?;
+        }
+        Ok(stream.flush()?)
+    }
+}
+
+impl Server {
+    /// `auth`, if given, is the `user:password` clients must present via
+    /// HTTP basic auth. Only entries `scope` allows `audience` to see are
+    /// served.
+    ///
+    pub fn new(
+        jrnl_path: &Path,
+        auth: Option<&str>,
+        scope: Scope,
+        audience: Visibility,
+    ) -> Server {
+        Server {
+            jrnl_path: jrnl_path.to_owned(),
+            storage: FileStorage::new(jrnl_path),
+            auth_header: auth.map(|a| format!("Basic {}", base64(a.as_bytes()))),
+            scope,
+            audience,
+        }
+    }
+
+    /// Serve requests on `addr` forever, one at a time.
+    ///
+    pub fn run(&self, addr: &str) -> Result<()> {
+        let listener = TcpListener::bind(addr)?;
+        eprintln!("serving {} at http://{}/", self.jrnl_path.display(), addr);
+
+        for stream in listener.incoming() {
+            let handled = match stream {
+                Ok(s) => self.handle(s),
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = handled {
+                eprintln!("error handling request: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    fn handle(&self, mut stream: TcpStream) -> Result<()> {
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        let mut reader = BufReader::new(stream.try_clone()?.take(MAX_REQUEST));
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+
+        let mut authorized = self.auth_header.is_none();
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+                break;
+            }
+            let mut parts = line.splitn(2, ':');
+            let name = parts.next().unwrap_or("").trim();
+            let value = parts.next().unwrap_or("").trim();
+            if name.eq_ignore_ascii_case("authorization") {
+                if let Some(ref expected) = self.auth_header {
+                    authorized |= constant_time_eq(expected.as_bytes(), value.as_bytes());
+                }
+            }
+        }
+
+        let mut parts = request_line.split_whitespace();
+        let method = parts.next().unwrap_or("");
+        let target = parts.next().unwrap_or("/");
+
+        let response = if !authorized {
+            let mut r = Response::error("401 Unauthorized");
+            r.headers.push(("WWW-Authenticate", "Basic realm=\"jrni\"".to_owned()));
+            r
+        } else if method != "GET" && method != "HEAD" {
+            let mut r = Response::error("405 Method Not Allowed");
+            r.headers.push(("Allow", "GET, HEAD".to_owned()));
+            r
+        } else {
+            self.route(target)
+        };
+
+        response.write_to(&mut stream, method == "HEAD")
+    }
+
+    fn route(&self, target: &str) -> Response {
+        let (path, query) = match target.find('?') {
+            Some(i) => (&target[..i], &target[i + 1..]),
+            None => (target, ""),
+        };
+        let entries = self.entries();
+
+        if path == "/" {
+            return Response::ok(self.index(&entries, query));
+        }
+        if let Some(rel) = path.strip_prefix("/e/") {
+            let wanted = percent_decode(rel, false);
+            if let Some(e) = entries.iter().find(|e| self.relative(e) == wanted) {
+                return match self.entry_page(e, &entries) {
+                    Ok(page) => Response::ok(page),
+                    Err(err) => {
+                        eprintln!("error rendering {}: {}", wanted, err);
+                        Response::error("500 Internal Server Error")
+                    },
+                };
+            }
+        }
+        Response::error("404 Not Found")
+    }
+
+    /// All entries visible to the audience, newest first.
+    ///
+    fn entries(&self) -> Vec<Entry> {
+        let (entries, errors) = self.storage.entries_checked();
+        for e in errors.iter() {
+            eprintln!("warning: {}", e);
+        }
+        let mut entries = export::publishable(entries, &self.scope, self.audience);
+        entries.reverse();
+        entries
+    }
+
+    fn relative(&self, e: &Entry) -> String {
+        e.path.strip_prefix(&self.jrnl_path).unwrap_or(&e.path)
+            .to_string_lossy().replace('\\', "/")
+    }
+
+    /// List entries, optionally filtered by `?tag=` and `?author=`.
+    ///
+    fn index(&self, entries: &[Entry], query: &str) -> String {
+        let mut tag = None;
+        let mut author = None;
+        for pair in query.split('&') {
+            let mut kv = pair.splitn(2, '=');
+            let key = kv.next().unwrap_or("");
+            let value = percent_decode(kv.next().unwrap_or(""), true);
+            match key {
+                "tag" => tag = Some(value),
+                "author" => author = Some(value),
+                _ => (),
+            }
+        }
+
+        let mut heading = String::from("Journal");
+        if let Some(ref t) = tag {
+            heading.push_str(&format!(" &middot; tagged {}", html::escape(t)));
+        }
+        if let Some(ref a) = author {
+            heading.push_str(&format!(" &middot; by {}", html::escape(a)));
+        }
+
+        let mut list = String::new();
+        for e in entries.iter() {
+            let tags = e.get_tags().unwrap_or_default();
+            if tag.as_ref().is_some_and(|t| !tags.iter().any(|e_t| tags::is_within(e_t, t))) {
+                continue;
+            }
+            if author.is_some() && e.author() != author.as_deref() {
+                continue;
+            }
+            list.push_str(&format!(
+                "<li><a href=\"/e/{}\">{}</a> {}</li>\n",
+                percent_encode(&self.relative(e)),
+                html::escape(e.title()),
+                self.meta(e)));
+        }
+
+        html::page("Journal", &format!(
+            "<h1>{}</h1>\n<p><a href=\"/\">all entries</a></p>\n<ul>\n{}</ul>",
+            heading, list))
+    }
+
+    /// Render an entry, transcluding from any of the other visible `entries`.
+    ///
+    fn entry_page(&self, e: &Entry, entries: &[Entry]) -> Result<String> {
+        let title = e.title();
+        let body = Transcluder::new(entries).expand(e)?;
+        let today = datetime::now().naive_local().date();
+        let body = Placeholders::new(entries, today).render(e, &body);
+        Ok(html::described_page(title, e.summary(), &format!(
+            "<p><a href=\"/\">&larr; all entries</a></p>\n<h1>{}</h1>\n<p>{}</p>\n{}",
+            html::escape(title), self.meta(e), html::safe_markdown(&body))))
+    }
+
+    /// Date, author and tag links for an entry.
+    ///
+    fn meta(&self, e: &Entry) -> String {
+        let mut meta = Vec::new();
+        if let Some(d) = e.pubdate() {
+            meta.push(d.format("%F").to_string());
+        }
+        if let Some(a) = e.author() {
+            meta.push(format!("<a href=\"/?author={}\">{}</a>",
+                percent_encode(a), html::escape(a)));
+        }
+        for t in e.get_tags().unwrap_or_default() {
+            meta.push(format!("<a href=\"/?tag={}\">#{}</a>",
+                percent_encode(t), html::escape(t)));
+        }
+        meta.join(" ")
+    }
+}
+
+fn percent_encode(s: &str) -> String {
+    let mut out = String::new();
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9'
+                | b'-' | b'.' | b'_' | b'~' | b'/' => out.push(b as char),
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+fn percent_decode(s: &str, plus_as_space: bool) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                out.push(b);
+                i += 3;
+                continue;
+            },
+            (b'+', _) if plus_as_space => out.push(b' '),
+            (b, _) => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Compare `a` and `b` in time that depends only on their lengths, so a
+/// client can't guess the password a byte at a time.
+///
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    let mut diff = a.len() ^ b.len();
+    for (i, x) in a.iter().enumerate() {
+        diff |= (x ^ b.get(i).unwrap_or(&0)) as usize;
+    }
+    diff == 0
+}
+
+fn base64(input: &[u8]) -> String {
+    const CHARS: &[u8] =
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+
+    for chunk in input.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(CHARS[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
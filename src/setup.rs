@@ -0,0 +1,186 @@
+//! Setting up jrni for the first time: `jrni init` creates the journal and
+//! writes the config for it, asking about each setting with
+//! `--interactive` (as bare `jrni` does, when there's no config or journal
+//! yet).
+//!
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use chrono::Local;
+use toml::Value;
+
+use crate::config;
+use crate::crypt::run;
+use crate::error::Result;
+use crate::filedb;
+use crate::git;
+use crate::locale::Locale;
+use crate::prompt;
+use crate::templates;
+
+
+/// Layouts offered for new entries' filenames, with what each is for.
+pub const FILENAMES: &[(&str, &str)] = &[
+    (filedb::DEFAULT_FILENAME, "everything in one directory"),
+    ("{date:%Y/%m}/{slug}.md", "a directory per month"),
+    ("{date:%Y}/{date:%m-%d}-{slug}.md", "a directory per year"),
+];
+
+/// What a starter template holds, for a default template that doesn't
+/// exist yet.
+const STARTER_TEMPLATE: &str = "tags: \n---\n\n## What happened\n\n## What's next\n";
+
+/// The choices made about a new journal.
+///
+#[derive(Debug)]
+pub struct Setup {
+    pub path: PathBuf,
+    pub editor: Option<String>,
+    /// Template for new entries given no `--template`.
+    pub default_template: Option<String>,
+    /// How new entries are named; see `filedb::entry_filename`.
+    pub filename: String,
+    pub git: bool,
+    /// age (recipient, identity file) for encrypted entries.
+    pub encrypt: Option<(String, String)>,
+}
+
+impl Setup {
+    /// The defaults for everything, with the journal at `path`.
+    ///
+    pub fn defaults(path: &Path) -> Setup {
+        Setup {
+            path: path.to_owned(),
+            editor: None,
+            default_template: None,
+            filename: filedb::DEFAULT_FILENAME.to_owned(),
+            git: false,
+            encrypt: None,
+        }
+    }
+
+    /// Ask about each setting on the terminal, suggesting `default_path`
+    /// for the journal.
+    ///
+    pub fn ask(default_path: &Path, locale: &Locale) -> Result<Setup> {
+        eprintln!("Setting up jrni. Press enter to take the default in brackets.\n");
+
+        let path = ask_or("Where should the journal live?", &default_path.display().to_string())?;
+        let path = config::expand_home(&path);
+
+        let editor = prompt::ask("Editor (blank for $EDITOR):")?;
+        let editor = if editor.is_empty() { None } else { Some(editor) };
+
+        eprintln!("\nHow should new entries be named?");
+        for (i, (format, about)) in FILENAMES.iter().enumerate() {
+            let example = filedb::entry_filename(format, "standup", &Local::now())?;
+            eprintln!("  {}. {:<28} {}", i + 1, example.display(), about);
+        }
+        let filename = loop {
+            let answer = ask_or("Pick one, or give a layout of your own like {date:%F}-{slug}.md:", "1")?;
+            let format = match answer.parse::<usize>() {
+                Ok(n) if n >= 1 && n <= FILENAMES.len() => FILENAMES[n - 1].0.to_owned(),
+                _ => answer,
+            };
+            match filedb::entry_filename(&format, "standup", &Local::now()) {
+                Ok(_) => break format,
+                Err(e) => eprintln!("{}", e),
+            }
+        };
+
+        let existing = templates::list(&templates::template_dir(&path))?;
+        if !existing.is_empty() {
+            eprintln!("\nTemplates: {}", existing.join(", "));
+        }
+        let template = prompt::ask("Template for new entries (blank for none):")?;
+        let default_template = if template.is_empty() { None } else { Some(template) };
+
+        let git = prompt::ask_yes_no("Keep the journal in git, committing each change?", locale)?;
+
+        let encrypt = match prompt::ask_yes_no("Encrypt entries with age?", locale)? {
+            true => {
+                let default_identity = config::Config::default_path()
+                    .map(|p| p.with_file_name("age.key").display().to_string())
+                    .unwrap_or_default();
+                let identity = ask_or("age identity (key) file; made if it doesn't exist:",
+                                      &default_identity)?;
+                Some((String::new(), identity))
+            },
+            false => None,
+        };
+
+        Ok(Setup { path, editor, default_template, filename, git, encrypt })
+    }
+
+    /// The config file for these settings.
+    ///
+    pub fn to_toml(&self) -> String {
+        let mut out = String::from("# Written by `jrni init`; see the README for other settings.\n\n");
+        setting(&mut out, "path", Some(self.path.to_string_lossy().as_ref()));
+        setting(&mut out, "editor", self.editor.as_deref());
+        setting(&mut out, "filename", Some(&self.filename));
+        setting(&mut out, "default_template", self.default_template.as_deref());
+        if self.git {
+            out.push_str("git = true\n");
+        }
+        if let Some((ref recipient, ref identity)) = self.encrypt {
+            out.push_str("\n[encrypt]\n");
+            setting(&mut out, "recipient", Some(recipient));
+            setting(&mut out, "identity", Some(identity));
+        }
+        out
+    }
+
+    /// Create the journal and whatever goes with it, and write the config
+    /// to `config_path`.
+    ///
+    pub fn apply(mut self, config_path: &Path) -> Result<()> {
+        fs::create_dir_all(&self.path)?;
+
+        if let Some(ref name) = self.default_template {
+            let dir = templates::template_dir(&self.path);
+            let path = templates::template_path(&dir, name);
+            if !path.exists() {
+                fs::create_dir_all(&dir)?;
+                fs::write(&path, STARTER_TEMPLATE)?;
+                eprintln!("wrote a starter template to {}", path.display());
+            }
+        }
+
+        if self.git && !git::is_repo(&self.path) {
+            git::init(&self.path)?;
+        }
+
+        if let Some((ref mut recipient, ref identity)) = self.encrypt {
+            let key = config::expand_home(identity);
+            if !key.exists() {
+                if let Some(dir) = key.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                run(Command::new("age-keygen").arg("-o").arg(&key), None)?;
+                eprintln!("made an age key at {}; back it up, or encrypted entries are lost with it",
+                          key.display());
+            }
+            let public = run(Command::new("age-keygen").arg("-y").arg(&key), None)?;
+            *recipient = String::from_utf8_lossy(&public).trim().to_owned();
+        }
+
+        if let Some(dir) = config_path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(config_path, self.to_toml())?;
+        Ok(())
+    }
+}
+
+fn ask_or(question: &str, default: &str) -> Result<String> {
+    let answer = prompt::ask(&format!("{} [{}]", question, default))?;
+    Ok(if answer.is_empty() { default.to_owned() } else { answer })
+}
+
+fn setting(out: &mut String, key: &str, value: Option<&str>) {
+    if let Some(v) = value {
+        out.push_str(&format!("{} = {}\n", key, Value::String(v.to_owned())));
+    }
+}
@@ -0,0 +1,152 @@
+//! Finding entries about the same thing as another, by comparing embeddings
+//! of their text.
+//!
+//! Embeddings come from an external command (configured as `embedder`),
+//! which reads text on stdin and prints its embedding as a JSON array of
+//! numbers, or as whitespace-separated numbers. They're cached in
+//! `.jrni/embeddings`, a JSON object per line, keyed by a hash of the
+//! command and the text, so an entry is only embedded again once it changes
+//! (or the command does). Each time the cache is written, embeddings that
+//! weren't used are dropped, so it doesn't grow with every edit.
+//!
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+
+use crate::activity;
+use crate::crypt::run;
+use crate::error::Result;
+use crate::filedb::{self, Entry};
+
+
+const CACHE_FILE: &str = "embeddings";
+
+#[derive(Serialize, Deserialize)]
+struct CacheRecord {
+    key: String,
+    vector: Vec<f32>,
+}
+
+pub struct Embedder {
+    command: String,
+    cache_path: PathBuf,
+    cache: HashMap<String, Vec<f32>>,
+    /// Keys of the embeddings asked for, which are all that's kept.
+    used: HashSet<String>,
+    /// Whether anything's been added to the cache since it was read.
+    dirty: bool,
+}
+
+impl Embedder {
+    pub fn new(jrnl_path: &Path, command: &str) -> Embedder {
+        let cache_path = activity::state_dir(jrnl_path).join(CACHE_FILE);
+        let cache = fs::read_to_string(&cache_path).unwrap_or_default()
+            .lines()
+            .filter_map(|l| serde_json::from_str::<CacheRecord>(l).ok())
+            .map(|r| (r.key, r.vector))
+            .collect();
+        Embedder {
+            command: command.to_owned(),
+            cache_path,
+            cache,
+            used: HashSet::new(),
+            dirty: false,
+        }
+    }
+
+    /// The embedding of `e`'s title and body, from the cache if it's there.
+    ///
+    pub fn embed(&mut self, e: &Entry) -> Result<Vec<f32>> {
+        let text = match e.frontmatter.title {
+            Some(ref title) => format!("{}\n\n{}", title, e.body.trim()),
+            None => e.body.trim().to_owned(),
+        };
+        let key = filedb::digest(format!("{}\0{}", self.command, text).as_bytes());
+        self.used.insert(key.clone());
+        if let Some(v) = self.cache.get(&key) {
+            return Ok(v.clone());
+        }
+
+        let out = run(Command::new("sh").arg("-c").arg(&self.command), Some(text.as_bytes()))?;
+        let vector = parse_vector(&String::from_utf8_lossy(&out))?;
+        self.cache.insert(key, vector.clone());
+        self.dirty = true;
+        Ok(vector)
+    }
+
+    /// Write out the cache, if anything's been added to it, and with `prune`,
+    /// drop the embeddings that weren't used. Don't prune after a failure
+    /// part way through, or the rest would have to be embedded again.
+    ///
+    pub fn save(&self, prune: bool) -> Result<()> {
+        let pruned = prune && self.cache.len() > self.used.len();
+        if !self.dirty && !pruned {
+            return Ok(());
+        }
+        fs::create_dir_all(self.cache_path.parent().unwrap())?;
+        let tmp = self.cache_path.with_extension(format!("tmp{}", std::process::id()));
+        let mut f = BufWriter::new(fs::File::create(&tmp)?);
+        for (key, vector) in self.cache.iter().filter(|(k, _)| !prune || self.used.contains(*k)) {
+            let record = CacheRecord { key: key.clone(), vector: vector.clone() };
+            writeln!(f, "{}", serde_json::to_string(&record)?)?;
+        }
+        f.flush()?;
+        drop(f);
+        fs::rename(&tmp, &self.cache_path)?;
+        Ok(())
+    }
+}
+
+/// The `n` of `candidates` most similar to `target`, most similar first,
+/// with their cosine similarity to it. Encrypted entries, and `target`
+/// itself, are left out.
+///
+pub fn most_similar<'a>(
+    embedder: &mut Embedder,
+    target: &Entry,
+    candidates: &'a [Entry],
+    n: usize,
+) -> Result<Vec<(f32, &'a Entry)>> {
+    let wanted = embedder.embed(target)?;
+    let mut scored = Vec::new();
+    for e in candidates.iter().filter(|e| e.path != target.path && !e.is_encrypted) {
+        scored.push((cosine(&wanted, &embedder.embed(e)?), e));
+    }
+    scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal)
+        .then(a.1.path.cmp(&b.1.path)));
+    scored.truncate(n);
+    Ok(scored)
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norms = a.iter().map(|x| x * x).sum::<f32>().sqrt()
+        * b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norms == 0.0 {
+        return 0.0;
+    }
+    dot / norms
+}
+
+/// An embedding printed as a JSON array, or as numbers separated by
+/// whitespace.
+///
+fn parse_vector(out: &str) -> Result<Vec<f32>> {
+    let out = out.trim();
+    let vector: Option<Vec<f32>> = match out.starts_with('[') {
+        true => serde_json::from_str(out).ok(),
+        false => out.split_whitespace().map(|n| n.parse().ok()).collect(),
+    };
+    match vector {
+        Some(v) if !v.is_empty() => Ok(v),
+        _ => bail!("the embedder should print a list of numbers, not '{}'",
+                   out.chars().take(40).collect::<String>()),
+    }
+}
@@ -0,0 +1,428 @@
+//! Aggregates over entries, grouped by a frontmatter field, so numeric
+//! fields (expenses, km run, hours) become reportable, and metrics about
+//! journaling habits (words today, the current streak) to check rules
+//! against, or to summarize in a report.
+//!
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use chrono::{Datelike, Duration, NaiveDate};
+use serde_yaml::Value as YValue;
+
+use crate::datetime::Period;
+use crate::error::Result;
+use crate::filedb::{self, Entry};
+use crate::tags;
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Agg {
+    /// Number of entries.
+    Count,
+    /// Total words in entry bodies.
+    Words,
+    /// Sum of a numeric field.
+    Sum(String),
+    /// Mean of a numeric field, over entries that have it.
+    Avg(String),
+}
+
+impl Agg {
+    /// Parse `count`, `words`, `avg:<field>`, or `<field>` (summed).
+    ///
+    pub fn parse(s: &str) -> Result<Agg> {
+        let s = s.trim();
+        Ok(match s {
+            "" => bail!("empty aggregate"),
+            "count" => Agg::Count,
+            "words" => Agg::Words,
+            _ if s.starts_with("avg:") => Agg::Avg(s[4..].to_owned()),
+            _ if s.starts_with("sum:") => Agg::Sum(s[4..].to_owned()),
+            _ => Agg::Sum(s.to_owned()),
+        })
+    }
+}
+
+impl fmt::Display for Agg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Agg::Count => write!(f, "count"),
+            Agg::Words => write!(f, "words"),
+            Agg::Sum(field) => write!(f, "{}", field),
+            Agg::Avg(field) => write!(f, "avg:{}", field),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Row {
+    pub group: String,
+    /// One value per requested aggregate, in order: None for an average of
+    /// no values.
+    pub values: Vec<Option<f64>>,
+}
+
+/// A frontmatter field as a number, if it is one.
+///
+pub fn number(e: &Entry, field: &str) -> Option<f64> {
+    match e.frontmatter.get(field)? {
+        YValue::Number(n) => n.as_f64(),
+        YValue::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// The groups `e` belongs to under `field`: one per item if the field is a
+/// list (like tags), `-` if it's missing.
+///
+fn groups(e: &Entry, field: &str) -> Vec<String> {
+    match e.frontmatter.get(field) {
+        Some(YValue::Sequence(ref items)) if !items.is_empty() =>
+            items.iter().map(filedb::inline_value).collect(),
+        Some(YValue::Sequence(_)) | Some(YValue::Null) | None => vec!["-".to_owned()],
+        Some(ref v) => vec![filedb::inline_value(v)],
+    }
+}
+
+/// Compute `aggs` over `entries`, grouped by the value of `group_by` (or all
+/// together), with groups in sorted order.
+///
+pub fn aggregate<'a, I>(entries: I, group_by: Option<&str>, aggs: &[Agg]) -> Vec<Row>
+    where I: IntoIterator<Item = &'a Entry>
+{
+    // group -> (sum, number of values) per aggregate
+    let mut acc: BTreeMap<String, Vec<(f64, usize)>> = BTreeMap::new();
+
+    for e in entries {
+        let keys = match group_by {
+            Some(field) => groups(e, field),
+            None => vec!["all".to_owned()],
+        };
+        for key in keys {
+            let slots = acc.entry(key).or_insert_with(|| vec![(0.0, 0); aggs.len()]);
+            for (agg, slot) in aggs.iter().zip(slots.iter_mut()) {
+                let value = match agg {
+                    Agg::Count => Some(1.0),
+                    Agg::Words => Some(e.word_count() as f64),
+                    Agg::Sum(field) | Agg::Avg(field) => number(e, field),
+                };
+                if let Some(v) = value {
+                    slot.0 += v;
+                    slot.1 += 1;
+                }
+            }
+        }
+    }
+
+    acc.into_iter().map(|(group, slots)| Row {
+        group,
+        values: aggs.iter().zip(slots).map(|(agg, (sum, n))| match agg {
+            Agg::Avg(_) if n == 0 => None,
+            Agg::Avg(_) => Some(sum / n as f64),
+            _ => Some(sum),
+        }).collect(),
+    }).collect()
+}
+
+/// Show whole numbers without decimals and everything else with two.
+///
+pub fn format_number(v: f64) -> String {
+    match v.fract() == 0.0 {
+        true => format!("{}", v as i64),
+        false => format!("{:.2}", v),
+    }
+}
+
+/// Metrics `metric` knows how to compute.
+pub const METRICS: &[&str] = &[
+    "words_today", "entries_today", "words_week", "entries_week",
+    "streak", "longest_streak", "days_since_entry",
+];
+
+/// The current and longest runs of consecutive days with an entry. The
+/// current streak counts back from today, or from yesterday if there's no
+/// entry today yet.
+///
+pub fn streaks(days: &BTreeSet<NaiveDate>, today: NaiveDate) -> (usize, usize) {
+    let mut longest = 0;
+    let mut run = 0;
+    let mut prev: Option<NaiveDate> = None;
+    for d in days.iter() {
+        run = match prev {
+            Some(p) if *d - p == Duration::days(1) => run + 1,
+            _ => 1,
+        };
+        longest = longest.max(run);
+        prev = Some(*d);
+    }
+
+    let mut day = match days.contains(&today) {
+        true => today,
+        false => today - Duration::days(1),
+    };
+    let mut current = 0;
+    while days.contains(&day) {
+        current += 1;
+        day -= Duration::days(1);
+    }
+    (current, longest)
+}
+
+/// Compute one of `METRICS` over `entries` as of `today`. Entries are dated
+/// by their pubdate. `today_words` and `today_entries` are accepted too.
+///
+pub fn metric(name: &str, entries: &[Entry], today: NaiveDate) -> Result<f64> {
+    let dated: Vec<(NaiveDate, &Entry)> = entries.iter()
+        .filter_map(|e| Some((e.pubdate()?.naive_local().date(), e)))
+        .collect();
+    let week_start = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+    let words = |since: NaiveDate| dated.iter()
+        .filter(|(d, _)| *d >= since && *d <= today)
+        .map(|(_, e)| e.word_count())
+        .sum::<usize>() as f64;
+    let count = |since: NaiveDate| dated.iter()
+        .filter(|(d, _)| *d >= since && *d <= today)
+        .count() as f64;
+    let days: BTreeSet<NaiveDate> = dated.iter().map(|(d, _)| *d).collect();
+
+    Ok(match name {
+        "words_today" | "today_words" => words(today),
+        "entries_today" | "today_entries" => count(today),
+        "words_week" => words(week_start),
+        "entries_week" => count(week_start),
+        "streak" => streaks(&days, today).0 as f64,
+        "longest_streak" => streaks(&days, today).1 as f64,
+        "days_since_entry" => match days.range(..=today).next_back() {
+            Some(last) => (today - *last).num_days() as f64,
+            None => bail!("no dated entries yet"),
+        },
+        _ => bail!("unknown metric '{}'; try one of {}", name, METRICS.join(", ")),
+    })
+}
+
+/// An overview of writing habits over some entries.
+///
+#[derive(Debug)]
+pub struct Summary {
+    pub entries: usize,
+    pub words: usize,
+    /// Words written each month, keyed by the month's first day.
+    pub words_by_month: BTreeMap<NaiveDate, usize>,
+    /// Words written each ISO week, keyed by the week's Monday.
+    pub words_by_week: BTreeMap<NaiveDate, usize>,
+    pub streak: usize,
+    pub longest_streak: usize,
+    /// The most used tags and how many entries use them or a tag beneath
+    /// them, most used first.
+    pub top_tags: Vec<(String, usize)>,
+}
+
+impl Summary {
+    /// Mean words per entry.
+    ///
+    pub fn average_words(&self) -> f64 {
+        match self.entries {
+            0 => 0.0,
+            n => self.words as f64 / n as f64,
+        }
+    }
+}
+
+/// Summarize `entries` as of `today`, listing up to `top` tags. Entries
+/// are dated by pubdate, or failing that their filename; undated ones only
+/// count towards the totals.
+///
+pub fn summarize(entries: &[Entry], today: NaiveDate, top: usize) -> Summary {
+    let mut words_by_month: BTreeMap<NaiveDate, usize> = BTreeMap::new();
+    let mut words_by_week: BTreeMap<NaiveDate, usize> = BTreeMap::new();
+    let mut days = BTreeSet::new();
+    let mut words = 0;
+
+    for e in entries.iter() {
+        let n = e.word_count();
+        words += n;
+        if let Some(d) = e.date() {
+            *words_by_month.entry(d.with_day(1).unwrap_or(d)).or_insert(0) += n;
+            if let Ok(week) = Period::week(d) {
+                *words_by_week.entry(week.start).or_insert(0) += n;
+            }
+            days.insert(d);
+        }
+    }
+
+    // Counted as `jrni t` counts them, so a tag includes those beneath it.
+    let counts = tags::rolled_up_counts(entries.iter().map(|e| e.get_tags().unwrap_or_default()));
+    let mut top_tags: Vec<(String, usize)> = counts.into_iter().collect();
+    // Stable, so ties stay alphabetical.
+    top_tags.sort_by_key(|t| std::cmp::Reverse(t.1));
+    top_tags.truncate(top);
+
+    let (streak, longest_streak) = streaks(&days, today);
+    Summary {
+        entries: entries.len(),
+        words,
+        words_by_month,
+        words_by_week,
+        streak,
+        longest_streak,
+        top_tags,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Ge,
+    Le,
+    Gt,
+    Lt,
+    Eq,
+    Ne,
+}
+
+/// A threshold on a metric, e.g. `words_today >= 200`.
+///
+#[derive(Debug)]
+pub struct Rule {
+    pub metric: String,
+    op: Op,
+    threshold: f64,
+}
+
+impl Rule {
+    pub fn parse(s: &str) -> Result<Rule> {
+        // Two-character operators first, so `>=` isn't read as `>`.
+        let ops = [(">=", Op::Ge), ("<=", Op::Le), ("==", Op::Eq), ("!=", Op::Ne),
+                   (">", Op::Gt), ("<", Op::Lt)];
+        for (token, op) in ops.iter() {
+            if let Some(idx) = s.find(token) {
+                let metric = s[..idx].trim();
+                let threshold = s[idx + token.len()..].trim();
+                return match threshold.parse() {
+                    Ok(threshold) if !metric.is_empty() =>
+                        Ok(Rule { metric: metric.to_owned(), op: *op, threshold }),
+                    _ => bail!("can't parse rule '{}'", s),
+                };
+            }
+        }
+        bail!("can't parse rule '{}'; expected e.g. 'streak >= 3'", s)
+    }
+
+    pub fn holds(&self, value: f64) -> bool {
+        match self.op {
+            Op::Ge => value >= self.threshold,
+            Op::Le => value <= self.threshold,
+            Op::Gt => value > self.threshold,
+            Op::Lt => value < self.threshold,
+            Op::Eq => value == self.threshold,
+            Op::Ne => value != self.threshold,
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let op = match self.op {
+            Op::Ge => ">=",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Lt => "<",
+            Op::Eq => "==",
+            Op::Ne => "!=",
+        };
+        write!(f, "{} {} {}", self.metric, op, format_number(self.threshold))
+    }
+}
+
+/// Fill in `{metric}` placeholders in `format`, e.g. `{streak}d {words_today}w`.
+///
+pub fn render_status(format: &str, entries: &[Entry], today: NaiveDate) -> Result<String> {
+    let mut out = String::new();
+    let mut rest = format;
+    while let Some(open) = rest.find('{') {
+        let close = match rest[open..].find('}') {
+            Some(i) => open + i,
+            None => bail!("unclosed '{{' in status format"),
+        };
+        out.push_str(&rest[..open]);
+        out.push_str(&format_number(metric(&rest[open + 1..close], entries, today)?));
+        rest = &rest[close + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn entry(name: &str, tags: &str, pubdate: &str, body: &str) -> Entry {
+        let raw = format!("tags: {}\npubdate: {}\n---\n\n{}\n", tags, pubdate, body);
+        Entry::parse(Path::new(name), &raw)
+    }
+
+    fn day(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn top_tags_roll_up_like_jrni_t() {
+        let entries = vec![
+            entry("a.md", "work/meetings", "2024-05-10 09:00:00.000 +0000", "one two"),
+            entry("b.md", "work,work/code", "2024-05-11 09:00:00.000 +0000", "three"),
+            entry("c.md", "home", "2024-05-13 09:00:00.000 +0000", "four"),
+        ];
+        let s = summarize(&entries, day(2024, 5, 13), 2);
+        assert_eq!(s.top_tags, vec![("work".to_owned(), 2), ("home".to_owned(), 1)]);
+        assert_eq!(s.words, 4);
+        assert_eq!(s.words_by_week[&day(2024, 5, 6)], 3);
+        assert_eq!(s.words_by_week[&day(2024, 5, 13)], 1);
+        assert_eq!(s.streak, 1);
+    }
+
+    #[test]
+    fn rules_parse_and_hold() {
+        let r = Rule::parse(" words_today >= 200 ").unwrap();
+        assert_eq!(r.to_string(), "words_today >= 200");
+        assert!(r.holds(200.0));
+        assert!(!r.holds(199.0));
+        assert!(Rule::parse("streak<3").unwrap().holds(2.0));
+        assert!(!Rule::parse("streak != 1.5").unwrap().holds(1.5));
+        assert_eq!(Rule::parse("streak == 1.5").unwrap().to_string(), "streak == 1.50");
+        assert!(Rule::parse("streak").is_err());
+        assert!(Rule::parse(">= 3").is_err());
+        assert!(Rule::parse("streak >= lots").is_err());
+    }
+
+    #[test]
+    fn streaks_count_back_from_today_or_yesterday() {
+        let days: BTreeSet<NaiveDate> =
+            [day(2024, 5, 1), day(2024, 5, 2), day(2024, 5, 3), day(2024, 5, 8), day(2024, 5, 9)]
+            .iter().cloned().collect();
+        assert_eq!(streaks(&days, day(2024, 5, 9)), (2, 3));
+        assert_eq!(streaks(&days, day(2024, 5, 10)), (2, 3));
+        assert_eq!(streaks(&days, day(2024, 5, 11)), (0, 3));
+        assert_eq!(streaks(&BTreeSet::new(), day(2024, 5, 11)), (0, 0));
+    }
+
+    #[test]
+    fn metrics_as_of_a_day() {
+        // 2024-05-13 is a Monday.
+        let entries = vec![
+            entry("a.md", "", "2024-05-11 12:00:00.000 +0000", "one two"),
+            entry("b.md", "", "2024-05-13 12:00:00.000 +0000", "three four five"),
+            entry("c.md", "", "2024-05-14 12:00:00.000 +0000", "six"),
+            Entry::parse(Path::new("2024-05-14-undated.md"), "seven\n"),
+        ];
+        let m = |name: &str, d| metric(name, &entries, d).unwrap();
+        assert_eq!(m("words_today", day(2024, 5, 13)), 3.0);
+        assert_eq!(m("today_entries", day(2024, 5, 13)), 1.0);
+        assert_eq!(m("words_week", day(2024, 5, 14)), 4.0);
+        assert_eq!(m("entries_week", day(2024, 5, 12)), 1.0);
+        assert_eq!(m("streak", day(2024, 5, 14)), 2.0);
+        assert_eq!(m("days_since_entry", day(2024, 5, 20)), 6.0);
+        assert!(metric("days_since_entry", &entries, day(2024, 5, 1)).is_err());
+        assert!(metric("mood", &entries, day(2024, 5, 13)).is_err());
+    }
+}
@@ -0,0 +1,338 @@
+//! Where entries are kept. Everything that queries entries works on parsed
+//! `Entry`s, so it doesn't care; this is the seam for keeping them somewhere
+//! other than a directory of files (a zip archive, over SFTP, in memory).
+//!
+//! `FileStorage`, a directory of Markdown and text files, is the journal as
+//! jrni has always known it, and loads entries through `filedb`'s index.
+//! `MemoryStorage` keeps a journal in memory, for building one up
+//! programmatically without touching the disk. `Journal`, the `jrni`
+//! command and `jrni serve` all load entries through a `Storage`:
+//!
+//! ```
+//! use jrni::Journal;
+//! use jrni::config::Config;
+//! use jrni::storage::MemoryStorage;
+//!
+//! let storage = MemoryStorage::new()
+//!     .with_entry("standup", &["work"], "2024-05-10 09:00:00.000 +0000", "Shipped it.");
+//! let journal = Journal::with_storage("journal", Config::default(), storage);
+//! assert_eq!(journal.by_tag("work")?[0].get_id(), Some("standup"));
+//! # Ok::<(), jrni::error::Error>(())
+//! ```
+//!
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::thread;
+use std::time::{Duration, UNIX_EPOCH};
+
+use regex::Regex;
+
+use crate::error::{Error, ErrorKind, Result};
+use crate::filedb::{self, Entry};
+use crate::search::{self, Block};
+
+
+pub trait Storage {
+    /// Paths of every entry.
+    fn list(&self) -> Result<Vec<PathBuf>>;
+
+    /// The contents of the entry at `path`.
+    fn read(&self, path: &Path) -> Result<String>;
+
+    /// Replace the contents of the entry at `path`, creating it if need be.
+    fn write(&self, path: &Path, contents: &str) -> Result<()>;
+
+    /// Something that changes whenever the entry at `path` does, like its
+    /// modification time.
+    fn version(&self, path: &Path) -> Result<u64>;
+
+    /// Write a new entry at `path`, failing if there's one there already.
+    fn create(&self, path: &Path, contents: &str) -> Result<()> {
+        if self.list()?.iter().any(|p| p == path) {
+            bail!("{} exists already", path.display());
+        }
+        self.write(path, contents)
+    }
+
+    /// Call `on_change` with the paths of entries created, changed or
+    /// removed, checking every `interval`, until it returns false.
+    ///
+    /// The default polls `list` and `version`; backends with a way to be
+    /// notified of changes can do better.
+    fn watch(
+        &self,
+        interval: Duration,
+        on_change: &mut dyn FnMut(&[PathBuf]) -> Result<bool>,
+    ) -> Result<()> {
+        let mut seen = self.versions()?;
+        loop {
+            thread::sleep(interval);
+            let now = self.versions()?;
+            let mut changed: Vec<PathBuf> = now.iter()
+                .filter(|(p, v)| seen.get(*p) != Some(*v))
+                .map(|(p, _)| p.clone())
+                .chain(seen.keys().filter(|p| !now.contains_key(*p)).cloned())
+                .collect();
+            seen = now;
+            if changed.is_empty() {
+                continue;
+            }
+            changed.sort();
+            if !on_change(&changed)? {
+                return Ok(());
+            }
+        }
+    }
+
+    /// The version of every entry, by path.
+    fn versions(&self) -> Result<HashMap<PathBuf, u64>> {
+        self.list()?.into_iter()
+            .map(|p| self.version(&p).map(|v| (p, v)))
+            .collect()
+    }
+
+    /// Parse the entry at `path`.
+    fn entry(&self, path: &Path) -> Result<Entry> {
+        Ok(Entry::parse(path, &self.read(path)?))
+    }
+
+    /// Every entry, and an error for each that couldn't be read or whose
+    /// frontmatter has problems (which is still loaded).
+    fn entries_checked(&self) -> (Vec<Entry>, Vec<Error>) {
+        let paths = match self.list() {
+            Ok(paths) => paths,
+            Err(e) => return (Vec::new(), vec![e]),
+        };
+        let mut entries = Vec::new();
+        let mut errors = Vec::new();
+        for p in paths {
+            match self.read(&p) {
+                Ok(raw) => {
+                    let e = Entry::parse(&p, &raw);
+                    errors.extend(filedb::frontmatter_errors_in(&e, &raw));
+                    entries.push(e);
+                },
+                Err(e) => errors.push(ErrorKind::EntryUnreadable(p, e.to_string()).into()),
+            }
+        }
+        (entries, errors)
+    }
+
+    /// Every entry, or the first error for one that couldn't be read at
+    /// all. Entries with bad frontmatter are kept.
+    fn entries(&self) -> Result<Vec<Entry>> {
+        let (entries, errors) = self.entries_checked();
+        match errors.into_iter().find(|e| matches!(e.kind(), ErrorKind::EntryUnreadable(..))) {
+            Some(e) => Err(e),
+            None => Ok(entries),
+        }
+    }
+
+    /// The first entry, in the order of `list`, that `matches`. Fails if
+    /// none does and some entry couldn't be read, since it might have.
+    fn find(&self, matches: &dyn Fn(&Entry) -> bool) -> Result<Option<Entry>> {
+        let mut unread = None;
+        for p in self.list()? {
+            match self.entry(&p) {
+                Ok(e) if matches(&e) => return Ok(Some(e)),
+                Ok(_) => (),
+                Err(e) => { unread.get_or_insert(e); },
+            }
+        }
+        match unread {
+            Some(e) => Err(e),
+            None => Ok(None),
+        }
+    }
+
+    /// The id of every entry.
+    fn ids(&self) -> Result<HashSet<String>> {
+        Ok(self.entries()?.iter().filter_map(|e| e.get_id().map(String::from)).collect())
+    }
+
+    /// Those of `entries` with lines matching `pattern`; see `search::search`.
+    fn search<'a>(&self, entries: &'a [Entry], pattern: &Regex, context: usize)
+        -> Vec<(&'a Entry, Vec<Block>)>
+    {
+        search::search_with(entries, pattern, context, &|p| self.read(p))
+    }
+}
+
+/// A journal in a directory on disk.
+///
+pub struct FileStorage {
+    root: PathBuf,
+}
+
+impl FileStorage {
+    pub fn new(root: &Path) -> FileStorage {
+        FileStorage { root: root.to_owned() }
+    }
+}
+
+impl Storage for FileStorage {
+    /// Files that can't be walked are left out; `entries_checked` reports
+    /// them.
+    fn list(&self) -> Result<Vec<PathBuf>> {
+        Ok(filedb::entry_paths(&self.root).0)
+    }
+
+    fn read(&self, path: &Path) -> Result<String> {
+        Ok(fs::read_to_string(self.root.join(path))?)
+    }
+
+    fn write(&self, path: &Path, contents: &str) -> Result<()> {
+        let path = self.root.join(path);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        filedb::write_atomic(&path, contents)
+    }
+
+    fn version(&self, path: &Path) -> Result<u64> {
+        let m = fs::metadata(self.root.join(path))?;
+        let mtime = m.modified()?.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let stamp = format!("{}.{}:{}", mtime.as_secs(), mtime.subsec_nanos(), m.len());
+        Ok(filedb::content_hash(stamp.as_bytes()))
+    }
+
+    fn create(&self, path: &Path, contents: &str) -> Result<()> {
+        let path = self.root.join(path);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        filedb::create_atomic(&path, contents)
+    }
+
+    /// With their file metadata.
+    fn entry(&self, path: &Path) -> Result<Entry> {
+        Entry::from_path(&self.root.join(path))
+    }
+
+    /// Through the index, parsing only what's changed since it was written.
+    fn entries_checked(&self) -> (Vec<Entry>, Vec<Error>) {
+        filedb::load_entries_checked(&self.root)
+    }
+
+    /// Read in parallel, stopping at the first that matches.
+    fn find(&self, matches: &dyn Fn(&Entry) -> bool) -> Result<Option<Entry>> {
+        let path = match self.root.to_str() {
+            Some(p) => p,
+            None => bail!("can't walk {}: not UTF-8", self.root.display()),
+        };
+        let mut unread = None;
+        for e in filedb::walk_journal_iter(path, |p| Entry::from_path(&p)) {
+            match e {
+                Ok(e) if matches(&e) => return Ok(Some(e)),
+                Ok(_) => (),
+                Err(e) => { unread.get_or_insert(e); },
+            }
+        }
+        match unread {
+            Some(e) => Err(e),
+            None => Ok(None),
+        }
+    }
+
+    fn ids(&self) -> Result<HashSet<String>> {
+        filedb::ids(&self.root)
+    }
+
+    /// Streaming each file from disk, in parallel.
+    fn search<'a>(&self, entries: &'a [Entry], pattern: &Regex, context: usize)
+        -> Vec<(&'a Entry, Vec<Block>)>
+    {
+        search::search(entries, pattern, context)
+    }
+}
+
+/// A journal kept in memory. Paths are whatever they're written as; nothing
+/// is read from or written to disk.
+///
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    /// Contents and version of each entry. Every write bumps the version.
+    files: RwLock<BTreeMap<PathBuf, (String, u64)>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> MemoryStorage {
+        MemoryStorage::default()
+    }
+
+    /// Add an entry with the given contents.
+    ///
+    pub fn with(self, path: &str, contents: &str) -> MemoryStorage {
+        // Writing to memory can't fail.
+        self.write(Path::new(path), contents).unwrap();
+        self
+    }
+
+    /// Add an entry `<id>.md` with frontmatter as `jrni n` would write it.
+    ///
+    pub fn with_entry(self, id: &str, tags: &[&str], pubdate: &str, body: &str) -> MemoryStorage {
+        let contents = format!(
+            "tags: {}\nid: {}\npubdate: {}\n---\n\n{}\n", tags.join(","), id, pubdate, body);
+        self.with(&format!("{}.md", id), &contents)
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn list(&self) -> Result<Vec<PathBuf>> {
+        Ok(self.files.read().unwrap().keys().cloned().collect())
+    }
+
+    fn read(&self, path: &Path) -> Result<String> {
+        match self.files.read().unwrap().get(path) {
+            Some((contents, _)) => Ok(contents.clone()),
+            None => bail!("no entry at {}", path.display()),
+        }
+    }
+
+    fn write(&self, path: &Path, contents: &str) -> Result<()> {
+        let mut files = self.files.write().unwrap();
+        let version = files.get(path).map_or(0, |(_, v)| v + 1);
+        files.insert(path.to_owned(), (contents.to_owned(), version));
+        Ok(())
+    }
+
+    fn version(&self, path: &Path) -> Result<u64> {
+        match self.files.read().unwrap().get(path) {
+            Some((_, version)) => Ok(*version),
+            None => bail!("no entry at {}", path.display()),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_entries_report_bad_frontmatter() {
+        let storage = MemoryStorage::new()
+            .with_entry("a", &["x"], "2024-05-10 09:00:00.000 +0000", "fine")
+            .with("b.md", "tags: [unclosed\n---\n\nbody\n");
+        let (entries, errors) = storage.entries_checked();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].to_string().contains("b.md"));
+        // Bad frontmatter doesn't stop the entries loading.
+        assert_eq!(storage.entries().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn memory_writes_bump_versions_and_create_never_replaces() {
+        let storage = MemoryStorage::new().with("a.md", "one");
+        assert_eq!(storage.version(Path::new("a.md")).unwrap(), 0);
+        storage.write(Path::new("a.md"), "two").unwrap();
+        assert_eq!(storage.version(Path::new("a.md")).unwrap(), 1);
+        assert!(storage.create(Path::new("a.md"), "three").is_err());
+        assert_eq!(storage.read(Path::new("a.md")).unwrap(), "two");
+        storage.create(Path::new("b.md"), "new").unwrap();
+        assert_eq!(storage.list().unwrap(), vec![PathBuf::from("a.md"), PathBuf::from("b.md")]);
+    }
+}
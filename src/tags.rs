@@ -0,0 +1,145 @@
+//! Hierarchical tags. A tag like `work/project-x` sits under `work`, so
+//! filtering by `work` matches entries tagged `work/project-x` too, and
+//! counts of `work` include them.
+//!
+use std::collections::{BTreeMap, HashSet};
+
+
+/// `tag` in canonical form: each `/`-separated part trimmed, with empty
+/// parts dropped, so ` work / project-x/` is `work/project-x`.
+///
+pub fn normalize(tag: &str) -> String {
+    tag.split('/')
+        .map(|part| part.trim())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// `tags` normalized, less any that are empty or repeated.
+///
+pub fn normalize_tags<I, S>(tags: I) -> Vec<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut out: Vec<String> = Vec::new();
+    for t in tags {
+        let t = normalize(t.as_ref());
+        if !t.is_empty() && !out.contains(&t) {
+            out.push(t);
+        }
+    }
+    out
+}
+
+/// Tags from a comma-separated list, as given on the command line.
+///
+pub fn parse(list: &str) -> Vec<String> {
+    normalize_tags(list.split(','))
+}
+
+/// Whether `tag` is `parent` or somewhere beneath it.
+///
+pub fn is_within(tag: &str, parent: &str) -> bool {
+    tag.strip_prefix(parent).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// `tag` and each tag above it, from the top: `work`, `work/project-x`.
+///
+pub fn ancestors(tag: &str) -> impl Iterator<Item = &str> {
+    tag.match_indices('/').map(move |(i, _)| &tag[..i]).chain(Some(tag))
+}
+
+/// How many entries have each tag, counting an entry towards every tag
+/// above its own, but only once however many of its tags are beneath one.
+/// Takes the tags of each entry.
+///
+pub fn rolled_up_counts<'a, I>(entries: I) -> BTreeMap<String, usize>
+where
+    I: IntoIterator<Item = Vec<&'a str>>,
+{
+    let mut counts = BTreeMap::new();
+    for tags in entries {
+        let all: HashSet<&str> = tags.iter().flat_map(|t| ancestors(t)).collect();
+        for t in all {
+            *counts.entry(t.to_owned()).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+/// A tag in a tree of them, with its count from `rolled_up_counts`.
+///
+#[derive(Debug)]
+pub struct Node {
+    /// The last part of the tag.
+    pub name: String,
+    /// The whole tag.
+    pub tag: String,
+    pub count: usize,
+    pub children: Vec<Node>,
+}
+
+/// The tags in `counts` as a tree, in name order.
+///
+pub fn tree(counts: &BTreeMap<String, usize>) -> Vec<Node> {
+    let mut roots: Vec<Node> = Vec::new();
+    // A tag sorts after its parent, and after any sibling whose name sorts
+    // first, so each goes at the end of its parent's children, found by name.
+    'tags: for (tag, count) in counts.iter() {
+        let mut parts: Vec<&str> = tag.split('/').collect();
+        let name = parts.pop().unwrap_or_default();
+        let mut siblings = &mut roots;
+        for part in parts {
+            siblings = match siblings.binary_search_by(|n| n.name.as_str().cmp(part)) {
+                Ok(i) => &mut siblings[i].children,
+                Err(_) => continue 'tags,
+            };
+        }
+        siblings.push(Node {
+            name: name.to_owned(),
+            tag: tag.clone(),
+            count: *count,
+            children: Vec::new(),
+        });
+    }
+    roots
+}
+
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn tree_nests_children_in_name_order() {
+        let counts = rolled_up_counts(vec![vec!["a/b/c", "a-c"], vec!["a/a", "b"]]);
+        let roots = tree(&counts);
+        let names = |nodes: &[Node]| nodes.iter().map(|n| n.tag.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&roots), vec!["a", "a-c", "b"]);
+        assert_eq!(roots[0].count, 2);
+        assert_eq!(names(&roots[0].children), vec!["a/a", "a/b"]);
+        assert_eq!(names(&roots[0].children[1].children), vec!["a/b/c"]);
+    }
+
+    proptest! {
+        #[test]
+        fn normalize_is_idempotent(tag in "[ a-z/]{0,20}") {
+            let once = normalize(&tag);
+            prop_assert_eq!(normalize(&once), once.clone());
+            prop_assert!(once.split('/').all(|p| !p.is_empty() && p.trim() == p) || once.is_empty());
+            prop_assert!(is_within(&once, &once));
+        }
+
+        #[test]
+        fn normalize_tags_leaves_no_empties_or_repeats(tags in prop::collection::vec("[ a-z/]{0,8}", 0..8)) {
+            let normalized = normalize_tags(&tags);
+            let distinct: HashSet<&String> = normalized.iter().collect();
+            prop_assert_eq!(distinct.len(), normalized.len());
+            prop_assert!(normalized.iter().all(|t| !t.is_empty() && normalize(t) == *t));
+        }
+    }
+}
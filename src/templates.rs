@@ -0,0 +1,118 @@
+//! Entry templates, kept as ordinary entry files under `.templates/` in the
+//! journal directory, or wherever `template_dir` in the config says.
+//!
+//! A template's body seeds the body of new entries, and its tags are used
+//! when none are given on the command line. Templates may also declare
+//! prompts whose answers are recorded in the new entry's frontmatter:
+//!
+//! ```yaml
+//! prompts:
+//!   people: Who did you meet?
+//! ```
+//!
+//! or, equivalently, `prompts: ["Who did you meet? -> people"]`.
+//!
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_yaml::Value as YValue;
+
+use crate::error::Result;
+use crate::filedb::Entry;
+
+
+pub const TEMPLATE_DIR: &str = ".templates";
+
+/// Frontmatter fields that belong to a particular entry and so are dropped
+/// when turning an entry into a template.
+const ENTRY_FIELDS: &[&str] = &["id", "pubdate"];
+
+/// The default template directory of the journal at `jrnl_path`.
+///
+pub fn template_dir(jrnl_path: &Path) -> PathBuf {
+    jrnl_path.join(TEMPLATE_DIR)
+}
+
+pub fn template_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(format!("{}.md", name))
+}
+
+/// Names of all templates in `dir`, sorted.
+///
+pub fn list(dir: &Path) -> Result<Vec<String>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut names = Vec::new();
+    for f in fs::read_dir(dir)? {
+        let path = f?.path();
+        if path.extension().and_then(|e| e.to_str()) == Some("md") {
+            if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                names.push(stem.to_owned());
+            }
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+pub fn load(dir: &Path, name: &str) -> Result<Entry> {
+    let path = template_path(dir, name);
+    if !path.exists() {
+        bail!("no template named '{}'", name);
+    }
+    Entry::from_path(&path)
+}
+
+/// The (field, question) pairs a template asks about, in order.
+///
+pub fn prompts(template: &Entry) -> Result<Vec<(String, String)>> {
+    let mut prompts = Vec::new();
+
+    match template.frontmatter.extra.get("prompts") {
+        None | Some(YValue::Null) => (),
+        Some(YValue::Mapping(m)) => {
+            for (field, question) in m.iter() {
+                match (field.as_str(), question.as_str()) {
+                    (Some(f), Some(q)) => prompts.push((f.to_owned(), q.to_owned())),
+                    _ => bail!("template prompts must map fields to questions"),
+                }
+            }
+        },
+        Some(YValue::Sequence(s)) => {
+            for p in s.iter() {
+                let spec = p.as_str().unwrap_or("");
+                match spec.rfind("->") {
+                    Some(idx) => prompts.push((
+                        spec[idx + 2..].trim().to_owned(),
+                        spec[..idx].trim().to_owned())),
+                    None => bail!("template prompt '{}' should look like \"question -> field\"", spec),
+                }
+            }
+        },
+        Some(_) => bail!("template prompts should be a mapping or a list"),
+    }
+    Ok(prompts)
+}
+
+/// Turn the raw text of an entry into a template by dropping its
+/// entry-specific frontmatter fields.
+///
+pub fn from_entry_text(raw: &str) -> String {
+    let mut out = Vec::new();
+    let mut in_frontmatter = raw.lines().any(|l| l.trim() == "---");
+
+    for line in raw.lines() {
+        if in_frontmatter {
+            if line.trim() == "---" {
+                in_frontmatter = false;
+            } else if ENTRY_FIELDS.iter()
+                    .any(|f| line.starts_with(&format!("{}:", f))) {
+                continue;
+            }
+        }
+        out.push(line);
+    }
+    out.join("\n") + "\n"
+}
@@ -0,0 +1,338 @@
+//! Rendering entries for reading in a terminal, for `jrni show`: the
+//! frontmatter in a box, then the Markdown body with ANSI styling in place
+//! of its markup.
+//!
+use pulldown_cmark::{CodeBlockKind, Event, HeadingLevel, Parser, Tag};
+
+use crate::filedb::{self, Entry};
+use crate::html;
+
+
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const ITALIC: &str = "\x1b[3m";
+const UNDERLINE: &str = "\x1b[4m";
+const STRIKE: &str = "\x1b[9m";
+const CYAN: &str = "\x1b[36m";
+const YELLOW: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+/// Fields shown first in the header, in this order; the rest follow
+/// alphabetically.
+const HEADER_FIELDS: &[&str] = &["title", "pubdate", "id", "tags"];
+
+/// `e`'s frontmatter as a box `width` columns wide, with long values cut
+/// short.
+///
+pub fn header(e: &Entry, width: usize, color: bool) -> String {
+    let mut keys: Vec<String> = HEADER_FIELDS.iter().map(|k| k.to_string()).collect();
+    keys.extend(e.frontmatter.keys().into_iter().filter(|k| !HEADER_FIELDS.contains(&k.as_str())));
+    let rows: Vec<(String, String)> = keys.into_iter()
+        .filter_map(|k| {
+            let v = match k.as_str() {
+                "title" => e.title().to_owned(),
+                _ => filedb::inline_value(&e.frontmatter.get(&k)?),
+            };
+            match v.is_empty() {
+                true => None,
+                false => Some((k, v)),
+            }
+        })
+        .collect();
+
+    let inner = width.max(20) - 4;
+    let key_width = rows.iter().map(|(k, _)| k.chars().count()).max().unwrap_or(0);
+    let (bold, dim, reset) = match color {
+        true => (BOLD, DIM, RESET),
+        false => ("", "", ""),
+    };
+
+    let mut out = format!("┌{}┐\n", "─".repeat(inner + 2));
+    for (k, v) in rows.iter() {
+        let line = format!("{:<w$}  {}", k, v, w = key_width);
+        let line = truncate(&line, inner);
+        let pad = inner - line.chars().count();
+        let split = match line.is_char_boundary(k.len().min(line.len())) {
+            true => k.len().min(line.len()),
+            false => 0,
+        };
+        let (key, value) = line.split_at(split);
+        let style = if k == "title" { bold } else { "" };
+        out.push_str(&format!("│ {}{}{}{}{}{}{} │\n",
+            dim, key, reset, style, value, reset, " ".repeat(pad)));
+    }
+    out.push_str(&format!("└{}┘\n", "─".repeat(inner + 2)));
+    out
+}
+
+/// A Markdown `body` with its markup turned into ANSI styling, or with
+/// `color` off, into plain text laid out the same way.
+///
+pub fn markdown(body: &str, width: usize, color: bool) -> String {
+    let mut r = Renderer { color, width, ..Renderer::default() };
+    for event in Parser::new_ext(body, html::options()) {
+        r.event(event);
+    }
+    r.out.trim_end().to_owned() + "\n"
+}
+
+#[derive(Default)]
+struct Renderer {
+    out: String,
+    color: bool,
+    width: usize,
+    /// Styles in effect, innermost last.
+    styles: Vec<&'static str>,
+    /// Lists being rendered, innermost last, with the next number of each
+    /// ordered one.
+    lists: Vec<Option<u64>>,
+    quote_depth: usize,
+    in_code_block: bool,
+    at_line_start: bool,
+    /// Where the link being rendered goes, to show after its text.
+    link: Option<String>,
+}
+
+impl Renderer {
+    fn event(&mut self, event: Event) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) => self.text(&text),
+            Event::Code(code) => {
+                self.push_style(YELLOW);
+                self.text(&code);
+                self.pop_style();
+            },
+            Event::Html(raw) => self.text(&raw),
+            Event::FootnoteReference(label) => self.text(&format!("[^{}]", label)),
+            Event::SoftBreak | Event::HardBreak => self.newline(),
+            Event::Rule => {
+                self.block_gap();
+                let rule = "─".repeat(self.width.saturating_sub(self.indent().chars().count()));
+                self.push_style(DIM);
+                self.text(&rule);
+                self.pop_style();
+                self.newline();
+                self.newline();
+            },
+            Event::TaskListMarker(done) => self.text(if done { "[x] " } else { "[ ] " }),
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Paragraph => self.block_gap(),
+            Tag::Heading(level, _, _) => {
+                self.block_gap();
+                self.push_style(BOLD);
+                if level == HeadingLevel::H1 {
+                    self.push_style(UNDERLINE);
+                }
+            },
+            Tag::BlockQuote => {
+                self.block_gap();
+                self.quote_depth += 1;
+            },
+            Tag::CodeBlock(kind) => {
+                self.block_gap();
+                if let CodeBlockKind::Fenced(lang) = kind {
+                    if !lang.is_empty() {
+                        self.push_style(DIM);
+                        self.text(&format!("[{}]", lang));
+                        self.pop_style();
+                        self.newline();
+                    }
+                }
+                self.in_code_block = true;
+                self.push_style(CYAN);
+            },
+            Tag::List(first) => {
+                if self.lists.is_empty() {
+                    self.block_gap();
+                } else if !self.at_line_start {
+                    self.newline();
+                }
+                self.lists.push(first);
+            },
+            Tag::Item => {
+                if !self.at_line_start {
+                    self.newline();
+                }
+                let depth = self.lists.len().saturating_sub(1);
+                let bullet = match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{}. ", *n - 1)
+                    },
+                    _ => "• ".to_owned(),
+                };
+                let prefix = format!("{}{}", self.quote_prefix(), "  ".repeat(depth));
+                self.out.push_str(&prefix);
+                self.at_line_start = false;
+                self.text(&bullet);
+            },
+            Tag::Emphasis => self.push_style(ITALIC),
+            Tag::Strong => self.push_style(BOLD),
+            Tag::Strikethrough => self.push_style(STRIKE),
+            Tag::Link(_, url, _) => {
+                self.push_style(UNDERLINE);
+                self.link = Some(url.to_string());
+            },
+            Tag::Image(_, url, _) => {
+                self.text("[image: ");
+                self.link = Some(url.to_string());
+            },
+            Tag::Table(_) => self.block_gap(),
+            Tag::TableHead => self.push_style(BOLD),
+            Tag::TableRow => (),
+            Tag::TableCell => {
+                if !self.at_line_start {
+                    self.text(" │ ");
+                }
+            },
+            Tag::FootnoteDefinition(label) => {
+                self.block_gap();
+                self.text(&format!("[^{}]: ", label));
+            },
+        }
+    }
+
+    fn end(&mut self, tag: Tag) {
+        match tag {
+            Tag::Paragraph => self.newline(),
+            Tag::Heading(level, _, _) => {
+                if level == HeadingLevel::H1 {
+                    self.pop_style();
+                }
+                self.pop_style();
+                self.newline();
+            },
+            Tag::BlockQuote => self.quote_depth -= 1,
+            Tag::CodeBlock(_) => {
+                self.pop_style();
+                self.in_code_block = false;
+            },
+            Tag::List(_) => {
+                self.lists.pop();
+                if !self.at_line_start {
+                    self.newline();
+                }
+            },
+            Tag::Item => {
+                if !self.at_line_start {
+                    self.newline();
+                }
+            },
+            Tag::Emphasis | Tag::Strong | Tag::Strikethrough => self.pop_style(),
+            Tag::Link(..) => {
+                self.pop_style();
+                if let Some(url) = self.link.take() {
+                    self.push_style(DIM);
+                    self.text(&format!(" ({})", url));
+                    self.pop_style();
+                }
+            },
+            Tag::Image(..) => {
+                if let Some(url) = self.link.take() {
+                    self.text(&format!(" ({})]", url));
+                }
+            },
+            Tag::TableHead => {
+                self.pop_style();
+                self.newline();
+            },
+            Tag::TableRow => self.newline(),
+            Tag::Table(_) | Tag::TableCell | Tag::FootnoteDefinition(_) => (),
+        }
+    }
+
+    /// Write `text`, starting each new line with the indent of whatever
+    /// block it's in.
+    ///
+    fn text(&mut self, text: &str) {
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                self.newline();
+            }
+            if line.is_empty() {
+                continue;
+            }
+            if self.at_line_start {
+                let indent = self.indent();
+                self.out.push_str(&indent);
+                self.at_line_start = false;
+            }
+            self.out.push_str(line);
+        }
+    }
+
+    fn newline(&mut self) {
+        if self.color && !self.styles.is_empty() {
+            self.out.push_str(RESET);
+        }
+        self.out.push('\n');
+        self.at_line_start = true;
+        // Styles are reapplied on each line so that paging through the
+        // output with `less -R` doesn't lose them.
+        if self.color {
+            for s in self.styles.iter() {
+                self.out.push_str(s);
+            }
+        }
+    }
+
+    /// Leave a blank line before a block, unless it's first, or in a list
+    /// item.
+    ///
+    fn block_gap(&mut self) {
+        if self.out.is_empty() || !self.lists.is_empty() {
+            return;
+        }
+        if !self.at_line_start {
+            self.newline();
+        }
+        if !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+
+    fn indent(&self) -> String {
+        let list = match self.lists.len() {
+            0 => 0,
+            n => n * 2,
+        };
+        let code = if self.in_code_block { 4 } else { 0 };
+        format!("{}{}", self.quote_prefix(), " ".repeat(list + code))
+    }
+
+    fn quote_prefix(&self) -> String {
+        "│ ".repeat(self.quote_depth)
+    }
+
+    fn push_style(&mut self, style: &'static str) {
+        self.styles.push(style);
+        if self.color {
+            self.out.push_str(style);
+        }
+    }
+
+    fn pop_style(&mut self) {
+        self.styles.pop();
+        if self.color {
+            self.out.push_str(RESET);
+            for s in self.styles.iter() {
+                self.out.push_str(s);
+            }
+        }
+    }
+}
+
+/// `s` cut to at most `width` characters, ending in `…` if it was longer.
+///
+fn truncate(s: &str, width: usize) -> String {
+    match s.chars().count() > width {
+        true => s.chars().take(width.saturating_sub(1)).chain(Some('…')).collect(),
+        false => s.to_owned(),
+    }
+}
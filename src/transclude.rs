@@ -0,0 +1,64 @@
+//! `![[id]]` transclusion: inlining one entry's body into another when it's
+//! rendered, so that composite documents (a yearly review built from monthly
+//! ones) don't have to repeat themselves.
+//!
+use std::collections::HashMap;
+
+use regex::Regex;
+
+use crate::error::Result;
+use crate::filedb::Entry;
+
+
+pub struct Transcluder<'a> {
+    bodies: HashMap<&'a str, &'a str>,
+    pattern: Regex,
+}
+
+impl<'a> Transcluder<'a> {
+    /// Only `entries` can be transcluded; references to any other id are left
+    /// as they are. Exporters should pass only what they're publishing, so
+    /// that a public entry can't pull in a private one.
+    ///
+    pub fn new(entries: &'a [Entry]) -> Transcluder<'a> {
+        Transcluder {
+            bodies: entries.iter()
+                .filter_map(|e| Some((e.get_id()?, e.body.as_str())))
+                .collect(),
+            pattern: Regex::new(r"!\[\[([^\[\]]+)\]\]").unwrap(),
+        }
+    }
+
+    /// The body of `e` with transclusions expanded, recursively. Fails if an
+    /// entry ends up including itself.
+    ///
+    pub fn expand(&self, e: &Entry) -> Result<String> {
+        let mut stack: Vec<String> = e.get_id().map(String::from).into_iter().collect();
+        self.expand_body(&e.body, &mut stack)
+    }
+
+    fn expand_body(&self, body: &str, stack: &mut Vec<String>) -> Result<String> {
+        let mut out = String::with_capacity(body.len());
+        let mut last = 0;
+
+        for caps in self.pattern.captures_iter(body) {
+            let whole = caps.get(0).unwrap();
+            let id = caps[1].trim();
+            out.push_str(&body[last..whole.start()]);
+            last = whole.end();
+
+            match self.bodies.get(id) {
+                None => out.push_str(whole.as_str()),
+                Some(_) if stack.iter().any(|s| s == id) =>
+                    bail!("transclusion cycle: {} -> {}", stack.join(" -> "), id),
+                Some(inner) => {
+                    stack.push(id.to_owned());
+                    out.push_str(self.expand_body(inner, stack)?.trim());
+                    stack.pop();
+                },
+            }
+        }
+        out.push_str(&body[last..]);
+        Ok(out)
+    }
+}